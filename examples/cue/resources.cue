@@ -20,7 +20,7 @@ _resources: {for repo in _repositories {
 					advanced_security: {
 						status: "enabled"
 					}
-					security_and_analysis: {
+					secret_scanning: {
 						status: "enabled"
 					}
 					secret_scanning_push_protection: {
@@ -38,6 +38,7 @@ _resources: {for repo in _repositories {
 		#AutolinkReference & {
 			metadata: #metadata
 			spec: {
+				full_name:       repo
 				key_prefix:      "DV-"
 				url_template:    "https://otto-eg.atlassian.net/browse/DV-<num>"
 				is_alphanumeric: false
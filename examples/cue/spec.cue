@@ -17,9 +17,14 @@ package config
 #RepositorySpec: {
 	full_name:               string
 	delete_branch_on_merge?: bool
+	allow_auto_merge?:       bool
+	allow_squash_merge?:     bool
+	allow_merge_commit?:     bool
+	allow_rebase_merge?:     bool
+	allow_update_branch?:    bool
 	security_and_analysis?: {
 		advanced_security?:               #SecurityAndAnalysisStatus
-		security_and_analysis?:           #SecurityAndAnalysisStatus
+		secret_scanning?:                 #SecurityAndAnalysisStatus
 		secret_scanning_push_protection?: #SecurityAndAnalysisStatus
 		dependabot_security_updates?:     #SecurityAndAnalysisStatus
 		secret_scanning_validity_checks?: #SecurityAndAnalysisStatus
@@ -38,6 +43,7 @@ package config
 }
 
 #AutolinkReferenceEntry: {
+	full_name:       string
 	key_prefix:      string
 	url_template:    string
 	is_alphanumeric: bool
@@ -0,0 +1,48 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+
+use crate::domain::service::event_publisher::{DomainEvent, EventPublisher, EventSubject};
+use crate::ControllerError;
+
+/// Keeps the events for tests, the clones share them.
+#[derive(Clone, Default)]
+pub struct CapturingEventPublisher {
+    events: Arc<Mutex<Vec<(EventSubject, DomainEvent)>>>,
+}
+
+impl CapturingEventPublisher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn events(&self) -> Vec<DomainEvent> {
+        self.published()
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect()
+    }
+
+    /// The events with the objects they were published for.
+    pub fn published(&self) -> Vec<(EventSubject, DomainEvent)> {
+        self.events
+            .lock()
+            .expect("events should not be poisoned")
+            .clone()
+    }
+}
+
+#[async_trait]
+impl EventPublisher for CapturingEventPublisher {
+    async fn publish(
+        &self,
+        subject: &EventSubject,
+        event: &DomainEvent,
+    ) -> Result<(), ControllerError> {
+        self.events
+            .lock()
+            .expect("events should not be poisoned")
+            .push((subject.clone(), event.clone()));
+        Ok(())
+    }
+}
@@ -0,0 +1,647 @@
+use std::collections::BTreeMap;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::body::{to_bytes, Body};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// The token the stub accepts.
+pub const TOKEN: &str = "stub-token";
+const RATE_LIMIT: u32 = 5000;
+const REPOSITORY_MEDIA_TYPE: &str = "application/vnd.github.v3.repository+json";
+
+/// An embedded server that emulates the parts of the GitHub REST API the
+/// [`super::http_github_service::HttpGithubService`] uses, the tests point the service at
+/// [`GitHubApiStub::url`].
+///
+/// It records every request and answers like GitHub: 401 without the [`TOKEN`], 404 for unknown
+/// resources, paginated lists and `x-ratelimit-*` headers.
+pub struct GitHubApiStub {
+    address: SocketAddr,
+    state: Arc<Mutex<StubState>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub path: String,
+    pub query: BTreeMap<String, String>,
+    pub headers: HeaderMap,
+    pub body: Option<Value>,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+}
+
+#[derive(Default)]
+struct StubState {
+    /// The repositories as GitHub returns them by lower case full name
+    repositories: BTreeMap<String, Value>,
+    autolink_references: BTreeMap<String, Vec<Value>>,
+    last_autolink_reference_id: u32,
+    /// The role by lower case full team name and full name
+    team_permissions: BTreeMap<(String, String), String>,
+    collaborators: BTreeMap<String, Vec<Value>>,
+    rulesets: BTreeMap<String, Vec<Value>>,
+    rate_limit_remaining: u32,
+    /// Answer the next request with the status instead
+    next_status: Option<StatusCode>,
+    requests: Vec<RecordedRequest>,
+}
+
+impl GitHubApiStub {
+    /// Start the server on a free port of localhost, it stops with the runtime of the test.
+    pub fn start() -> Self {
+        let state = Arc::new(Mutex::new(StubState {
+            rate_limit_remaining: RATE_LIMIT,
+            ..Default::default()
+        }));
+        let router = Router::new()
+            .route("/rate_limit", get(rate_limit))
+            .route(
+                "/repos/{owner}/{repo}",
+                get(get_repository).post(update_repository),
+            )
+            .route("/repos/{owner}/{repo}/transfer", post(transfer_repository))
+            .route(
+                "/repos/{owner}/{repo}/autolinks",
+                get(get_autolink_references).post(add_autolink_reference),
+            )
+            .route(
+                "/repos/{owner}/{repo}/autolinks/{id}",
+                get(get_autolink_reference).delete(delete_autolink_reference),
+            )
+            .route(
+                "/orgs/{org}/teams/{team_slug}/repos/{owner}/{repo}",
+                get(get_team_permission)
+                    .put(update_team_permission)
+                    .delete(delete_team_permission),
+            )
+            .route("/orgs/{org}/repos", get(list_organization_repositories))
+            .route("/repos/{owner}/{repo}/teams", get(get_repository_teams))
+            .route(
+                "/repos/{owner}/{repo}/collaborators",
+                get(get_repository_collaborators),
+            )
+            .route(
+                "/repos/{owner}/{repo}/rulesets",
+                get(get_repository_rulesets),
+            )
+            .layer(middleware::from_fn_with_state(state.clone(), github))
+            .with_state(state.clone());
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("should bind a port");
+        listener
+            .set_nonblocking(true)
+            .expect("should set the listener non blocking");
+        let address = listener.local_addr().expect("should have an address");
+        tokio::spawn(async move {
+            axum_server::from_tcp(listener)
+                .serve(router.into_make_service())
+                .await
+                .expect("stub should serve");
+        });
+        Self { address, state }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Add the repository of `mocks/github.repository.json` as `full_name`.
+    pub fn with_repository(self, full_name: &str) -> Self {
+        let mut repository: Value =
+            serde_json::from_str(include_str!("../../mocks/github.repository.json"))
+                .expect("mock should be valid json");
+        repository["full_name"] = json!(full_name);
+        self.state().repositories.insert(key(full_name), repository);
+        self
+    }
+
+    pub fn with_autolink_reference(
+        self,
+        full_name: &str,
+        key_prefix: &str,
+        url_template: &str,
+    ) -> Self {
+        {
+            let mut state = self.state();
+            state.last_autolink_reference_id += 1;
+            let autolink_reference = json!({
+                "id": state.last_autolink_reference_id,
+                "key_prefix": key_prefix,
+                "url_template": url_template,
+                "is_alphanumeric": true,
+            });
+            state
+                .autolink_references
+                .entry(key(full_name))
+                .or_default()
+                .push(autolink_reference);
+        }
+        self
+    }
+
+    pub fn with_team_permission(
+        self,
+        full_team_name: &str,
+        full_name: &str,
+        role_name: &str,
+    ) -> Self {
+        self.state()
+            .team_permissions
+            .insert((key(full_team_name), key(full_name)), role_name.to_string());
+        self
+    }
+
+    pub fn with_collaborators(self, full_name: &str, collaborators: Vec<Value>) -> Self {
+        self.state()
+            .collaborators
+            .insert(key(full_name), collaborators);
+        self
+    }
+
+    pub fn with_rulesets(self, full_name: &str, rulesets: Vec<Value>) -> Self {
+        self.state().rulesets.insert(key(full_name), rulesets);
+        self
+    }
+
+    pub fn with_rate_limit_remaining(self, remaining: u32) -> Self {
+        self.state().rate_limit_remaining = remaining;
+        self
+    }
+
+    /// Answer the next request with the status, e.g. a `500` of GitHub.
+    pub fn respond_next_with(&self, status: StatusCode) {
+        self.state().next_status = Some(status);
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state().requests.clone()
+    }
+
+    /// The requests as `METHOD /path`, without the query.
+    pub fn calls(&self) -> Vec<String> {
+        self.state()
+            .requests
+            .iter()
+            .map(|request| format!("{} {}", request.method, request.path))
+            .collect()
+    }
+
+    pub fn repository(&self, full_name: &str) -> Option<Value> {
+        self.state().repositories.get(&key(full_name)).cloned()
+    }
+
+    pub fn team_permission(&self, full_team_name: &str, full_name: &str) -> Option<String> {
+        self.state()
+            .team_permissions
+            .get(&(key(full_team_name), key(full_name)))
+            .cloned()
+    }
+
+    fn state(&self) -> MutexGuard<'_, StubState> {
+        lock(&self.state)
+    }
+}
+
+type SharedState = Arc<Mutex<StubState>>;
+
+fn lock(state: &SharedState) -> MutexGuard<'_, StubState> {
+    state.lock().expect("stub state should not be poisoned")
+}
+
+fn key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+fn full_name_key(owner: &str, repo: &str) -> String {
+    key(&format!("{owner}/{repo}"))
+}
+
+/// Record the request, check the token and add the rate limit headers.
+async fn github(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    let (parts, body) = request.into_parts();
+    let bytes = to_bytes(body, usize::MAX)
+        .await
+        .expect("request body should be readable");
+    let Query(query) = Query::<BTreeMap<String, String>>::try_from_uri(&parts.uri)
+        .expect("query should be url encoded");
+    let next_status = {
+        let mut state = lock(&state);
+        state.requests.push(RecordedRequest {
+            method: parts.method.clone(),
+            path: parts.uri.path().to_string(),
+            query,
+            headers: parts.headers.clone(),
+            body: serde_json::from_slice(&bytes).ok(),
+        });
+        state.rate_limit_remaining = state.rate_limit_remaining.saturating_sub(1);
+        state.next_status.take()
+    };
+    let authorized = parts
+        .headers
+        .get(header::AUTHORIZATION)
+        .is_some_and(|value| value == format!("Bearer {}", TOKEN).as_str());
+
+    let mut response = if let Some(status) = next_status {
+        error(status, "injected by the stub")
+    } else if !authorized {
+        error(StatusCode::UNAUTHORIZED, "Bad credentials")
+    } else {
+        next.run(Request::from_parts(parts, Body::from(bytes)))
+            .await
+    };
+    let remaining = lock(&state).rate_limit_remaining;
+    let headers = response.headers_mut();
+    headers.insert("x-ratelimit-limit", HeaderValue::from(RATE_LIMIT));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(remaining));
+    headers.insert("x-ratelimit-resource", HeaderValue::from_static("core"));
+    response
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(json!({
+            "message": message,
+            "documentation_url": "https://docs.github.com/rest",
+        })),
+    )
+        .into_response()
+}
+
+fn not_found() -> Response {
+    error(StatusCode::NOT_FOUND, "Not Found")
+}
+
+#[derive(Deserialize)]
+struct Page {
+    per_page: Option<usize>,
+    page: Option<usize>,
+}
+
+/// Answer a list endpoint like GitHub, 30 items per page by default.
+fn paginate(items: Vec<Value>, page: &Page) -> Response {
+    let per_page = page.per_page.unwrap_or(30).clamp(1, 100);
+    let page = page.page.unwrap_or(1).max(1);
+    let items = items
+        .into_iter()
+        .skip((page - 1) * per_page)
+        .take(per_page)
+        .collect::<Vec<_>>();
+    Json(items).into_response()
+}
+
+async fn rate_limit(State(state): State<SharedState>) -> Response {
+    let remaining = lock(&state).rate_limit_remaining;
+    Json(json!({
+        "resources": {
+            "core": { "limit": RATE_LIMIT, "remaining": remaining },
+        },
+    }))
+    .into_response()
+}
+
+async fn get_repository(
+    State(state): State<SharedState>,
+    Path((owner, repo)): Path<(String, String)>,
+) -> Response {
+    match lock(&state).repositories.get(&full_name_key(&owner, &repo)) {
+        Some(repository) => Json(repository.clone()).into_response(),
+        None => not_found(),
+    }
+}
+
+/// GitHub accepts `POST` for the `PATCH` of a repository, a `name` renames it.
+async fn update_repository(
+    State(state): State<SharedState>,
+    Path((owner, repo)): Path<(String, String)>,
+    Json(update): Json<Value>,
+) -> Response {
+    let mut state = lock(&state);
+    let mut full_name = full_name_key(&owner, &repo);
+    if let Some(name) = update.get("name").and_then(Value::as_str) {
+        let new_full_name = format!("{}/{}", owner, name);
+        if let Err(response) = move_repository(&mut state, &full_name, &new_full_name) {
+            return response;
+        }
+        full_name = key(&new_full_name);
+    }
+    let Some(repository) = state.repositories.get_mut(&full_name) else {
+        return not_found();
+    };
+    if let (Some(repository), Some(update)) = (repository.as_object_mut(), update.as_object()) {
+        for (field, value) in update.iter().filter(|(_, value)| !value.is_null()) {
+            repository.insert(field.clone(), value.clone());
+        }
+    }
+    Json(repository.clone()).into_response()
+}
+
+/// GitHub answers `202`, the stub finishes the transfer right away.
+async fn transfer_repository(
+    State(state): State<SharedState>,
+    Path((owner, repo)): Path<(String, String)>,
+    Json(request): Json<Value>,
+) -> Response {
+    let Some(new_owner) = request.get("new_owner").and_then(Value::as_str) else {
+        return error(StatusCode::UNPROCESSABLE_ENTITY, "Validation Failed");
+    };
+    let new_name = request
+        .get("new_name")
+        .and_then(Value::as_str)
+        .unwrap_or(&repo);
+    let new_full_name = format!("{}/{}", new_owner, new_name);
+    let mut state = lock(&state);
+    match move_repository(&mut state, &full_name_key(&owner, &repo), &new_full_name) {
+        Ok(repository) => (StatusCode::ACCEPTED, Json(repository)).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Move the repository with everything that belongs to it.
+#[allow(clippy::result_large_err)]
+fn move_repository(
+    state: &mut StubState,
+    full_name: &str,
+    new_full_name: &str,
+) -> Result<Value, Response> {
+    let (from, to) = (key(full_name), key(new_full_name));
+    if state.repositories.contains_key(&to) {
+        return Err(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "name already exists on this account",
+        ));
+    }
+    let mut repository = state.repositories.remove(&from).ok_or_else(not_found)?;
+    repository["full_name"] = json!(new_full_name);
+    if let Some((_, name)) = new_full_name.split_once('/') {
+        repository["name"] = json!(name);
+    }
+    state.repositories.insert(to.clone(), repository.clone());
+    if let Some(autolink_references) = state.autolink_references.remove(&from) {
+        state
+            .autolink_references
+            .insert(to.clone(), autolink_references);
+    }
+    if let Some(collaborators) = state.collaborators.remove(&from) {
+        state.collaborators.insert(to.clone(), collaborators);
+    }
+    if let Some(rulesets) = state.rulesets.remove(&from) {
+        state.rulesets.insert(to.clone(), rulesets);
+    }
+    let team_permissions = std::mem::take(&mut state.team_permissions);
+    state.team_permissions = team_permissions
+        .into_iter()
+        .map(|((team, repository), role)| {
+            let repository = if repository == from {
+                to.clone()
+            } else {
+                repository
+            };
+            ((team, repository), role)
+        })
+        .collect();
+    Ok(repository)
+}
+
+async fn get_autolink_references(
+    State(state): State<SharedState>,
+    Path((owner, repo)): Path<(String, String)>,
+) -> Response {
+    let full_name = full_name_key(&owner, &repo);
+    let state = lock(&state);
+    if !state.repositories.contains_key(&full_name) {
+        return not_found();
+    }
+    let autolink_references = state
+        .autolink_references
+        .get(&full_name)
+        .cloned()
+        .unwrap_or_default();
+    Json(autolink_references).into_response()
+}
+
+async fn get_autolink_reference(
+    State(state): State<SharedState>,
+    Path((owner, repo, id)): Path<(String, String, u32)>,
+) -> Response {
+    let state = lock(&state);
+    state
+        .autolink_references
+        .get(&full_name_key(&owner, &repo))
+        .into_iter()
+        .flatten()
+        .find(|autolink_reference| autolink_reference["id"] == id)
+        .map(|autolink_reference| Json(autolink_reference.clone()).into_response())
+        .unwrap_or_else(not_found)
+}
+
+async fn add_autolink_reference(
+    State(state): State<SharedState>,
+    Path((owner, repo)): Path<(String, String)>,
+    Json(request): Json<Value>,
+) -> Response {
+    let full_name = full_name_key(&owner, &repo);
+    let mut state = lock(&state);
+    if !state.repositories.contains_key(&full_name) {
+        return not_found();
+    }
+    let duplicate = state
+        .autolink_references
+        .get(&full_name)
+        .into_iter()
+        .flatten()
+        .any(|existing| existing["key_prefix"] == request["key_prefix"]);
+    if duplicate {
+        let body = json!({
+            "message": "Validation Failed",
+            "errors": [{ "resource": "Autolink", "field": "key_prefix", "code": "already_exists" }],
+            "documentation_url": "https://docs.github.com/rest/repos/autolinks#create-an-autolink-reference-for-a-repository",
+        });
+        return (StatusCode::UNPROCESSABLE_ENTITY, Json(body)).into_response();
+    }
+    state.last_autolink_reference_id += 1;
+    let autolink_reference = json!({
+        "id": state.last_autolink_reference_id,
+        "key_prefix": request["key_prefix"],
+        "url_template": request["url_template"],
+        "is_alphanumeric": request.get("is_alphanumeric").cloned().unwrap_or(json!(true)),
+    });
+    state
+        .autolink_references
+        .entry(full_name)
+        .or_default()
+        .push(autolink_reference.clone());
+    (StatusCode::CREATED, Json(autolink_reference)).into_response()
+}
+
+async fn delete_autolink_reference(
+    State(state): State<SharedState>,
+    Path((owner, repo, id)): Path<(String, String, u32)>,
+) -> Response {
+    let mut state = lock(&state);
+    let Some(autolink_references) = state
+        .autolink_references
+        .get_mut(&full_name_key(&owner, &repo))
+    else {
+        return not_found();
+    };
+    let count = autolink_references.len();
+    autolink_references.retain(|autolink_reference| autolink_reference["id"] != id);
+    if autolink_references.len() == count {
+        return not_found();
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// Without the repository media type GitHub only answers `204` if the team has access.
+async fn get_team_permission(
+    State(state): State<SharedState>,
+    Path((org, team_slug, owner, repo)): Path<(String, String, String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    let full_name = full_name_key(&owner, &repo);
+    let state = lock(&state);
+    let (Some(role_name), Some(repository)) = (
+        state
+            .team_permissions
+            .get(&(key(&format!("{org}/{team_slug}")), full_name.clone())),
+        state.repositories.get(&full_name),
+    ) else {
+        return not_found();
+    };
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .unwrap_or_default();
+    if accept != REPOSITORY_MEDIA_TYPE {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+    let mut repository = repository.clone();
+    repository["role_name"] = json!(role_name);
+    Json(repository).into_response()
+}
+
+async fn update_team_permission(
+    State(state): State<SharedState>,
+    Path((org, team_slug, owner, repo)): Path<(String, String, String, String)>,
+    Json(request): Json<Value>,
+) -> Response {
+    let full_name = full_name_key(&owner, &repo);
+    let mut state = lock(&state);
+    if !state.repositories.contains_key(&full_name) {
+        return not_found();
+    }
+    let Some(permission) = request["permission"].as_str() else {
+        return error(StatusCode::UNPROCESSABLE_ENTITY, "Validation Failed");
+    };
+    state.team_permissions.insert(
+        (key(&format!("{org}/{team_slug}")), full_name),
+        permission.to_string(),
+    );
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn delete_team_permission(
+    State(state): State<SharedState>,
+    Path((org, team_slug, owner, repo)): Path<(String, String, String, String)>,
+) -> Response {
+    let full_name = full_name_key(&owner, &repo);
+    let mut state = lock(&state);
+    if !state.repositories.contains_key(&full_name) {
+        return not_found();
+    }
+    state
+        .team_permissions
+        .remove(&(key(&format!("{org}/{team_slug}")), full_name));
+    StatusCode::NO_CONTENT.into_response()
+}
+
+async fn list_organization_repositories(
+    State(state): State<SharedState>,
+    Path(org): Path<String>,
+    Query(page): Query<Page>,
+) -> Response {
+    let prefix = format!("{}/", key(&org));
+    let repositories = lock(&state)
+        .repositories
+        .iter()
+        .filter(|(full_name, _)| full_name.starts_with(&prefix))
+        .map(|(_, repository)| repository.clone())
+        .collect();
+    paginate(repositories, &page)
+}
+
+async fn get_repository_teams(
+    State(state): State<SharedState>,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(page): Query<Page>,
+) -> Response {
+    let full_name = full_name_key(&owner, &repo);
+    let state = lock(&state);
+    if !state.repositories.contains_key(&full_name) {
+        return not_found();
+    }
+    let teams = state
+        .team_permissions
+        .iter()
+        .filter(|((_, repository), _)| *repository == full_name)
+        .filter_map(|((team, _), permission)| {
+            let (_, slug) = team.split_once('/')?;
+            Some(json!({ "slug": slug, "permission": permission }))
+        })
+        .collect();
+    paginate(teams, &page)
+}
+
+async fn get_repository_collaborators(
+    State(state): State<SharedState>,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(page): Query<Page>,
+) -> Response {
+    list(
+        &state,
+        &full_name_key(&owner, &repo),
+        |state| &state.collaborators,
+        &page,
+    )
+}
+
+async fn get_repository_rulesets(
+    State(state): State<SharedState>,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(page): Query<Page>,
+) -> Response {
+    list(
+        &state,
+        &full_name_key(&owner, &repo),
+        |state| &state.rulesets,
+        &page,
+    )
+}
+
+fn list(
+    state: &SharedState,
+    full_name: &str,
+    items: impl Fn(&StubState) -> &BTreeMap<String, Vec<Value>>,
+    page: &Page,
+) -> Response {
+    let state = lock(state);
+    if !state.repositories.contains_key(full_name) {
+        return not_found();
+    }
+    let items = items(&state).get(full_name).cloned().unwrap_or_default();
+    paginate(items, page)
+}
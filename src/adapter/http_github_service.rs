@@ -1,31 +1,42 @@
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
-use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use tracing::{event, instrument};
-use ureq::Request;
+use ureq::{Request, Response};
 
 use crate::domain::model::autolink_reference::{
     AutolinkReferenceRequest, AutolinkReferenceResponse,
 };
-use crate::domain::model::repository::RepositoryResponse;
+use crate::domain::model::repository::{
+    CollaboratorPermission, RepositoryResponse, RulesetResponse, TeamPermission,
+};
+use crate::domain::model::split_full_name;
 use crate::domain::service::github_service::GitHubService;
-use crate::ControllerError;
+use crate::metrics::GitHubApiMetrics;
+use crate::{ControllerError, GitHubError};
 
 #[derive(Clone, Debug)]
 pub struct HttpGithubService {
     client: ureq::Agent,
+    base_url: String,
     github_token: String,
+    metrics: Option<GitHubApiMetrics>,
 }
 
 // as an alternate: https://docs.rs/reqwest/latest/reqwest/
 
 impl HttpGithubService {
-    const HOST: &'static str = "https://api.github.com";
+    const BASE_URL: &'static str = "https://api.github.com";
 
     pub fn from_env() -> Self {
         let github_token = std::env::var("GITHUB_TOKEN").expect("GITHUB_TOKEN is not set");
-        Self::new(github_token)
+        let service = Self::new(github_token);
+        match std::env::var("GITHUB_API_URL") {
+            Ok(base_url) => service.with_base_url(base_url),
+            Err(_) => service,
+        }
     }
 
     pub fn new(github_token: String) -> Self {
@@ -35,166 +46,319 @@ impl HttpGithubService {
             .build();
         Self {
             client,
+            base_url: Self::BASE_URL.to_string(),
             github_token,
+            metrics: None,
         }
     }
 
+    /// Use another API, e.g. of a GitHub Enterprise Server like `https://github.example.com/api/v3`.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Record metrics for every request to the GitHub API.
+    pub fn with_metrics(mut self, metrics: GitHubApiMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     fn url(&self, path: String) -> String {
-        format!("{}{}", Self::HOST, path.as_str())
+        format!("{}{}", self.base_url, path.as_str())
     }
 
-    fn get(&self, path: String) -> Request {
-        self.client
-            .get(self.url(path).as_str())
-            .set("Authorization", &format!("Bearer {}", self.github_token))
+    fn request(&self, endpoint: &'static str, request: Request) -> GitHubRequest<'_> {
+        GitHubRequest {
+            request: request.set("Authorization", &format!("Bearer {}", self.github_token)),
+            endpoint,
+            server_address: server_address(&self.base_url),
+            metrics: self.metrics.as_ref(),
+        }
     }
 
-    fn delete(&self, path: String) -> Request {
-        self.client
-            .delete(self.url(path).as_str())
-            .set("Authorization", &format!("Bearer {}", self.github_token))
+    fn get(&self, endpoint: &'static str, path: String) -> GitHubRequest<'_> {
+        self.request(endpoint, self.client.get(self.url(path).as_str()))
     }
 
-    fn post(&self, path: String) -> Request {
-        self.client
-            .post(self.url(path).as_str())
-            .set("Authorization", &format!("Bearer {}", self.github_token))
+    fn delete(&self, endpoint: &'static str, path: String) -> GitHubRequest<'_> {
+        self.request(endpoint, self.client.delete(self.url(path).as_str()))
     }
 
-    fn put(&self, path: String) -> Request {
-        self.client
-            .put(self.url(path).as_str())
-            .set("Authorization", &format!("Bearer {}", self.github_token))
+    fn post(&self, endpoint: &'static str, path: String) -> GitHubRequest<'_> {
+        self.request(endpoint, self.client.post(self.url(path).as_str()))
     }
 
-    fn box_error(error: ureq::Error) -> ControllerError {
-        ControllerError::HttpError(Box::new(error))
+    fn put(&self, endpoint: &'static str, path: String) -> GitHubRequest<'_> {
+        self.request(endpoint, self.client.put(self.url(path).as_str()))
+    }
+
+    /// Follow the pages of a list endpoint until a page is not full.
+    fn get_all_pages<T: DeserializeOwned>(
+        &self,
+        endpoint: &'static str,
+        path: String,
+    ) -> Result<Vec<T>, ControllerError> {
+        const PER_PAGE: usize = 100;
+        let mut items = Vec::new();
+        for page in 1.. {
+            let page: Vec<T> = self
+                .get(endpoint, path.clone())
+                .query("per_page", &PER_PAGE.to_string())
+                .query("page", &page.to_string())
+                .call()
+                .map_err(Self::map_error)?
+                .into_json()
+                .map_err(ControllerError::IoError)?;
+            let last = page.len() < PER_PAGE;
+            items.extend(page);
+            if last {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// Error responses become a [`GitHubError`] with the details of the body.
+    fn map_error(error: ureq::Error) -> ControllerError {
+        match error {
+            ureq::Error::Status(status, response) => {
+                ControllerError::GitHubError(github_error(status, response))
+            }
+            error => ControllerError::HttpError(Box::new(error)),
+        }
+    }
+}
+
+/// The host of the base url, e.g. `api.github.com`.
+fn server_address(base_url: &str) -> &str {
+    let address = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest);
+    address.split('/').next().unwrap_or(address)
+}
+
+fn github_error(status: u16, response: Response) -> GitHubError {
+    let rate_limit_remaining = response
+        .header("x-ratelimit-remaining")
+        .and_then(|remaining| remaining.parse().ok());
+    let retry_after = response
+        .header("retry-after")
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs)
+        .or_else(|| {
+            // the reset is only relevant if the rate limit is exhausted
+            let reset: u64 = response.header("x-ratelimit-reset")?.parse().ok()?;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+            (rate_limit_remaining == Some(0))
+                .then(|| Duration::from_secs(reset.saturating_sub(now)))
+        });
+    let status_text = response.status_text().to_string();
+    let body: ErrorResponse = response.into_json().unwrap_or_default();
+    GitHubError {
+        status,
+        message: body.message.unwrap_or(status_text),
+        documentation_url: body.documentation_url,
+        errors: body.errors.iter().map(ErrorDetail::describe).collect(),
+        rate_limit_remaining,
+        retry_after,
     }
 }
 
 #[async_trait]
 impl GitHubService for HttpGithubService {
-    #[instrument]
+    #[instrument(skip(self))]
+    async fn verify_credentials(&self) -> Result<(), ControllerError> {
+        // the rate limit endpoint works for every kind of token and does not count against the limit
+        self.get("/rate_limit", "/rate_limit".to_string())
+            .call()
+            .map(|_| ())
+            .map_err(Self::map_error)
+    }
+
+    #[instrument(skip(self))]
     async fn create_repository(
         &self,
         full_name: &str,
         repository: &RepositoryResponse,
     ) -> Result<RepositoryResponse, ControllerError> {
-        self.post(format!("/repos/{full_name}"))
+        self.post("/repos/{owner}/{repo}", format!("/repos/{full_name}"))
             .send_json(ureq::json!(repository))
-            .map_err(Self::box_error)?
+            .map_err(Self::map_error)?
             .into_json()
             .map_err(ControllerError::IoError)
     }
 
-    #[instrument]
+    #[instrument(skip(self))]
     async fn get_repository(
         &self,
         full_name: &str,
     ) -> Result<Option<RepositoryResponse>, ControllerError> {
         event!(tracing::Level::INFO, "get_repository: {}", full_name);
-        let result = self.get(format!("/repos/{full_name}")).call();
+        let result = self
+            .get("/repos/{owner}/{repo}", format!("/repos/{full_name}"))
+            .call();
         match result {
             Ok(response) => Ok(Some(
                 response.into_json().map_err(ControllerError::IoError)?,
             )),
             Err(ureq::Error::Status(404, _)) => Ok(None),
-            Err(e) => Err(Self::box_error(e)),
+            Err(e) => Err(Self::map_error(e)),
         }
     }
 
-    #[instrument]
+    #[instrument(skip(self))]
     async fn update_repository(
         &self,
         full_name: &str,
         repository: &RepositoryResponse,
     ) -> Result<RepositoryResponse, ControllerError> {
         let result = self
-            .post(format!("/repos/{full_name}"))
+            .post("/repos/{owner}/{repo}", format!("/repos/{full_name}"))
             .send_json(ureq::json!(repository));
         match result {
             Ok(response) => Ok(response.into_json().map_err(ControllerError::IoError)?),
             Err(ureq::Error::Status(404, _)) => Err(ControllerError::NotFound),
-            Err(e) => Err(Self::box_error(e)),
+            Err(e) => Err(Self::map_error(e)),
         }
     }
 
-    #[instrument]
+    #[instrument(skip(self))]
     async fn archive_repository(&self, _full_name: &str) -> Result<(), ControllerError> {
         Ok(())
     }
 
-    #[instrument]
+    #[instrument(skip(self))]
+    async fn rename_repository(
+        &self,
+        full_name: &str,
+        new_name: &str,
+    ) -> Result<RepositoryResponse, ControllerError> {
+        let result = self
+            .post("/repos/{owner}/{repo}", format!("/repos/{full_name}"))
+            .send_json(ureq::json!({ "name": new_name }));
+        match result {
+            Ok(response) => Ok(response.into_json().map_err(ControllerError::IoError)?),
+            Err(ureq::Error::Status(404, _)) => Err(ControllerError::NotFound),
+            Err(e) => Err(Self::map_error(e)),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn transfer_repository(
+        &self,
+        full_name: &str,
+        new_owner: &str,
+        new_name: &str,
+    ) -> Result<(), ControllerError> {
+        let result = self
+            .post(
+                "/repos/{owner}/{repo}/transfer",
+                format!("/repos/{full_name}/transfer"),
+            )
+            .send_json(ureq::json!({ "new_owner": new_owner, "new_name": new_name }));
+        match result {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(404, _)) => Err(ControllerError::NotFound),
+            Err(e) => Err(Self::map_error(e)),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn list_organization_repositories(
+        &self,
+        org: &str,
+    ) -> Result<Vec<String>, ControllerError> {
+        let repositories: Vec<OrganizationRepositoryResponse> =
+            self.get_all_pages("/orgs/{org}/repos", format!("/orgs/{org}/repos"))?;
+        Ok(repositories
+            .into_iter()
+            .map(|repository| repository.full_name)
+            .collect())
+    }
+
+    #[instrument(skip(self))]
     async fn get_autolink_references(
         &self,
         full_name: &str,
     ) -> Result<Vec<AutolinkReferenceResponse>, ControllerError> {
-        self.get(format!("/repos/{full_name}/autolinks"))
-            .call()
-            .map_err(Self::box_error)?
-            .into_json()
-            .map_err(ControllerError::IoError)
+        self.get(
+            "/repos/{owner}/{repo}/autolinks",
+            format!("/repos/{full_name}/autolinks"),
+        )
+        .call()
+        .map_err(Self::map_error)?
+        .into_json()
+        .map_err(ControllerError::IoError)
     }
 
-    #[instrument]
+    #[instrument(skip(self))]
     async fn get_autolink_reference(
         &self,
         full_name: &str,
         id: &u32,
     ) -> Result<AutolinkReferenceResponse, ControllerError> {
         let result = self
-            .get(format!("/repos/{full_name}/autolinks/{id}"))
+            .get(
+                "/repos/{owner}/{repo}/autolinks/{autolink_id}",
+                format!("/repos/{full_name}/autolinks/{id}"),
+            )
             .call();
         match result {
             Ok(response) => Ok(response.into_json().map_err(ControllerError::IoError)?),
             Err(ureq::Error::Status(404, _)) => Err(ControllerError::NotFound),
-            Err(e) => Err(Self::box_error(e)),
+            Err(e) => Err(Self::map_error(e)),
         }
     }
 
-    #[instrument]
+    #[instrument(skip(self))]
     async fn add_autolink_reference(
         &self,
         full_name: &str,
         autolink_reference: &AutolinkReferenceRequest,
     ) -> Result<AutolinkReferenceResponse, ControllerError> {
-        self.post(format!("/repos/{full_name}/autolinks"))
-            .send_json(ureq::json!(autolink_reference))
-            .map_err(Self::box_error)?
-            .into_json()
-            .map_err(ControllerError::IoError)
+        self.post(
+            "/repos/{owner}/{repo}/autolinks",
+            format!("/repos/{full_name}/autolinks"),
+        )
+        .send_json(ureq::json!(autolink_reference))
+        .map_err(Self::map_error)?
+        .into_json()
+        .map_err(ControllerError::IoError)
     }
 
-    #[instrument]
+    #[instrument(skip(self))]
     async fn delete_autolink_references(
         &self,
         full_name: &str,
         autolink_reference_id: &u32,
     ) -> Result<(), ControllerError> {
         let result = self
-            .delete(format!(
-                "/repos/{full_name}/autolinks/{autolink_reference_id}"
-            ))
+            .delete(
+                "/repos/{owner}/{repo}/autolinks/{autolink_id}",
+                format!("/repos/{full_name}/autolinks/{autolink_reference_id}"),
+            )
             .call()
             .map(|_| ());
         match result {
             Ok(()) => Ok(()),
             Err(ureq::Error::Status(404, _)) => Ok(()),
-            Err(e) => Err(Self::box_error(e)),
+            Err(e) => Err(Self::map_error(e)),
         }
     }
 
-    #[instrument]
+    #[instrument(skip(self))]
     async fn get_team_permission(
         &self,
         full_name: &str,
         full_team_name: &str,
     ) -> Result<Option<String>, ControllerError> {
-        let (org, team_slug) = full_team_name
-            .split_once('/')
-            .expect("team name should be valid");
+        let (org, team_slug) = split_full_name(full_team_name)?;
         let result = self
-            .get(format!("/orgs/{org}/teams/{team_slug}/repos/{full_name}"))
+            .get(
+                "/orgs/{org}/teams/{team_slug}/repos/{owner}/{repo}",
+                format!("/orgs/{org}/teams/{team_slug}/repos/{full_name}"),
+            )
             .set("Accept", "application/vnd.github.v3.repository+json")
             .call();
         match result {
@@ -204,41 +368,215 @@ impl GitHubService for HttpGithubService {
                 Ok(Some(repository.role_name))
             }
             Err(ureq::Error::Status(404, _)) => Ok(None),
-            Err(error) => Err(Self::box_error(error)),
+            Err(error) => Err(Self::map_error(error)),
         }
     }
 
-    #[instrument]
+    #[instrument(skip(self))]
     async fn update_team_permission(
         &self,
         full_name: &str,
         full_team_name: &str,
         role_name: &str,
     ) -> Result<(), ControllerError> {
-        let (org, team_slug) = full_team_name
-            .split_once('/')
-            .expect("team name should be valid");
-        self.put(format!("/orgs/{org}/teams/{team_slug}/repos/{full_name}"))
-            .set("Accept", "application/vnd.github.v3.repository+json")
-            .send_json(ureq::json!({"permission": role_name}))
-            .map(|_| ())
-            .map_err(Self::box_error)
+        let (org, team_slug) = split_full_name(full_team_name)?;
+        self.put(
+            "/orgs/{org}/teams/{team_slug}/repos/{owner}/{repo}",
+            format!("/orgs/{org}/teams/{team_slug}/repos/{full_name}"),
+        )
+        .set("Accept", "application/vnd.github.v3.repository+json")
+        .send_json(ureq::json!({"permission": role_name}))
+        .map(|_| ())
+        .map_err(Self::map_error)
     }
 
-    #[instrument]
+    #[instrument(skip(self))]
     async fn delete_team_permission(
         &self,
         full_name: &str,
         full_team_name: &str,
     ) -> Result<(), ControllerError> {
-        let (org, team_slug) = full_team_name
-            .split_once('/')
-            .expect("team name should be valid");
-        self.delete(format!("/orgs/{org}/teams/{team_slug}/repos/{full_name}"))
-            .set("Accept", "application/vnd.github.v3.repository+json")
-            .call()
-            .map(|_| ())
-            .map_err(Self::box_error)
+        let (org, team_slug) = split_full_name(full_team_name)?;
+        self.delete(
+            "/orgs/{org}/teams/{team_slug}/repos/{owner}/{repo}",
+            format!("/orgs/{org}/teams/{team_slug}/repos/{full_name}"),
+        )
+        .set("Accept", "application/vnd.github.v3.repository+json")
+        .call()
+        .map(|_| ())
+        .map_err(Self::map_error)
+    }
+
+    #[instrument(skip(self))]
+    async fn get_repository_teams(
+        &self,
+        full_name: &str,
+    ) -> Result<Vec<TeamPermission>, ControllerError> {
+        let (org, _) = split_full_name(full_name)?;
+        let teams: Vec<RepositoryTeamResponse> = self.get_all_pages(
+            "/repos/{owner}/{repo}/teams",
+            format!("/repos/{full_name}/teams"),
+        )?;
+        Ok(teams
+            .into_iter()
+            .map(|team| TeamPermission {
+                org: org.to_string(),
+                team_slug: team.slug,
+                permission: team.permission,
+            })
+            .collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn get_repository_collaborators(
+        &self,
+        full_name: &str,
+    ) -> Result<Vec<CollaboratorPermission>, ControllerError> {
+        self.get_all_pages(
+            "/repos/{owner}/{repo}/collaborators",
+            format!("/repos/{full_name}/collaborators?affiliation=direct"),
+        )
+    }
+
+    #[instrument(skip(self))]
+    async fn get_repository_rulesets(
+        &self,
+        full_name: &str,
+    ) -> Result<Vec<RulesetResponse>, ControllerError> {
+        self.get_all_pages(
+            "/repos/{owner}/{repo}/rulesets",
+            format!("/repos/{full_name}/rulesets"),
+        )
+    }
+}
+
+/// A request to the GitHub API that is traced and records metrics for its endpoint template when sent.
+struct GitHubRequest<'a> {
+    request: Request,
+    endpoint: &'static str,
+    server_address: &'a str,
+    metrics: Option<&'a GitHubApiMetrics>,
+}
+
+#[allow(clippy::result_large_err)]
+impl GitHubRequest<'_> {
+    fn set(self, header: &str, value: &str) -> Self {
+        Self {
+            request: self.request.set(header, value),
+            ..self
+        }
+    }
+
+    fn query(self, param: &str, value: &str) -> Self {
+        Self {
+            request: self.request.query(param, value),
+            ..self
+        }
+    }
+
+    fn call(self) -> Result<Response, ureq::Error> {
+        self.send(Request::call)
+    }
+
+    fn send_json(self, data: impl Serialize) -> Result<Response, ureq::Error> {
+        self.send(|request| request.send_json(data))
+    }
+
+    /// Send the request in a client span, so the traces show the calls below a reconciliation.
+    fn send(
+        self,
+        send: impl FnOnce(Request) -> Result<Response, ureq::Error>,
+    ) -> Result<Response, ureq::Error> {
+        let method = self.request.method().to_string();
+        let span = tracing::info_span!(
+            "github_request",
+            otel.name = format!("{} {}", method, self.endpoint),
+            otel.kind = "client",
+            otel.status_code = tracing::field::Empty,
+            http.request.method = method,
+            http.response.status_code = tracing::field::Empty,
+            server.address = self.server_address,
+            url.template = self.endpoint,
+        );
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = send(self.request);
+        let response = match &result {
+            Ok(response) => Some(response),
+            Err(ureq::Error::Status(_, response)) => Some(response),
+            Err(_) => None,
+        };
+        match response {
+            Some(response) => {
+                span.record("http.response.status_code", response.status());
+            }
+            None => {
+                span.record("otel.status_code", "ERROR");
+            }
+        }
+        if let Some(metrics) = self.metrics {
+            metrics.requested(
+                &method,
+                self.endpoint,
+                response.map(Response::status),
+                started.elapsed(),
+                response
+                    .and_then(|response| response.header("x-ratelimit-remaining"))
+                    .and_then(|remaining| remaining.parse().ok()),
+            );
+        }
+        result
+    }
+}
+
+// https://docs.github.com/en/rest/using-the-rest-api/troubleshooting-the-rest-api
+
+#[derive(Deserialize, Default)]
+struct ErrorResponse {
+    message: Option<String>,
+    documentation_url: Option<String>,
+    #[serde(default)]
+    errors: Vec<ErrorDetail>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ErrorDetail {
+    Message(String),
+    Field {
+        resource: Option<String>,
+        field: Option<String>,
+        code: Option<String>,
+        message: Option<String>,
+    },
+}
+
+impl ErrorDetail {
+    fn describe(&self) -> String {
+        match self {
+            ErrorDetail::Message(message) => message.clone(),
+            ErrorDetail::Field {
+                message: Some(message),
+                ..
+            } => message.clone(),
+            ErrorDetail::Field {
+                resource,
+                field,
+                code,
+                ..
+            } => {
+                let path = [resource, field]
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(".");
+                match code {
+                    Some(code) => format!("{} {}", path, code),
+                    None => path,
+                }
+            }
+        }
     }
 }
 
@@ -247,10 +585,396 @@ struct TeamPermissionRepositoryResponse {
     role_name: String,
 }
 
+#[derive(Deserialize)]
+struct OrganizationRepositoryResponse {
+    full_name: String,
+}
+
+#[derive(Deserialize)]
+struct RepositoryTeamResponse {
+    slug: String,
+    permission: String,
+}
+
 #[cfg(test)]
 mod tests {
+    use axum::http::StatusCode;
+    use prometheus::{Encoder, Registry, TextEncoder};
+    use serde_json::json;
+
+    use crate::adapter::github_api_stub::{GitHubApiStub, TOKEN};
     use crate::adapter::http_github_service::HttpGithubService;
+    use crate::domain::model::autolink_reference::AutolinkReferenceRequest;
+    use crate::domain::model::repository::RepositoryResponse;
     use crate::domain::service::github_service::GitHubService;
+    use crate::metrics::Metrics;
+    use crate::{ControllerError, ErrorClass, GitHubError};
+
+    const FULL_NAME: &str = "otto-ec/pdh-da_test";
+    const TEAM: &str = "otto-ec/pdh-da";
+
+    // the service blocks a worker on the requests, the stub answers on the other one
+
+    fn github_service(stub: &GitHubApiStub) -> HttpGithubService {
+        HttpGithubService::new(TOKEN.to_string()).with_base_url(stub.url())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn get_repository_sends_token_and_reads_response() {
+        let stub = GitHubApiStub::start().with_repository(FULL_NAME);
+
+        let repository = github_service(&stub)
+            .get_repository(FULL_NAME)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(repository.delete_branch_on_merge, Some(true));
+        let requests = stub.requests();
+        assert_eq!(stub.calls(), vec![format!("GET /repos/{}", FULL_NAME)]);
+        assert_eq!(
+            requests[0].header("authorization"),
+            Some(format!("Bearer {}", TOKEN).as_str())
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn get_repository_maps_404_to_none() {
+        let stub = GitHubApiStub::start();
+
+        let repository = github_service(&stub).get_repository(FULL_NAME).await;
+
+        assert!(matches!(repository, Ok(None)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn update_repository_sends_settings() {
+        let stub = GitHubApiStub::start().with_repository(FULL_NAME);
+        let update = RepositoryResponse {
+            allow_auto_merge: Some(true),
+            ..Default::default()
+        };
+
+        let repository = github_service(&stub)
+            .update_repository(FULL_NAME, &update)
+            .await
+            .unwrap();
+
+        assert_eq!(repository.allow_auto_merge, Some(true));
+        let body = stub.requests()[0].body.clone().unwrap();
+        assert_eq!(body["allow_auto_merge"], json!(true));
+        assert_eq!(
+            stub.repository(FULL_NAME).unwrap()["allow_auto_merge"],
+            json!(true)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn update_repository_maps_404_to_not_found() {
+        let stub = GitHubApiStub::start();
+
+        let result = github_service(&stub)
+            .update_repository(FULL_NAME, &RepositoryResponse::default())
+            .await;
+
+        assert!(matches!(result, Err(ControllerError::NotFound)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn rename_repository_sends_new_name() {
+        let stub = GitHubApiStub::start()
+            .with_repository("otto-ec/pdh-da_old")
+            .with_team_permission(TEAM, "otto-ec/pdh-da_old", "push");
+
+        let repository = github_service(&stub)
+            .rename_repository("otto-ec/pdh-da_old", "pdh-da_test")
+            .await
+            .unwrap();
+
+        assert_eq!(repository.delete_branch_on_merge, Some(true));
+        assert_eq!(
+            stub.requests()[0].body,
+            Some(json!({ "name": "pdh-da_test" }))
+        );
+        assert!(stub.repository("otto-ec/pdh-da_old").is_none());
+        assert_eq!(
+            stub.repository(FULL_NAME).unwrap()["full_name"],
+            json!(FULL_NAME)
+        );
+        assert_eq!(
+            stub.team_permission(TEAM, FULL_NAME).as_deref(),
+            Some("push")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn transfer_repository_sends_new_owner() {
+        let stub = GitHubApiStub::start().with_repository("otto-eg/pdh-da_test");
+
+        github_service(&stub)
+            .transfer_repository("otto-eg/pdh-da_test", "otto-ec", "pdh-da_test")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            stub.calls(),
+            vec!["POST /repos/otto-eg/pdh-da_test/transfer".to_string()]
+        );
+        assert_eq!(
+            stub.requests()[0].body,
+            Some(json!({ "new_owner": "otto-ec", "new_name": "pdh-da_test" }))
+        );
+        assert!(stub.repository(FULL_NAME).is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn rename_repository_fails_if_the_name_is_taken() {
+        let stub = GitHubApiStub::start()
+            .with_repository("otto-ec/pdh-da_old")
+            .with_repository(FULL_NAME);
+
+        let result = github_service(&stub)
+            .rename_repository("otto-ec/pdh-da_old", "pdh-da_test")
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(ControllerError::GitHubError(GitHubError {
+                status: 422,
+                ..
+            }))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn get_team_permission_requests_repository_media_type() {
+        let stub = GitHubApiStub::start()
+            .with_repository(FULL_NAME)
+            .with_team_permission(TEAM, FULL_NAME, "maintain");
+
+        let permission = github_service(&stub)
+            .get_team_permission(FULL_NAME, TEAM)
+            .await
+            .unwrap();
+
+        assert_eq!(permission.as_deref(), Some("maintain"));
+        assert_eq!(
+            stub.requests()[0].header("accept"),
+            Some("application/vnd.github.v3.repository+json")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn get_team_permission_maps_404_to_none() {
+        let stub = GitHubApiStub::start().with_repository(FULL_NAME);
+
+        let permission = github_service(&stub)
+            .get_team_permission(FULL_NAME, TEAM)
+            .await
+            .unwrap();
+
+        assert_eq!(permission, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn update_team_permission_puts_permission() {
+        let stub = GitHubApiStub::start().with_repository(FULL_NAME);
+
+        github_service(&stub)
+            .update_team_permission(FULL_NAME, TEAM, "push")
+            .await
+            .unwrap();
+
+        let requests = stub.requests();
+        assert_eq!(
+            stub.calls(),
+            vec![format!(
+                "PUT /orgs/otto-ec/teams/pdh-da/repos/{}",
+                FULL_NAME
+            )]
+        );
+        assert_eq!(requests[0].body, Some(json!({"permission": "push"})));
+        assert_eq!(
+            stub.team_permission(TEAM, FULL_NAME).as_deref(),
+            Some("push")
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn autolink_references_map_404() {
+        let stub = GitHubApiStub::start()
+            .with_repository(FULL_NAME)
+            .with_autolink_reference(FULL_NAME, "JIRA-", "https://jira/browse/JIRA-<num>");
+        let github_service = github_service(&stub);
+
+        let missing = github_service.get_autolink_reference(FULL_NAME, &42).await;
+        let deleted = github_service
+            .delete_autolink_references(FULL_NAME, &42)
+            .await;
+
+        assert!(matches!(missing, Err(ControllerError::NotFound)));
+        assert!(deleted.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn add_autolink_reference_posts_request() {
+        let stub = GitHubApiStub::start().with_repository(FULL_NAME);
+        let request = AutolinkReferenceRequest {
+            key_prefix: "JIRA-".to_string(),
+            url_template: "https://jira/browse/JIRA-<num>".to_string(),
+            is_alphanumeric: false,
+        };
+
+        let response = github_service(&stub)
+            .add_autolink_reference(FULL_NAME, &request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.id, 1);
+        assert_eq!(
+            stub.requests()[0].body,
+            Some(serde_json::to_value(&request).unwrap())
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn list_organization_repositories_follows_pages() {
+        let stub = (0..150).fold(GitHubApiStub::start(), |stub, index| {
+            stub.with_repository(&format!("otto-ec/repository-{:03}", index))
+        });
+
+        let repositories = github_service(&stub)
+            .list_organization_repositories("otto-ec")
+            .await
+            .unwrap();
+
+        assert_eq!(repositories.len(), 150);
+        let pages = stub
+            .requests()
+            .iter()
+            .map(|request| {
+                (
+                    request.query.get("page").cloned(),
+                    request.query.get("per_page").cloned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pages,
+            vec![
+                (Some("1".to_string()), Some("100".to_string())),
+                (Some("2".to_string()), Some("100".to_string())),
+            ]
+        );
+    }
+
+    fn github_error(result: Result<impl std::fmt::Debug, ControllerError>) -> GitHubError {
+        match result {
+            Err(ControllerError::GitHubError(error)) => error,
+            result => panic!("expected a GitHub error, got {:?}", result),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn errors_contain_github_details() {
+        let stub = GitHubApiStub::start().with_repository(FULL_NAME);
+        stub.respond_next_with(StatusCode::INTERNAL_SERVER_ERROR);
+
+        let error = github_error(github_service(&stub).get_repository(FULL_NAME).await);
+
+        assert_eq!(error.status, 500);
+        assert_eq!(error.message, "injected by the stub");
+        assert_eq!(
+            error.documentation_url.as_deref(),
+            Some("https://docs.github.com/rest")
+        );
+        assert_eq!(error.class(), ErrorClass::Retryable);
+        assert_eq!(error.reason(), "GitHubUnavailable");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn verify_credentials_rejects_wrong_token() {
+        let stub = GitHubApiStub::start();
+
+        let error = github_error(
+            HttpGithubService::new("wrong".to_string())
+                .with_base_url(stub.url())
+                .verify_credentials()
+                .await,
+        );
+
+        assert_eq!(error.status, 401);
+        assert_eq!(error.message, "Bad credentials");
+        assert_eq!(error.class(), ErrorClass::Auth);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn exhausted_rate_limit_is_retryable() {
+        let stub = GitHubApiStub::start()
+            .with_repository(FULL_NAME)
+            .with_rate_limit_remaining(1);
+        stub.respond_next_with(StatusCode::FORBIDDEN);
+
+        let error = github_error(github_service(&stub).get_repository(FULL_NAME).await);
+
+        assert_eq!(error.rate_limit_remaining, Some(0));
+        assert_eq!(error.class(), ErrorClass::Retryable);
+        assert_eq!(error.reason(), "RateLimited");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn forbidden_is_permission_denied() {
+        let stub = GitHubApiStub::start().with_repository(FULL_NAME);
+        stub.respond_next_with(StatusCode::FORBIDDEN);
+
+        let error = github_error(github_service(&stub).get_repository(FULL_NAME).await);
+
+        assert_eq!(error.class(), ErrorClass::PermissionDenied);
+        assert_eq!(error.reason(), "Forbidden");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn duplicate_autolink_reference_fails_validation() {
+        let stub = GitHubApiStub::start()
+            .with_repository(FULL_NAME)
+            .with_autolink_reference(FULL_NAME, "JIRA-", "https://jira/browse/JIRA-<num>");
+        let request = AutolinkReferenceRequest {
+            key_prefix: "JIRA-".to_string(),
+            url_template: "https://jira/browse/JIRA-<num>".to_string(),
+            is_alphanumeric: true,
+        };
+
+        let error = github_error(
+            github_service(&stub)
+                .add_autolink_reference(FULL_NAME, &request)
+                .await,
+        );
+
+        assert_eq!(error.status, 422);
+        assert_eq!(error.errors, vec!["Autolink.key_prefix already_exists"]);
+        assert_eq!(error.class(), ErrorClass::Permanent);
+        assert_eq!(error.reason(), "ValidationFailed");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn records_rate_limit_remaining() {
+        let stub = GitHubApiStub::start().with_rate_limit_remaining(4242);
+        let registry = Registry::new();
+        let metrics = Metrics::register(&registry).unwrap();
+
+        github_service(&stub)
+            .with_metrics(metrics.github)
+            .verify_credentials()
+            .await
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&registry.gather(), &mut buffer)
+            .unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.contains("github_operator_github_rate_limit_remaining 4241"));
+    }
 
     #[tokio::test]
     async fn test() {
@@ -0,0 +1,503 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::domain::model::autolink_reference::{
+    AutolinkReferenceRequest, AutolinkReferenceResponse,
+};
+use crate::domain::model::repository::{
+    CollaboratorPermission, RepositoryResponse, RulesetResponse, SecurityAndAnalysisResponse,
+    TeamPermission,
+};
+use crate::domain::service::github_service::GitHubService;
+use crate::ControllerError;
+
+/// A stateful fake of GitHub for tests, the clones share their state.
+///
+/// Missing resources behave like the 404 responses of [`super::http_github_service::HttpGithubService`].
+/// Errors can be injected per method with [`InMemoryGithubService::fail_next`], a slow GitHub with
+/// [`InMemoryGithubService::with_latency`].
+#[derive(Clone, Default)]
+pub struct InMemoryGithubService {
+    state: Arc<Mutex<State>>,
+    latency: Duration,
+}
+
+#[derive(Default)]
+struct State {
+    repositories: BTreeMap<String, RepositoryResponse>,
+    archived: BTreeSet<String>,
+    autolink_references: BTreeMap<String, Vec<AutolinkReferenceResponse>>,
+    last_autolink_reference_id: u32,
+    /// The role of a team by repository and team
+    team_permissions: BTreeMap<(String, String), String>,
+    collaborators: BTreeMap<String, Vec<CollaboratorPermission>>,
+    rulesets: BTreeMap<String, Vec<RulesetResponse>>,
+    failures: HashMap<&'static str, VecDeque<ControllerError>>,
+    calls: Vec<String>,
+}
+
+impl InMemoryGithubService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delay every call, e.g. to test timeouts.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn with_repository(self, full_name: &str, repository: RepositoryResponse) -> Self {
+        self.state().repositories.insert(key(full_name), repository);
+        self
+    }
+
+    /// Returns the id of the new autolink reference.
+    pub fn add_autolink(&self, full_name: &str, request: &AutolinkReferenceRequest) -> u32 {
+        let mut state = self.state();
+        state.last_autolink_reference_id += 1;
+        let response = AutolinkReferenceResponse {
+            id: state.last_autolink_reference_id,
+            key_prefix: request.key_prefix.clone(),
+            url_template: request.url_template.clone(),
+            is_alphanumeric: request.is_alphanumeric,
+        };
+        state
+            .autolink_references
+            .entry(key(full_name))
+            .or_default()
+            .push(response);
+        state.last_autolink_reference_id
+    }
+
+    pub fn with_team_permission(self, full_name: &str, full_team_name: &str, role: &str) -> Self {
+        self.state()
+            .team_permissions
+            .insert((key(full_name), key(full_team_name)), role.to_string());
+        self
+    }
+
+    pub fn with_collaborator(self, full_name: &str, collaborator: CollaboratorPermission) -> Self {
+        self.state()
+            .collaborators
+            .entry(key(full_name))
+            .or_default()
+            .push(collaborator);
+        self
+    }
+
+    pub fn with_ruleset(self, full_name: &str, ruleset: RulesetResponse) -> Self {
+        self.state()
+            .rulesets
+            .entry(key(full_name))
+            .or_default()
+            .push(ruleset);
+        self
+    }
+
+    /// The next call of the method, e.g. `get_repository`, fails with the error. Multiple errors
+    /// for the same method fail the following calls in order.
+    pub fn fail_next(&self, method: &'static str, error: ControllerError) {
+        self.state()
+            .failures
+            .entry(method)
+            .or_default()
+            .push_back(error);
+    }
+
+    pub fn repository(&self, full_name: &str) -> Option<RepositoryResponse> {
+        self.state().repositories.get(&key(full_name)).cloned()
+    }
+
+    pub fn is_archived(&self, full_name: &str) -> bool {
+        self.state().archived.contains(&key(full_name))
+    }
+
+    pub fn autolink_references(&self, full_name: &str) -> Vec<AutolinkReferenceResponse> {
+        self.state()
+            .autolink_references
+            .get(&key(full_name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn team_permission(&self, full_name: &str, full_team_name: &str) -> Option<String> {
+        self.state()
+            .team_permissions
+            .get(&(key(full_name), key(full_team_name)))
+            .cloned()
+    }
+
+    /// The methods called so far, e.g. `update_repository otto-ec/repo`.
+    pub fn calls(&self) -> Vec<String> {
+        self.state().calls.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("state should not be poisoned")
+    }
+
+    /// Record the call and apply the latency and injected errors.
+    async fn call(&self, method: &'static str, target: &str) -> Result<(), ControllerError> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        let mut state = self.state();
+        state.calls.push(format!("{} {}", method, target));
+        match state.failures.get_mut(method).and_then(VecDeque::pop_front) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Move the repository with everything that belongs to it, like a rename on GitHub.
+    fn move_repository(
+        &self,
+        full_name: &str,
+        new_full_name: &str,
+    ) -> Result<RepositoryResponse, ControllerError> {
+        let (from, to) = (key(full_name), key(new_full_name));
+        let mut state = self.state();
+        if state.repositories.contains_key(&to) {
+            return Err(ControllerError::AlreadyExists);
+        }
+        let repository = state
+            .repositories
+            .remove(&from)
+            .ok_or(ControllerError::NotFound)?;
+        state.repositories.insert(to.clone(), repository.clone());
+        if state.archived.remove(&from) {
+            state.archived.insert(to.clone());
+        }
+        if let Some(autolink_references) = state.autolink_references.remove(&from) {
+            state
+                .autolink_references
+                .insert(to.clone(), autolink_references);
+        }
+        if let Some(collaborators) = state.collaborators.remove(&from) {
+            state.collaborators.insert(to.clone(), collaborators);
+        }
+        if let Some(rulesets) = state.rulesets.remove(&from) {
+            state.rulesets.insert(to.clone(), rulesets);
+        }
+        let team_permissions = std::mem::take(&mut state.team_permissions);
+        state.team_permissions = team_permissions
+            .into_iter()
+            .map(|((repository, team), role)| {
+                let repository = if repository == from {
+                    to.clone()
+                } else {
+                    repository
+                };
+                ((repository, team), role)
+            })
+            .collect();
+        Ok(repository)
+    }
+
+    fn existing(state: &State, full_name: &str) -> Result<(), ControllerError> {
+        if state.repositories.contains_key(&key(full_name)) {
+            Ok(())
+        } else {
+            Err(ControllerError::NotFound)
+        }
+    }
+}
+
+/// GitHub names are case insensitive.
+fn key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// Apply the set fields like a `PATCH` on GitHub.
+fn merge(actual: &mut RepositoryResponse, update: &RepositoryResponse) {
+    fn set<T: Clone>(actual: &mut Option<T>, update: &Option<T>) {
+        if update.is_some() {
+            actual.clone_from(update);
+        }
+    }
+    if let Some(update) = &update.security_and_analysis {
+        let actual = actual
+            .security_and_analysis
+            .get_or_insert(SecurityAndAnalysisResponse {
+                advanced_security: None,
+                secret_scanning: None,
+                secret_scanning_push_protection: None,
+                dependabot_security_updates: None,
+                secret_scanning_validity_checks: None,
+            });
+        set(&mut actual.advanced_security, &update.advanced_security);
+        set(&mut actual.secret_scanning, &update.secret_scanning);
+        set(
+            &mut actual.secret_scanning_push_protection,
+            &update.secret_scanning_push_protection,
+        );
+        set(
+            &mut actual.dependabot_security_updates,
+            &update.dependabot_security_updates,
+        );
+        set(
+            &mut actual.secret_scanning_validity_checks,
+            &update.secret_scanning_validity_checks,
+        );
+    }
+    set(
+        &mut actual.delete_branch_on_merge,
+        &update.delete_branch_on_merge,
+    );
+    set(&mut actual.allow_auto_merge, &update.allow_auto_merge);
+    set(&mut actual.allow_squash_merge, &update.allow_squash_merge);
+    set(&mut actual.allow_merge_commit, &update.allow_merge_commit);
+    set(&mut actual.allow_rebase_merge, &update.allow_rebase_merge);
+    set(&mut actual.allow_update_branch, &update.allow_update_branch);
+}
+
+#[async_trait]
+impl GitHubService for InMemoryGithubService {
+    async fn verify_credentials(&self) -> Result<(), ControllerError> {
+        self.call("verify_credentials", "").await
+    }
+
+    async fn create_repository(
+        &self,
+        full_name: &str,
+        repository: &RepositoryResponse,
+    ) -> Result<RepositoryResponse, ControllerError> {
+        self.call("create_repository", full_name).await?;
+        let mut state = self.state();
+        if state.repositories.contains_key(&key(full_name)) {
+            return Err(ControllerError::AlreadyExists);
+        }
+        // the defaults of GitHub for new repositories
+        let mut created = RepositoryResponse {
+            security_and_analysis: None,
+            delete_branch_on_merge: Some(false),
+            allow_auto_merge: Some(false),
+            allow_squash_merge: Some(true),
+            allow_merge_commit: Some(true),
+            allow_rebase_merge: Some(true),
+            allow_update_branch: Some(false),
+        };
+        merge(&mut created, repository);
+        state.repositories.insert(key(full_name), created.clone());
+        Ok(created)
+    }
+
+    async fn get_repository(
+        &self,
+        full_name: &str,
+    ) -> Result<Option<RepositoryResponse>, ControllerError> {
+        self.call("get_repository", full_name).await?;
+        Ok(self.state().repositories.get(&key(full_name)).cloned())
+    }
+
+    async fn update_repository(
+        &self,
+        full_name: &str,
+        repository: &RepositoryResponse,
+    ) -> Result<RepositoryResponse, ControllerError> {
+        self.call("update_repository", full_name).await?;
+        let mut state = self.state();
+        let actual = state
+            .repositories
+            .get_mut(&key(full_name))
+            .ok_or(ControllerError::NotFound)?;
+        merge(actual, repository);
+        Ok(actual.clone())
+    }
+
+    async fn archive_repository(&self, full_name: &str) -> Result<(), ControllerError> {
+        self.call("archive_repository", full_name).await?;
+        let mut state = self.state();
+        Self::existing(&state, full_name)?;
+        state.archived.insert(key(full_name));
+        Ok(())
+    }
+
+    async fn rename_repository(
+        &self,
+        full_name: &str,
+        new_name: &str,
+    ) -> Result<RepositoryResponse, ControllerError> {
+        self.call("rename_repository", full_name).await?;
+        let (owner, _) = full_name.split_once('/').ok_or(ControllerError::NotFound)?;
+        self.move_repository(full_name, &format!("{}/{}", owner, new_name))
+    }
+
+    async fn transfer_repository(
+        &self,
+        full_name: &str,
+        new_owner: &str,
+        new_name: &str,
+    ) -> Result<(), ControllerError> {
+        self.call("transfer_repository", full_name).await?;
+        self.move_repository(full_name, &format!("{}/{}", new_owner, new_name))
+            .map(|_| ())
+    }
+
+    async fn list_organization_repositories(
+        &self,
+        org: &str,
+    ) -> Result<Vec<String>, ControllerError> {
+        self.call("list_organization_repositories", org).await?;
+        let prefix = format!("{}/", key(org));
+        Ok(self
+            .state()
+            .repositories
+            .keys()
+            .filter(|full_name| full_name.starts_with(&prefix))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_autolink_references(
+        &self,
+        full_name: &str,
+    ) -> Result<Vec<AutolinkReferenceResponse>, ControllerError> {
+        self.call("get_autolink_references", full_name).await?;
+        Self::existing(&self.state(), full_name)?;
+        Ok(self.autolink_references(full_name))
+    }
+
+    async fn get_autolink_reference(
+        &self,
+        full_name: &str,
+        id: &u32,
+    ) -> Result<AutolinkReferenceResponse, ControllerError> {
+        self.call("get_autolink_reference", full_name).await?;
+        self.autolink_references(full_name)
+            .into_iter()
+            .find(|autolink_reference| autolink_reference.id == *id)
+            .ok_or(ControllerError::NotFound)
+    }
+
+    async fn add_autolink_reference(
+        &self,
+        full_name: &str,
+        autolink_reference: &AutolinkReferenceRequest,
+    ) -> Result<AutolinkReferenceResponse, ControllerError> {
+        self.call("add_autolink_reference", full_name).await?;
+        {
+            let state = self.state();
+            Self::existing(&state, full_name)?;
+            let duplicate = state
+                .autolink_references
+                .get(&key(full_name))
+                .into_iter()
+                .flatten()
+                .any(|existing| existing.key_prefix == autolink_reference.key_prefix);
+            if duplicate {
+                return Err(ControllerError::AlreadyExists);
+            }
+        }
+        let id = self.add_autolink(full_name, autolink_reference);
+        Ok(AutolinkReferenceResponse {
+            id,
+            key_prefix: autolink_reference.key_prefix.clone(),
+            url_template: autolink_reference.url_template.clone(),
+            is_alphanumeric: autolink_reference.is_alphanumeric,
+        })
+    }
+
+    async fn delete_autolink_references(
+        &self,
+        full_name: &str,
+        autolink_reference_id: &u32,
+    ) -> Result<(), ControllerError> {
+        self.call("delete_autolink_references", full_name).await?;
+        if let Some(autolink_references) = self.state().autolink_references.get_mut(&key(full_name))
+        {
+            autolink_references.retain(|existing| existing.id != *autolink_reference_id);
+        }
+        Ok(())
+    }
+
+    async fn get_team_permission(
+        &self,
+        full_name: &str,
+        full_team_name: &str,
+    ) -> Result<Option<String>, ControllerError> {
+        self.call("get_team_permission", full_name).await?;
+        Ok(self.team_permission(full_name, full_team_name))
+    }
+
+    async fn update_team_permission(
+        &self,
+        full_name: &str,
+        full_team_name: &str,
+        role_name: &str,
+    ) -> Result<(), ControllerError> {
+        self.call("update_team_permission", full_name).await?;
+        let mut state = self.state();
+        Self::existing(&state, full_name)?;
+        state
+            .team_permissions
+            .insert((key(full_name), key(full_team_name)), role_name.to_string());
+        Ok(())
+    }
+
+    async fn delete_team_permission(
+        &self,
+        full_name: &str,
+        full_team_name: &str,
+    ) -> Result<(), ControllerError> {
+        self.call("delete_team_permission", full_name).await?;
+        self.state()
+            .team_permissions
+            .remove(&(key(full_name), key(full_team_name)));
+        Ok(())
+    }
+
+    async fn get_repository_teams(
+        &self,
+        full_name: &str,
+    ) -> Result<Vec<TeamPermission>, ControllerError> {
+        self.call("get_repository_teams", full_name).await?;
+        let state = self.state();
+        Self::existing(&state, full_name)?;
+        Ok(state
+            .team_permissions
+            .iter()
+            .filter(|((repository, _), _)| *repository == key(full_name))
+            .filter_map(|((_, team), permission)| {
+                let (org, team_slug) = team.split_once('/')?;
+                Some(TeamPermission {
+                    org: org.to_string(),
+                    team_slug: team_slug.to_string(),
+                    permission: permission.clone(),
+                })
+            })
+            .collect())
+    }
+
+    async fn get_repository_collaborators(
+        &self,
+        full_name: &str,
+    ) -> Result<Vec<CollaboratorPermission>, ControllerError> {
+        self.call("get_repository_collaborators", full_name).await?;
+        let state = self.state();
+        Self::existing(&state, full_name)?;
+        Ok(state
+            .collaborators
+            .get(&key(full_name))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn get_repository_rulesets(
+        &self,
+        full_name: &str,
+    ) -> Result<Vec<RulesetResponse>, ControllerError> {
+        self.call("get_repository_rulesets", full_name).await?;
+        let state = self.state();
+        Self::existing(&state, full_name)?;
+        Ok(state
+            .rulesets
+            .get(&key(full_name))
+            .cloned()
+            .unwrap_or_default())
+    }
+}
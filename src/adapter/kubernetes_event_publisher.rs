@@ -0,0 +1,52 @@
+use async_trait::async_trait;
+use k8s_openapi::api::core::v1::ObjectReference;
+use kube::runtime::events::{Event, EventType, Recorder};
+use kube::Client;
+
+use crate::domain::service::event_publisher::{DomainEvent, EventPublisher, EventSubject};
+use crate::ControllerError;
+
+/// Publishes the events as Kubernetes events of the object.
+#[derive(Clone)]
+pub struct KubernetesEventPublisher {
+    recorder: Recorder,
+}
+
+impl KubernetesEventPublisher {
+    pub fn new(client: Client, reporter: &str) -> Self {
+        Self {
+            recorder: Recorder::new(client, reporter.to_string().into()),
+        }
+    }
+}
+
+#[async_trait]
+impl EventPublisher for KubernetesEventPublisher {
+    async fn publish(
+        &self,
+        subject: &EventSubject,
+        event: &DomainEvent,
+    ) -> Result<(), ControllerError> {
+        let reference = ObjectReference {
+            api_version: Some(subject.api_version.clone()),
+            kind: Some(subject.kind.clone()),
+            namespace: subject.namespace.clone(),
+            name: Some(subject.name.clone()),
+            uid: subject.uid.clone(),
+            ..ObjectReference::default()
+        };
+        self.recorder
+            .publish(
+                &Event {
+                    action: event.action().into(),
+                    reason: "Reconciling".into(),
+                    note: Some(event.note()),
+                    type_: EventType::Normal,
+                    secondary: None,
+                },
+                &reference,
+            )
+            .await
+            .map_err(ControllerError::KubeError)
+    }
+}
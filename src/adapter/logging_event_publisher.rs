@@ -0,0 +1,20 @@
+use async_trait::async_trait;
+
+use crate::domain::service::event_publisher::{DomainEvent, EventPublisher, EventSubject};
+use crate::ControllerError;
+
+/// Logs the events, e.g. for the CLI.
+#[derive(Clone, Default)]
+pub struct LoggingEventPublisher;
+
+#[async_trait]
+impl EventPublisher for LoggingEventPublisher {
+    async fn publish(
+        &self,
+        subject: &EventSubject,
+        event: &DomainEvent,
+    ) -> Result<(), ControllerError> {
+        log::info!("{}: {}", subject, event.note());
+        Ok(())
+    }
+}
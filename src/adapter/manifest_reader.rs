@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::domain::model::conversion::{convert, V1BETA1};
+use crate::domain::model::manifest::Manifest;
+use crate::ControllerError;
+
+/// A YAML document of a manifest file.
+#[derive(Clone, Debug)]
+pub struct Document {
+    pub file: PathBuf,
+    /// Position of the document in the file, starting with 1
+    pub index: usize,
+    pub value: Value,
+}
+
+/// Read all manifests of a YAML file or of the `.yaml` and `.yml` files below a directory.
+///
+/// Files may contain multiple documents, documents of other kinds are skipped.
+pub fn read_manifests(path: &Path) -> Result<Vec<Manifest>, ControllerError> {
+    let mut manifests = Vec::new();
+    for document in read_documents(path)? {
+        if let Some(manifest) = parse_document(document.value).map_err(|e| {
+            ControllerError::ManifestError(format!("{}: {}", document.file.display(), e))
+        })? {
+            manifests.push(manifest);
+        }
+    }
+    Ok(manifests)
+}
+
+/// Read the documents like [`read_manifests`] without interpreting them.
+pub fn read_documents(path: &Path) -> Result<Vec<Document>, ControllerError> {
+    let mut files = Vec::new();
+    collect_files(path, &mut files)?;
+    let mut documents = Vec::new();
+    for file in files {
+        documents.extend(read_file(&file)?);
+    }
+    Ok(documents)
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), ControllerError> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(path)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(ControllerError::IoError)?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        let is_yaml = path
+            .extension()
+            .is_some_and(|extension| extension == "yaml" || extension == "yml");
+        if path.is_dir() || is_yaml {
+            collect_files(&path, files)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_file(path: &Path) -> Result<Vec<Document>, ControllerError> {
+    let content = std::fs::read_to_string(path).map_err(ControllerError::IoError)?;
+    let mut documents = Vec::new();
+    for (index, document) in serde_yaml::Deserializer::from_str(&content).enumerate() {
+        let value = Value::deserialize(document)
+            .map_err(|e| ControllerError::ManifestError(format!("{}: {}", path.display(), e)))?;
+        if value.is_null() {
+            continue;
+        }
+        documents.push(Document {
+            file: path.to_path_buf(),
+            index: index + 1,
+            value,
+        });
+    }
+    Ok(documents)
+}
+
+fn parse_document(document: Value) -> Result<Option<Manifest>, String> {
+    let kind = document
+        .get("kind")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    if !matches!(
+        kind.as_str(),
+        "Repository" | "AutolinkReference" | "RepositoryPermission"
+    ) {
+        log::warn!("skipping document of kind {:?}", kind);
+        return Ok(None);
+    }
+    // older versions are read like the API server serves them
+    let document = convert(document, V1BETA1)?;
+    let manifest = match kind.as_str() {
+        "Repository" => serde_json::from_value(document).map(Manifest::Repository),
+        "AutolinkReference" => serde_json::from_value(document).map(Manifest::AutolinkReference),
+        _ => serde_json::from_value(document).map(Manifest::RepositoryPermission),
+    };
+    manifest.map(Some).map_err(|e| e.to_string())
+}
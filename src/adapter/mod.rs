@@ -1 +1,11 @@
+#[cfg(test)]
+pub mod capturing_event_publisher;
+#[cfg(test)]
+pub mod github_api_stub;
 pub mod http_github_service;
+#[cfg(test)]
+pub mod in_memory_github_service;
+pub mod kubernetes_event_publisher;
+pub mod logging_event_publisher;
+pub mod manifest_reader;
+pub mod state_file;
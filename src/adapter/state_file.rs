@@ -0,0 +1,22 @@
+use std::path::Path;
+
+use crate::domain::model::apply_state::ApplyState;
+use crate::ControllerError;
+
+/// Read the state of the previous run, empty if there was none.
+pub fn read_state(path: &Path) -> Result<ApplyState, ControllerError> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).map_err(ControllerError::SerializationError),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ApplyState::default()),
+        Err(e) => Err(ControllerError::IoError(e)),
+    }
+}
+
+/// Write the state, replacing the file only once it is written completely.
+pub fn write_state(path: &Path, state: &ApplyState) -> Result<(), ControllerError> {
+    let content =
+        serde_json::to_string_pretty(state).map_err(ControllerError::SerializationError)?;
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, content).map_err(ControllerError::IoError)?;
+    std::fs::rename(&temporary, path).map_err(ControllerError::IoError)
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use github_operator::domain::model::manifest::Manifest;
+
+/// Render the manifests as `_resources` of the CUE package in `examples/cue`, grouped by the given key.
+pub fn render(resources: &BTreeMap<String, Vec<Manifest>>) -> String {
+    let mut cue = String::from("package config\n\n_resources: {\n");
+    for (key, manifests) in resources {
+        writeln!(cue, "\t{}: [", Value::from(key.as_str())).unwrap();
+        for manifest in manifests {
+            let value = serde_json::to_value(manifest).expect("manifest should be serializable");
+            let mut fields = serde_json::Map::new();
+            // apiVersion and kind are defined by the CUE definition
+            for field in ["metadata", "spec"] {
+                if let Some(value) = value.get(field) {
+                    fields.insert(field.to_string(), snake_case_keys(value));
+                }
+            }
+            write!(cue, "\t\t#{} & ", manifest.kind()).unwrap();
+            write_value(&mut cue, &Value::Object(fields), 2);
+            cue.push_str(",\n");
+        }
+        cue.push_str("\t]\n");
+    }
+    cue.push_str("}\n");
+    cue
+}
+
+fn write_value(cue: &mut String, value: &Value, depth: usize) {
+    match value {
+        Value::Object(fields) => {
+            cue.push_str("{\n");
+            for (name, value) in fields {
+                if value.is_null() {
+                    continue;
+                }
+                write!(cue, "{}{}: ", "\t".repeat(depth + 1), name).unwrap();
+                write_value(cue, value, depth + 1);
+                cue.push('\n');
+            }
+            write!(cue, "{}}}", "\t".repeat(depth)).unwrap();
+        }
+        Value::Array(items) => {
+            cue.push_str("[\n");
+            for item in items {
+                cue.push_str(&"\t".repeat(depth + 1));
+                write_value(cue, item, depth + 1);
+                cue.push_str(",\n");
+            }
+            write!(cue, "{}]", "\t".repeat(depth)).unwrap();
+        }
+        // JSON scalars are valid CUE
+        value => cue.push_str(&value.to_string()),
+    }
+}
+
+/// The CUE definitions use the snake case field names of the GitHub API.
+fn snake_case_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| (snake_case(name), snake_case_keys(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(snake_case_keys).collect()),
+        value => value.clone(),
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut result = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            result.push('_');
+            result.push(c.to_ascii_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
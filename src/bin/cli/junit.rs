@@ -0,0 +1,77 @@
+use std::fmt::Write;
+
+use github_operator::domain::audit_repositories_use_case::AuditResult;
+
+/// Render the audit as JUnit XML, one test case per repository.
+pub fn render(org: &str, results: &[AuditResult]) -> String {
+    let failures = results
+        .iter()
+        .filter(|result| !result.violations.is_empty())
+        .count();
+    let errors = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        "<testsuites name=\"audit\" tests=\"{}\" failures=\"{}\" errors=\"{}\">",
+        results.len(),
+        failures,
+        errors
+    )
+    .unwrap();
+    writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\">",
+        escape(org),
+        results.len(),
+        failures,
+        errors
+    )
+    .unwrap();
+    for result in results {
+        write!(
+            xml,
+            "    <testcase classname=\"{}\" name=\"{}\"",
+            escape(org),
+            escape(&result.full_name)
+        )
+        .unwrap();
+        if result.is_compliant() {
+            xml.push_str("/>\n");
+            continue;
+        }
+        xml.push_str(">\n");
+        if let Some(error) = &result.error {
+            writeln!(xml, "      <error message=\"{}\"/>", escape(error)).unwrap();
+        }
+        if !result.violations.is_empty() {
+            let details = result
+                .violations
+                .iter()
+                .map(|violation| violation.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            writeln!(
+                xml,
+                "      <failure message=\"{} violation(s)\">{}</failure>",
+                result.violations.len(),
+                escape(&details)
+            )
+            .unwrap();
+        }
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,674 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use kube::api::ListParams;
+use kube::{Api, Client};
+use tracing::event;
+
+use github_operator::adapter::http_github_service::HttpGithubService;
+use github_operator::adapter::logging_event_publisher::LoggingEventPublisher;
+use github_operator::adapter::manifest_reader::{read_documents, read_manifests};
+use github_operator::adapter::state_file::{read_state, write_state};
+use github_operator::domain::apply_manifests_use_case::{ApplyManifestsUseCase, ApplyOutcome};
+use github_operator::domain::archive_repository_use_case::ArchiveRepositoryUseCase;
+use github_operator::domain::audit_repositories_use_case::AuditRepositoriesUseCase;
+use github_operator::domain::delete_autolink_reference_use_case::DeleteAutolinkReferenceUseCase;
+use github_operator::domain::delete_permissions_use_case::DeletePermissionUseCase;
+use github_operator::domain::diff::Drift;
+use github_operator::domain::diff_manifest_use_case::DiffManifestUseCase;
+use github_operator::domain::get_repository_use_case::GetRepositoryUseCase;
+use github_operator::domain::list_repositories_use_case::ListRepositoriesUseCase;
+use github_operator::domain::model::conversion::{convert, V1BETA1};
+use github_operator::domain::model::manifest::{render_name_template, Manifest};
+use github_operator::domain::model::repository::{Repository, RepositoryResponse, RepositorySpec};
+use github_operator::domain::reconcile_autolink_reference_use_case::ReconcileAutolinkReferenceUseCase;
+use github_operator::domain::reconcile_permissions_use_case::ReconcilePermissionUseCase;
+use github_operator::domain::reconcile_repository_use_case::ReconcileRepositoryUseCase;
+use github_operator::domain::set_repository_use_case::SetRepositoryUseCase;
+use github_operator::domain::validation::{document_key, validate_document, validate_spec};
+use github_operator::{init_tracing, ControllerError};
+
+mod cue;
+mod junit;
+mod table;
+
+/// CLI to manage GitHub repositories
+#[derive(Debug, Parser)] // requires `derive` feature
+#[command(name = "repository")]
+#[command(about = "A fictional versioning CLI", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+    #[arg(short, long)]
+    pub output_format: Option<OutputFormat>,
+}
+
+#[derive(Debug, clap::ValueEnum, Clone)]
+enum OutputFormat {
+    Default,
+    Json,
+    Yaml,
+    Table,
+    /// The custom resources that reproduce the repository
+    Manifest,
+    /// The audit findings as test report
+    Junit,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// show repository settings
+    #[command(arg_required_else_help = true)]
+    Get {
+        /// The repository
+        repository: String,
+    },
+    /// update repository settings
+    #[command(arg_required_else_help = true)]
+    Set {
+        /// The repository
+        repository: String,
+        /// A Repository manifest or spec, the flags take precedence
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        #[command(flatten)]
+        settings: RepositorySettings,
+    },
+    /// compare manifests with the live state, exits with 1 on drift and 2 on errors
+    #[command(arg_required_else_help = true)]
+    Diff {
+        /// A manifest file or a directory of manifests
+        #[arg(short, long)]
+        file: PathBuf,
+    },
+    /// print manifests for existing repositories
+    #[command(arg_required_else_help = true)]
+    Import {
+        /// The repository
+        #[arg(required_unless_present = "org", conflicts_with = "org")]
+        repository: Option<String>,
+        /// Import all repositories of the organization
+        #[arg(long)]
+        org: Option<String>,
+        /// Only import repositories whose name matches, e.g. `pdh-da_*`
+        #[arg(long, requires = "org")]
+        filter: Option<String>,
+        /// Template of the namespace, supports `{owner}`, `{repo}` and `{full_name}`
+        #[arg(long, default_value = "{owner}")]
+        namespace: String,
+        /// Template of the name, supports `{owner}`, `{repo}` and `{full_name}`
+        #[arg(long, default_value = "{repo}")]
+        name: String,
+        /// Print CUE for the definitions in `examples/cue/spec.cue` instead of YAML
+        #[arg(long)]
+        cue: bool,
+    },
+    /// check the repositories of an organization against a policy, exits with 1 on violations
+    #[command(arg_required_else_help = true)]
+    Audit {
+        #[arg(long)]
+        org: String,
+        /// The required settings, e.g. `security_and_analysis.secret_scanning.status: enabled`
+        #[arg(long)]
+        policy: PathBuf,
+        /// Only audit repositories whose name matches, e.g. `pdh-da_*`
+        #[arg(long)]
+        filter: Option<String>,
+        /// Only audit repositories that have a Repository resource in the cluster
+        #[arg(long)]
+        managed_only: bool,
+    },
+    /// reconcile manifests without Kubernetes, exits with 1 if anything failed
+    #[command(arg_required_else_help = true)]
+    Apply {
+        /// A manifest file or a directory of manifests
+        #[arg(short, long)]
+        file: PathBuf,
+        /// Where the ids of the created resources are kept between runs
+        #[arg(long, default_value = ".github-operator-state.json")]
+        state: PathBuf,
+        /// Delete the resources whose manifests were removed since the previous run
+        #[arg(long)]
+        prune: bool,
+    },
+    /// check manifests offline against the CRD schemas and rules, exits with 1 if any is invalid
+    #[command(arg_required_else_help = true)]
+    Validate {
+        /// A manifest file or a directory of manifests
+        #[arg(short, long)]
+        file: PathBuf,
+        /// The manifests before the change, e.g. of the main branch, to check immutable fields
+        #[arg(long)]
+        previous: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct RepositorySettings {
+    #[arg(long)]
+    delete_branch_on_merge: Option<bool>,
+    #[arg(long)]
+    allow_auto_merge: Option<bool>,
+    #[arg(long)]
+    allow_squash_merge: Option<bool>,
+    #[arg(long)]
+    allow_merge_commit: Option<bool>,
+    #[arg(long)]
+    allow_rebase_merge: Option<bool>,
+    #[arg(long)]
+    allow_update_branch: Option<bool>,
+}
+
+impl RepositorySettings {
+    fn apply_to(self, spec: &mut RepositorySpec) {
+        let RepositorySettings {
+            delete_branch_on_merge,
+            allow_auto_merge,
+            allow_squash_merge,
+            allow_merge_commit,
+            allow_rebase_merge,
+            allow_update_branch,
+        } = self;
+        spec.delete_branch_on_merge = delete_branch_on_merge.or(spec.delete_branch_on_merge);
+        spec.allow_auto_merge = allow_auto_merge.or(spec.allow_auto_merge);
+        spec.allow_squash_merge = allow_squash_merge.or(spec.allow_squash_merge);
+        spec.allow_merge_commit = allow_merge_commit.or(spec.allow_merge_commit);
+        spec.allow_rebase_merge = allow_rebase_merge.or(spec.allow_rebase_merge);
+        spec.allow_update_branch = allow_update_branch.or(spec.allow_update_branch);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let _tracing = init_tracing("github-operator-cli").expect("Failed to initialize logging");
+
+    let args = Cli::parse();
+
+    // works without a token, e.g. in pre-commit hooks
+    if let Commands::Validate { file, previous } = &args.command {
+        validate(file, previous.as_deref(), &args.output_format);
+        return;
+    }
+
+    let github_service = HttpGithubService::from_env();
+    let get_github_repository_use_case =
+        GetRepositoryUseCase::new(Box::new(github_service.clone()));
+    let diff_manifest_use_case = DiffManifestUseCase::new(Box::new(github_service.clone()));
+    let list_repositories_use_case = ListRepositoriesUseCase::new(Box::new(github_service.clone()));
+    let audit_repositories_use_case =
+        AuditRepositoriesUseCase::new(Box::new(github_service.clone()));
+    let events = LoggingEventPublisher;
+    let set_repository_use_case = SetRepositoryUseCase::new(ReconcileRepositoryUseCase::new(
+        Box::new(github_service.clone()),
+        Box::new(events.clone()),
+    ));
+    let apply_manifests_use_case = ApplyManifestsUseCase::new(
+        ReconcileRepositoryUseCase::new(Box::new(github_service.clone()), Box::new(events.clone())),
+        ReconcileAutolinkReferenceUseCase::new(
+            Box::new(github_service.clone()),
+            Box::new(events.clone()),
+        ),
+        ReconcilePermissionUseCase::new(Box::new(github_service.clone()), Box::new(events.clone())),
+        ArchiveRepositoryUseCase::new(Box::new(github_service.clone()), Box::new(events.clone())),
+        DeleteAutolinkReferenceUseCase::new(
+            Box::new(github_service.clone()),
+            Box::new(events.clone()),
+        ),
+        DeletePermissionUseCase::new(Box::new(github_service), Box::new(events)),
+    );
+
+    match args.command {
+        Commands::Get {
+            repository: full_name,
+        } => {
+            event!(tracing::Level::INFO, "get {}", full_name);
+            let view = match get_github_repository_use_case.execute(&full_name).await {
+                Ok(Some(view)) => view,
+                Ok(None) => exit_with_error(format!("repository {} not found", full_name)),
+                Err(e) => exit_with_error(format!("couldn't get {}: {}", full_name, e)),
+            };
+            match args.output_format {
+                Some(OutputFormat::Json) => {
+                    println!("{}", serde_json::to_string_pretty(&view).unwrap());
+                }
+                Some(OutputFormat::Yaml) => print!("{}", serde_yaml::to_string(&view).unwrap()),
+                Some(OutputFormat::Table) => table::print_view(&view),
+                Some(OutputFormat::Manifest) => print_manifests(&Manifest::from_view(
+                    &view,
+                    &render_name_template("{owner}", &full_name),
+                    &render_name_template("{repo}", &full_name),
+                )),
+                Some(OutputFormat::Default | OutputFormat::Junit) | None => {
+                    println!("{:#?}", view)
+                }
+            }
+        }
+        Commands::Set {
+            repository,
+            file,
+            settings,
+        } => {
+            event!(tracing::Level::INFO, "set {}", repository);
+            let mut spec = match file {
+                Some(file) => {
+                    read_repository_spec(&file, &repository).unwrap_or_else(|e| exit_with_error(e))
+                }
+                None => RepositorySpec {
+                    full_name: repository,
+                    ..RepositorySpec::default()
+                },
+            };
+            settings.apply_to(&mut spec);
+            // the same checks as the API server and the controller, before anything is sent to GitHub
+            let errors = validate_spec(
+                "Repository",
+                &serde_json::to_value(&spec).unwrap_or_default(),
+            );
+            if !errors.is_empty() {
+                let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+                exit_with_error(format!("invalid spec: {}", errors.join("; ")));
+            }
+            match set_repository_use_case.execute(&spec).await {
+                Ok(changes) if changes.is_empty() => println!("{} is up to date", spec.full_name),
+                Ok(changes) => {
+                    println!("{} updated:", spec.full_name);
+                    for change in changes {
+                        println!("  {}", change);
+                    }
+                }
+                Err(e) => exit_with_error(format!("couldn't update {}: {}", spec.full_name, e)),
+            }
+        }
+        Commands::Diff { file } => {
+            event!(tracing::Level::INFO, "diff {}", file.display());
+            let manifests = read_manifests(&file).unwrap_or_else(|e| exit_with(2, e));
+            let mut drifted = false;
+            let mut results = Vec::new();
+            for manifest in &manifests {
+                let drift = diff_manifest_use_case
+                    .execute(manifest)
+                    .await
+                    .unwrap_or_else(|e| {
+                        exit_with(
+                            2,
+                            format!(
+                                "couldn't diff {} {}: {}",
+                                manifest.kind(),
+                                manifest.name(),
+                                e
+                            ),
+                        )
+                    });
+                drifted |= !drift.is_in_sync();
+                match args.output_format {
+                    Some(OutputFormat::Json) => results.push(serde_json::json!({
+                        "kind": manifest.kind(),
+                        "name": manifest.name(),
+                        "fullName": manifest.full_name(),
+                        "drift": drift,
+                    })),
+                    _ => print_drift(
+                        &format!(
+                            "{} {} ({})",
+                            manifest.kind(),
+                            manifest.name(),
+                            manifest.full_name()
+                        ),
+                        &drift,
+                    ),
+                }
+            }
+            if let Some(OutputFormat::Json) = args.output_format {
+                println!("{}", serde_json::to_string_pretty(&results).unwrap());
+            }
+            if drifted {
+                std::process::exit(1);
+            }
+        }
+        Commands::Import {
+            repository,
+            org,
+            filter,
+            namespace,
+            name,
+            cue,
+        } => {
+            let full_names = match (repository, org) {
+                (Some(repository), _) => vec![repository],
+                (None, Some(org)) => list_repositories_use_case
+                    .execute(&org, filter.as_deref())
+                    .await
+                    .unwrap_or_else(|e| exit_with_error(format!("couldn't list {}: {}", org, e))),
+                (None, None) => unreachable!("clap requires a repository or an organization"),
+            };
+            let mut resources = BTreeMap::new();
+            for full_name in full_names {
+                event!(tracing::Level::INFO, "import {}", full_name);
+                let view = match get_github_repository_use_case.execute(&full_name).await {
+                    Ok(Some(view)) => view,
+                    Ok(None) => exit_with_error(format!("repository {} not found", full_name)),
+                    Err(e) => exit_with_error(format!("couldn't import {}: {}", full_name, e)),
+                };
+                let name = render_name_template(&name, &full_name);
+                let manifests = Manifest::from_view(
+                    &view,
+                    &render_name_template(&namespace, &full_name),
+                    &name,
+                );
+                resources.insert(name, manifests);
+            }
+            if cue {
+                print!("{}", cue::render(&resources));
+            } else {
+                print_manifests(&resources.into_values().flatten().collect::<Vec<_>>());
+            }
+        }
+        Commands::Audit {
+            org,
+            policy,
+            filter,
+            managed_only,
+        } => {
+            event!(tracing::Level::INFO, "audit {}", org);
+            let policy: RepositoryResponse = std::fs::read_to_string(&policy)
+                .map_err(|e| e.to_string())
+                .and_then(|content| serde_yaml::from_str(&content).map_err(|e| e.to_string()))
+                .unwrap_or_else(|e| {
+                    exit_with_error(format!("invalid policy {}: {}", policy.display(), e))
+                });
+            let mut full_names = list_repositories_use_case
+                .execute(&org, filter.as_deref())
+                .await
+                .unwrap_or_else(|e| exit_with_error(format!("couldn't list {}: {}", org, e)));
+            if managed_only {
+                let managed = managed_repositories().await.unwrap_or_else(|e| {
+                    exit_with_error(format!("couldn't list Repository resources: {}", e))
+                });
+                full_names.retain(|full_name| managed.contains(full_name));
+            }
+            let results = audit_repositories_use_case
+                .execute(&full_names, &policy)
+                .await;
+            match args.output_format {
+                Some(OutputFormat::Json) => {
+                    println!("{}", serde_json::to_string_pretty(&results).unwrap());
+                }
+                Some(OutputFormat::Junit) => print!("{}", junit::render(&org, &results)),
+                _ => table::print_table(
+                    &["REPOSITORY", "SETTING", "ACTUAL", "REQUIRED"],
+                    results
+                        .iter()
+                        .flat_map(|result| {
+                            let error = result.error.iter().map(|error| {
+                                vec![
+                                    result.full_name.clone(),
+                                    "-".to_string(),
+                                    format!("error: {}", error),
+                                    "-".to_string(),
+                                ]
+                            });
+                            let violations = result.violations.iter().map(|violation| {
+                                vec![
+                                    result.full_name.clone(),
+                                    violation.path.clone(),
+                                    violation.actual.to_string(),
+                                    violation.desired.to_string(),
+                                ]
+                            });
+                            error.chain(violations).collect::<Vec<_>>()
+                        })
+                        .collect(),
+                ),
+            }
+            if results.iter().any(|result| !result.is_compliant()) {
+                std::process::exit(1);
+            }
+        }
+        Commands::Apply { file, state, prune } => {
+            event!(tracing::Level::INFO, "apply {}", file.display());
+            let manifests = read_manifests(&file).unwrap_or_else(|e| exit_with_error(e));
+            let previous = read_state(&state).unwrap_or_else(|e| {
+                exit_with_error(format!("couldn't read state {}: {}", state.display(), e))
+            });
+            let (next, results) = apply_manifests_use_case
+                .execute(&manifests, &previous, prune)
+                .await;
+            write_state(&state, &next).unwrap_or_else(|e| {
+                exit_with_error(format!("couldn't write state {}: {}", state.display(), e))
+            });
+            match args.output_format {
+                Some(OutputFormat::Json) => {
+                    println!("{}", serde_json::to_string_pretty(&results).unwrap());
+                }
+                _ => table::print_table(
+                    &["RESOURCE", "REPOSITORY", "RESULT"],
+                    results
+                        .iter()
+                        .map(|result| {
+                            let outcome = match &result.outcome {
+                                ApplyOutcome::Applied(change) => {
+                                    format!("{:?}", change).to_lowercase()
+                                }
+                                ApplyOutcome::Failed(e) => format!("failed: {}", e),
+                                ApplyOutcome::Deleted => "deleted".to_string(),
+                                ApplyOutcome::Orphaned => {
+                                    "orphaned, use --prune to delete".to_string()
+                                }
+                                ApplyOutcome::Replaced => {
+                                    "replaced by another manifest".to_string()
+                                }
+                            };
+                            vec![result.key.clone(), result.full_name.clone(), outcome]
+                        })
+                        .collect(),
+                ),
+            }
+            if results
+                .iter()
+                .any(|result| matches!(result.outcome, ApplyOutcome::Failed(_)))
+            {
+                std::process::exit(1);
+            }
+        }
+        Commands::Validate { .. } => unreachable!("validate doesn't need GitHub"),
+    }
+}
+
+/// Validate the manifests, exits with 1 if any is invalid.
+fn validate(file: &Path, previous: Option<&Path>, output_format: &Option<OutputFormat>) {
+    event!(tracing::Level::INFO, "validate {}", file.display());
+    let documents = read_documents(file).unwrap_or_else(|e| exit_with(2, e));
+    let previous = match previous {
+        Some(previous) => read_documents(previous)
+            .unwrap_or_else(|e| exit_with(2, e))
+            .into_iter()
+            .map(|document| (document_key(&document.value), document.value))
+            .collect(),
+        None => BTreeMap::new(),
+    };
+    let mut invalid = false;
+    let mut results = Vec::new();
+    for document in &documents {
+        let key = document_key(&document.value);
+        let errors = validate_document(&document.value, previous.get(&key));
+        invalid |= !errors.is_empty();
+        match output_format {
+            Some(OutputFormat::Json) => results.push(serde_json::json!({
+                "file": document.file,
+                "document": document.index,
+                "resource": key,
+                "errors": errors,
+            })),
+            _ => {
+                for error in errors {
+                    println!(
+                        "{} (document {}) {}: {}",
+                        document.file.display(),
+                        document.index,
+                        key,
+                        error
+                    );
+                }
+            }
+        }
+    }
+    if let Some(OutputFormat::Json) = output_format {
+        println!("{}", serde_json::to_string_pretty(&results).unwrap());
+    }
+    if invalid {
+        std::process::exit(1);
+    }
+}
+
+/// The full names of all repositories with a Repository resource in the cluster.
+async fn managed_repositories() -> Result<HashSet<String>, ControllerError> {
+    let client = Client::try_default()
+        .await
+        .map_err(ControllerError::KubeError)?;
+    let repositories = Api::<Repository>::all(client)
+        .list(&ListParams::default())
+        .await
+        .map_err(ControllerError::KubeError)?;
+    Ok(repositories
+        .into_iter()
+        .map(|repository| repository.spec.full_name)
+        .collect())
+}
+
+/// Print the manifests as multi document YAML.
+fn print_manifests(manifests: &[Manifest]) {
+    print!("{}", render_manifests(manifests));
+}
+
+fn render_manifests(manifests: &[Manifest]) -> String {
+    manifests
+        .iter()
+        .map(|manifest| format!("---\n{}", serde_yaml::to_string(manifest).unwrap()))
+        .collect()
+}
+
+/// Print a diff like `kubectl diff`, colored if written to a terminal.
+fn print_drift(resource: &str, drift: &Drift) {
+    let colored = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let paint = |code: &str, text: String| {
+        if colored {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text
+        }
+    };
+    match drift {
+        Drift::InSync => {}
+        Drift::Missing => {
+            println!("{}", paint("1", format!("--- {}", resource)));
+            println!("{}", paint("32", "+ does not exist on GitHub".to_string()));
+        }
+        Drift::Changed(fields) => {
+            println!("{}", paint("1", format!("--- {}", resource)));
+            for field in fields {
+                println!(
+                    "{}",
+                    paint("31", format!("- {}: {}", field.path, field.actual))
+                );
+                println!(
+                    "{}",
+                    paint("32", format!("+ {}: {}", field.path, field.desired))
+                );
+            }
+        }
+    }
+}
+
+/// Read a `Repository` manifest of any served version or only its spec.
+fn read_repository_spec(path: &Path, full_name: &str) -> Result<RepositorySpec, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+    let mut document: serde_json::Value = serde_yaml::from_str(&content)
+        .map_err(|e| format!("couldn't parse {}: {}", path.display(), e))?;
+    if document.get("apiVersion").is_some() {
+        document = convert(document, V1BETA1)
+            .map_err(|e| format!("invalid manifest in {}: {}", path.display(), e))?;
+    }
+    if let Some(spec) = document.get_mut("spec") {
+        document = spec.take();
+    }
+    // the repository given as argument wins
+    if let Some(spec) = document.as_object_mut() {
+        spec.insert("fullName".into(), full_name.into());
+    }
+    serde_json::from_value(document)
+        .map_err(|e| format!("invalid spec in {}: {}", path.display(), e))
+}
+
+fn exit_with_error(message: impl std::fmt::Display) -> ! {
+    exit_with(1, message)
+}
+
+fn exit_with(code: i32, message: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use github_operator::domain::model::RepositoryFullView;
+    use serde_json::json;
+
+    use super::*;
+
+    fn imported_resources() -> BTreeMap<String, Vec<Manifest>> {
+        let view: RepositoryFullView = serde_json::from_value(json!({
+            "full_name": "octo-org/hello-world",
+            "repository": {
+                "security_and_analysis": {
+                    "secret_scanning": { "status": "enabled" },
+                },
+                "delete_branch_on_merge": true,
+                "allow_auto_merge": false,
+                "allow_squash_merge": true,
+            },
+            "autolink_references": [
+                {
+                    "id": 1,
+                    "key_prefix": "JIRA-",
+                    "url_template": "https://jira.example.com/browse/JIRA-<num>",
+                    "is_alphanumeric": false,
+                },
+            ],
+            "teams": [
+                { "org": "octo-org", "team_slug": "developers", "permission": "push" },
+            ],
+        }))
+        .unwrap();
+        let name = render_name_template("{repo}", &view.full_name);
+        let manifests = Manifest::from_view(
+            &view,
+            &render_name_template("{owner}", &view.full_name),
+            &name,
+        );
+        BTreeMap::from([(name, manifests)])
+    }
+
+    #[test]
+    fn renders_imported_manifests_as_yaml() {
+        let manifests = imported_resources()
+            .into_values()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            render_manifests(&manifests),
+            include_str!("testdata/import.yaml")
+        );
+    }
+
+    #[test]
+    fn renders_imported_manifests_as_cue() {
+        assert_eq!(
+            cue::render(&imported_resources()),
+            include_str!("testdata/import.cue")
+        );
+    }
+}
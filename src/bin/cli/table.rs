@@ -0,0 +1,109 @@
+use github_operator::domain::model::RepositoryFullView;
+
+/// Print the repository as a table per section.
+pub fn print_view(view: &RepositoryFullView) {
+    println!("REPOSITORY: {}", view.full_name);
+    if let Some(repository) = &view.repository {
+        let value = serde_json::to_value(repository).expect("repository should be serializable");
+        let mut rows = Vec::new();
+        flatten("", &value, &mut rows);
+        print_table(&["SETTING", "VALUE"], rows);
+    }
+    if let Some(autolink_references) = &view.autolink_references {
+        print_table(
+            &["ID", "KEY PREFIX", "URL TEMPLATE", "ALPHANUMERIC"],
+            autolink_references
+                .iter()
+                .map(|autolink_reference| {
+                    vec![
+                        autolink_reference.id.to_string(),
+                        autolink_reference.key_prefix.clone(),
+                        autolink_reference.url_template.clone(),
+                        autolink_reference.is_alphanumeric.to_string(),
+                    ]
+                })
+                .collect(),
+        );
+    }
+    if let Some(teams) = &view.teams {
+        print_table(
+            &["TEAM", "PERMISSION"],
+            teams
+                .iter()
+                .map(|team| {
+                    vec![
+                        format!("{}/{}", team.org, team.team_slug),
+                        team.permission.clone(),
+                    ]
+                })
+                .collect(),
+        );
+    }
+    if let Some(collaborators) = &view.collaborators {
+        print_table(
+            &["COLLABORATOR", "ROLE"],
+            collaborators
+                .iter()
+                .map(|collaborator| {
+                    vec![collaborator.login.clone(), collaborator.role_name.clone()]
+                })
+                .collect(),
+        );
+    }
+    if let Some(rulesets) = &view.rulesets {
+        print_table(
+            &["RULESET", "ID", "TARGET", "ENFORCEMENT"],
+            rulesets
+                .iter()
+                .map(|ruleset| {
+                    vec![
+                        ruleset.name.clone(),
+                        ruleset.id.to_string(),
+                        ruleset.target.clone().unwrap_or_default(),
+                        ruleset.enforcement.clone(),
+                    ]
+                })
+                .collect(),
+        );
+    }
+}
+
+/// Print the rows with aligned columns like `kubectl get`.
+pub fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    println!();
+    let headers = headers.iter().map(|header| header.to_string()).collect();
+    for row in std::iter::once(headers).chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("   ");
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Flatten nested settings to dotted names, e.g. `security_and_analysis.secret_scanning.status`.
+fn flatten(prefix: &str, value: &serde_json::Value, rows: &mut Vec<Vec<String>>) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, value) in fields {
+                let name = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                flatten(&name, value, rows);
+            }
+        }
+        serde_json::Value::Null => {}
+        serde_json::Value::String(value) => rows.push(vec![prefix.to_string(), value.clone()]),
+        value => rows.push(vec![prefix.to_string(), value.to_string()]),
+    }
+}
@@ -0,0 +1,46 @@
+package config
+
+_resources: {
+	"hello-world": [
+		#Repository & {
+			metadata: {
+				name: "hello-world"
+				namespace: "octo-org"
+			}
+			spec: {
+				allow_auto_merge: false
+				allow_squash_merge: true
+				delete_branch_on_merge: true
+				full_name: "octo-org/hello-world"
+				security_and_analysis: {
+					secret_scanning: {
+						status: "enabled"
+					}
+				}
+			}
+		},
+		#AutolinkReference & {
+			metadata: {
+				name: "hello-world-jira"
+				namespace: "octo-org"
+			}
+			spec: {
+				full_name: "octo-org/hello-world"
+				is_alphanumeric: false
+				key_prefix: "JIRA-"
+				url_template: "https://jira.example.com/browse/JIRA-<num>"
+			}
+		},
+		#RepositoryPermission & {
+			metadata: {
+				name: "hello-world-developers"
+				namespace: "octo-org"
+			}
+			spec: {
+				full_name: "octo-org/hello-world"
+				full_team_name: "octo-org/developers"
+				permission: "push"
+			}
+		},
+	]
+}
@@ -1,13 +1,44 @@
-use github_operator::domain::model::autolink_reference::AutolinkReference;
-use github_operator::domain::model::permission::RepositoryPermission;
-use github_operator::domain::model::repository::Repository;
+use std::collections::BTreeMap;
+
+use github_operator::domain::model::conversion;
+use github_operator::domain::model::tenant_policy::GitHubTenantPolicy;
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+    CustomResourceConversion, CustomResourceDefinition, ServiceReference, WebhookClientConfig,
+    WebhookConversion,
+};
 use kube::CustomResourceExt;
 
 fn main() {
-    let crds = vec![
-        serde_yaml::to_string(&Repository::crd()).unwrap(),
-        serde_yaml::to_string(&AutolinkReference::crd()).unwrap(),
-        serde_yaml::to_string(&RepositoryPermission::crd()).unwrap(),
-    ];
+    let crds = ["Repository", "AutolinkReference", "RepositoryPermission"]
+        .into_iter()
+        .map(|kind| with_conversion_webhook(conversion::crd(kind).unwrap()))
+        .chain(std::iter::once(GitHubTenantPolicy::crd()))
+        .map(|crd| serde_yaml::to_string(&crd).unwrap())
+        .collect::<Vec<_>>();
     print!("{}", crds.join("\n---\n"))
 }
+
+/// The operator converts between the versions, the webhook service and the certificate are the
+/// ones of `demo/webhook`.
+fn with_conversion_webhook(mut crd: CustomResourceDefinition) -> CustomResourceDefinition {
+    crd.metadata.annotations = Some(BTreeMap::from([(
+        "cert-manager.io/inject-ca-from".to_string(),
+        "github-operator/github-operator-webhook".to_string(),
+    )]));
+    crd.spec.conversion = Some(CustomResourceConversion {
+        strategy: "Webhook".into(),
+        webhook: Some(WebhookConversion {
+            client_config: Some(WebhookClientConfig {
+                service: Some(ServiceReference {
+                    name: "github-operator-webhook".into(),
+                    namespace: "github-operator".into(),
+                    path: Some("/convert".into()),
+                    port: None,
+                }),
+                ..WebhookClientConfig::default()
+            }),
+            conversion_review_versions: vec!["v1".into()],
+        }),
+    });
+    crd
+}
@@ -0,0 +1,62 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::ControllerError;
+
+/// Read an environment variable, falling back to the default if it is not set.
+pub fn env_or<T: FromStr>(name: &str, default: T) -> Result<T, ControllerError> {
+    match std::env::var(name) {
+        Ok(value) => value.parse().map_err(|_| {
+            ControllerError::ConfigurationError(format!("invalid value for {}: {}", name, value))
+        }),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Read a duration like `30s`, `5m` or `1h` from an environment variable.
+pub fn env_duration_or(name: &str, default: Duration) -> Result<Duration, ControllerError> {
+    match std::env::var(name) {
+        Ok(value) => parse_duration(&value).ok_or_else(|| {
+            ControllerError::ConfigurationError(format!("invalid duration for {}: {}", name, value))
+        }),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Parse a duration like `30s`, `5m` or `1h`, plain numbers are seconds.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (amount, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "s" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_secs(amount * 60)),
+        "h" => Some(Duration::from_secs(amount * 60 * 60)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration(" 2h "), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("15"), Some(Duration::from_secs(15)));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("5d"), None);
+        assert_eq!(parse_duration("1.5h"), None);
+        assert_eq!(parse_duration("-5s"), None);
+    }
+}
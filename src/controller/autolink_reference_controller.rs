@@ -1,185 +1,106 @@
-use std::sync::Arc;
-use std::time::Duration;
-
-use futures::StreamExt;
-use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
-use k8s_openapi::chrono;
-use kube::api::{Patch, PatchParams};
-use kube::runtime::controller::Action;
-use kube::runtime::events::Recorder;
-use kube::runtime::finalizer::{finalizer, Event};
-use kube::runtime::watcher::Config;
+use async_trait::async_trait;
+use garde::Validate;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
 use kube::runtime::Controller;
-use kube::{Api, Client, Resource, ResourceExt};
-use serde_json::json;
-use tracing::{instrument, Instrument};
+use kube::Client;
 
-use crate::controller::finalizer_name;
+use crate::controller::event_subject;
+use crate::controller::managed_resource::ManagedResource;
+use crate::controller::ownership::{self, RepositoryChild, RepositoryOwnership};
 use crate::domain::delete_autolink_reference_use_case::DeleteAutolinkReferenceUseCase;
-use crate::domain::model::autolink_reference::{AutolinkReference, AutolinkReferenceStatus};
+use crate::domain::model::autolink_reference::{
+    AutolinkReference, AutolinkReferenceSpec, AutolinkReferenceStatus,
+};
+use crate::domain::model::tenant_policy::TenantPolicy;
+use crate::domain::model::{Change, ReconcileStatus, RepositoryRef};
 use crate::domain::reconcile_autolink_reference_use_case::ReconcileAutolinkReferenceUseCase;
-use crate::extensions::DurationExtension;
 use crate::ControllerError;
 
-pub async fn run(
-    controller_context: AutolinkReferenceControllerContext,
-) -> Result<(), ControllerError> {
-    Controller::new(
-        controller_context.autolink_reference_api.clone(),
-        Config::default().any_semantic(),
-    )
-    .shutdown_on_signal()
-    .run(reconcile, handle_errors, controller_context.into())
-    .for_each(|res| async move {
-        match res {
-            Ok(o) => log::info!("reconciled {:?}", o),
-            Err(e) => log::warn!("reconcile failed: {}", e),
-        }
-    })
-    .await;
-
-    Ok(())
+pub struct AutolinkReferenceControllerContext {
+    pub reconcile_use_case: ReconcileAutolinkReferenceUseCase,
+    pub delete_use_case: DeleteAutolinkReferenceUseCase,
+    pub ownership: RepositoryOwnership,
 }
 
-#[instrument(ret, err, skip(object, ctx))]
-async fn reconcile(
-    object: Arc<AutolinkReference>,
-    ctx: Arc<AutolinkReferenceControllerContext>,
-) -> Result<Action, ControllerError> {
-    let reference = object.object_ref(&());
-    log::info!("reconcile: {:?}", reference);
-    // must be namespaced
-    let recorder = Recorder::new(
-        ctx.client.clone(),
-        "autolink-reference-github-controller".into(),
-    );
-    let autolink_reference_api = Api::<AutolinkReference>::namespaced(
-        ctx.client.clone(),
-        object
-            .metadata
-            .namespace
-            .as_ref()
-            .ok_or_else(|| ControllerError::IllegalDocument)?,
-    );
+#[async_trait]
+impl ManagedResource for AutolinkReference {
+    type Context = AutolinkReferenceControllerContext;
+    /// The id of the autolink reference on GitHub
+    type Output = u32;
+    type Status = AutolinkReferenceStatus;
 
-    finalizer(
-        &autolink_reference_api,
-        finalizer_name("autolink-reference").as_str(),
-        object,
-        |event| async {
-            match event {
-                Event::Apply(autolink_reference) => {
-                    log::info!("object ref: {:?}", autolink_reference.object_ref(&()));
-                    match ctx
-                        .reconcile_use_case
-                        .execute(&autolink_reference, recorder)
-                        .instrument(tracing::info_span!("apply"))
-                        .await
-                    {
-                        Ok(id) => {
-                            update_status(
-                                &autolink_reference_api,
-                                autolink_reference,
-                                Some(id),
-                                None,
-                            )
-                            .await?;
-                            Ok(Action::requeue(Duration::from_minutes(1)))
-                        }
-                        Err(e) => {
-                            log::error!("reconcile failed: {:?}", e);
-                            update_status(
-                                &autolink_reference_api,
-                                autolink_reference,
-                                None,
-                                Some(e),
-                            )
-                            .await?;
-                            Ok(Action::requeue(Duration::from_secs(5)))
-                        }
-                    }
-                }
-                Event::Cleanup(github_repository) => {
-                    match ctx
-                        .delete_use_case
-                        .execute(&github_repository, recorder)
-                        .instrument(tracing::info_span!("cleanup"))
-                        .await
-                    {
-                        Ok(_) => Ok(Action::requeue(Duration::from_minutes(1))),
-                        Err(_) => Ok(Action::requeue(Duration::from_secs(5))),
-                    }
-                }
-            }
-        },
-    )
-    .instrument(tracing::info_span!("finalizer"))
-    .await
-    .map_err(|e| ControllerError::FinalizerError(Box::new(e)))
-}
+    const NAME: &'static str = "autolink-reference";
 
-fn handle_errors(
-    _github_repository: Arc<AutolinkReference>,
-    error: &ControllerError,
-    _ctx: Arc<AutolinkReferenceControllerContext>,
-) -> Action {
-    log::warn!("reconcile failed: {:?}", error,);
-    Action::requeue(Duration::from_secs(5))
-}
+    fn validate_spec(&self) -> Result<(), garde::Report> {
+        self.spec.validate()
+    }
+
+    fn check_tenancy(&self, policy: &TenantPolicy) -> Result<(), String> {
+        policy.check_repository(&self.spec.full_name)
+    }
+
+    async fn apply(
+        &self,
+        ctx: &AutolinkReferenceControllerContext,
+    ) -> Result<(u32, Change), ControllerError> {
+        // follows the repository if it was renamed
+        let spec = AutolinkReferenceSpec {
+            full_name: ctx.ownership.adopt(self).await?,
+            ..self.spec.clone()
+        };
+        ctx.reconcile_use_case
+            .execute(&event_subject(self), &spec, self.id())
+            .await
+    }
 
-async fn update_status(
-    api: &Api<AutolinkReference>,
-    autolink_reference: Arc<AutolinkReference>,
-    id: Option<u32>,
-    e: Option<ControllerError>,
-) -> Result<(), ControllerError> {
-    let name = autolink_reference.name_unchecked();
-    let ready = match e {
-        Some(_) => Condition {
-            type_: "Ready".into(),
-            status: "False".into(),
-            reason: "ReconcileFailed".into(),
-            message: "Reconcile failed".into(),
-            last_transition_time: Time(chrono::Utc::now()),
-            observed_generation: autolink_reference.metadata.generation,
-        },
-        None => Condition {
-            type_: "Ready".into(),
-            status: "True".into(),
-            reason: "ReconcileSucceed".into(),
-            message: "Reconcile succeed".into(),
-            last_transition_time: Time(chrono::Utc::now()),
-            observed_generation: autolink_reference.metadata.generation,
-        },
-    };
-    let conditions = vec![ready];
-    let healthy = match e {
-        Some(_) => Some(false),
-        None => Some(true),
-    };
-    let status = json!({
-        "status": AutolinkReferenceStatus {
+    async fn cleanup(
+        &self,
+        ctx: &AutolinkReferenceControllerContext,
+    ) -> Result<(), ControllerError> {
+        let full_name = ctx.ownership.full_name(self).await?;
+        ctx.delete_use_case
+            .execute(&event_subject(self), &full_name, self.id())
+            .await
+    }
+
+    fn watch_related(controller: Controller<Self>, client: &Client) -> Controller<Self> {
+        ownership::watch_repositories(controller, client)
+    }
+
+    fn reconcile_status(&self) -> Option<&ReconcileStatus> {
+        self.status.as_ref().map(|status| &status.reconcile)
+    }
+
+    fn conditions(&self) -> &[Condition] {
+        self.status
+            .as_ref()
+            .map_or(&[], |status| status.conditions.as_slice())
+    }
+
+    fn build_status(
+        &self,
+        conditions: Vec<Condition>,
+        healthy: bool,
+        id: Option<u32>,
+        reconcile: ReconcileStatus,
+    ) -> AutolinkReferenceStatus {
+        // keep the known id if the reconciliation failed, otherwise the merge patch removes it
+        let id = id.or_else(|| self.status.as_ref().and_then(|status| status.id));
+        AutolinkReferenceStatus {
             conditions,
-            healthy,
+            healthy: Some(healthy),
             id,
+            reconcile,
         }
-    });
-    log::debug!("patching {} status with: {:#?}", name, status);
-    api.patch_status(
-        name.as_str(),
-        &PatchParams::default(),
-        &Patch::Merge(&status),
-    )
-    .await
-    .map_err(ControllerError::KubeError)?;
-
-    Ok(())
+    }
 }
 
-pub struct AutolinkReferenceControllerContext {
-    /// Kubernetes client
-    pub client: Client,
-    pub autolink_reference_api: Api<AutolinkReference>,
-    pub reconcile_use_case: ReconcileAutolinkReferenceUseCase,
-    pub delete_use_case: DeleteAutolinkReferenceUseCase,
+impl RepositoryChild for AutolinkReference {
+    fn full_name(&self) -> &str {
+        &self.spec.full_name
+    }
+
+    fn repository_ref(&self) -> Option<&RepositoryRef> {
+        self.spec.repository_ref.as_ref()
+    }
 }
@@ -0,0 +1,133 @@
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+
+use crate::{ControllerError, ErrorClass};
+
+/// The resource matches its spec on GitHub.
+pub const READY: &str = "Ready";
+/// The controller retries a failure that may go away on its own, e.g. once the token is rotated.
+pub const RECONCILING: &str = "Reconciling";
+/// The controller can't make progress until the spec, the policy or the permissions of the token
+/// change.
+pub const STALLED: &str = "Stalled";
+
+/// The conditions after a reconciliation in the style of kstatus.
+///
+/// `Ready` is always set, `Reconciling` and `Stalled` only while they are true. The transition
+/// time of a condition is kept from `previous` as long as its status doesn't change.
+pub fn conditions(
+    previous: &[Condition],
+    generation: Option<i64>,
+    error: Option<&ControllerError>,
+    now: Time,
+) -> Vec<Condition> {
+    let condition = |type_: &str, status: bool, reason: &str, message: String| {
+        let status = if status { "True" } else { "False" };
+        let last_transition_time = previous
+            .iter()
+            .find(|condition| condition.type_ == type_ && condition.status == status)
+            .map_or_else(
+                || now.clone(),
+                |condition| condition.last_transition_time.clone(),
+            );
+        Condition {
+            type_: type_.into(),
+            status: status.into(),
+            reason: reason.into(),
+            message,
+            last_transition_time,
+            observed_generation: generation,
+        }
+    };
+    match error {
+        None => vec![condition(
+            READY,
+            true,
+            "ReconcileSucceed",
+            "Reconcile succeed".into(),
+        )],
+        Some(e) => {
+            let progress = match e.class() {
+                // like the requeue, an invalid token is retried with a backoff
+                ErrorClass::Retryable | ErrorClass::Auth => RECONCILING,
+                ErrorClass::Permanent | ErrorClass::PermissionDenied => STALLED,
+            };
+            vec![
+                condition(READY, false, e.reason(), e.message()),
+                condition(progress, true, e.reason(), e.message()),
+            ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::chrono::{TimeZone, Utc};
+
+    use super::*;
+    use github_operator::GitHubError;
+
+    fn time(minute: u32) -> Time {
+        Time(Utc.with_ymd_and_hms(2024, 1, 1, 0, minute, 0).unwrap())
+    }
+
+    fn find<'a>(conditions: &'a [Condition], type_: &str) -> Option<&'a Condition> {
+        conditions.iter().find(|condition| condition.type_ == type_)
+    }
+
+    #[test]
+    fn keeps_the_transition_time_of_an_unchanged_status() {
+        let ready = conditions(&[], Some(1), None, time(0));
+        let conditions = conditions(&ready, Some(2), None, time(1));
+
+        assert_eq!(conditions.len(), 1);
+        let ready = find(&conditions, READY).unwrap();
+        assert_eq!(ready.status, "True");
+        assert_eq!(ready.last_transition_time, time(0));
+        assert_eq!(ready.observed_generation, Some(2));
+    }
+
+    #[test]
+    fn retryable_errors_are_reconciling() {
+        let ready = conditions(&[], Some(1), None, time(0));
+        let error = ControllerError::WaitingForRepository("owner/repo".into());
+        let conditions = conditions(&ready, Some(1), Some(&error), time(1));
+
+        let ready = find(&conditions, READY).unwrap();
+        assert_eq!(ready.status, "False");
+        assert_eq!(ready.last_transition_time, time(1));
+        assert_eq!(find(&conditions, RECONCILING).unwrap().status, "True");
+        assert!(find(&conditions, STALLED).is_none());
+    }
+
+    #[test]
+    fn auth_errors_are_reconciling() {
+        let error = ControllerError::GitHubError(GitHubError {
+            status: 401,
+            message: "Bad credentials".into(),
+            documentation_url: None,
+            errors: Vec::new(),
+            rate_limit_remaining: None,
+            retry_after: None,
+        });
+        let conditions = conditions(&[], Some(1), Some(&error), time(0));
+
+        assert_eq!(
+            find(&conditions, RECONCILING).unwrap().reason,
+            "Unauthorized"
+        );
+        assert!(find(&conditions, STALLED).is_none());
+    }
+
+    #[test]
+    fn permanent_errors_are_stalled() {
+        let error = ControllerError::InvalidSpec("invalid".into());
+        let stalled = conditions(&[], Some(1), Some(&error), time(0));
+        let conditions = conditions(&stalled, Some(1), Some(&error), time(1));
+
+        assert_eq!(
+            find(&conditions, STALLED).unwrap().last_transition_time,
+            time(0)
+        );
+        assert!(find(&conditions, RECONCILING).is_none());
+    }
+}
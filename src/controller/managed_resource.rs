@@ -0,0 +1,397 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use futures::future::{BoxFuture, Shared};
+use futures::StreamExt;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::{chrono, NamespaceResourceScope};
+use kube::api::{Patch, PatchParams};
+use kube::runtime::controller::Action;
+use kube::runtime::finalizer::{finalizer, Event};
+use kube::runtime::watcher::{watcher, Config};
+use kube::runtime::{reflector, Controller, WatchStreamExt};
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::json;
+use tracing::{instrument, Instrument};
+
+use crate::controller::requeue::Requeue;
+use crate::controller::tenancy::TenantPolicyResolver;
+use crate::controller::{conditions, finalizer_name, spec_or_finalizers};
+use crate::domain::model::tenant_policy::TenantPolicy;
+use crate::domain::model::{Change, ReconcileStatus};
+use crate::metrics::ReconcileMetrics;
+use crate::server::Readiness;
+use crate::ControllerError;
+
+/// A custom resource that is reconciled against GitHub by the generic controller.
+///
+/// Implementations only contain the domain logic; finalizers, status updates and requeue
+/// handling are the same for every kind.
+#[async_trait]
+pub trait ManagedResource:
+    Resource<DynamicType = (), Scope = NamespaceResourceScope>
+    + Clone
+    + Debug
+    + DeserializeOwned
+    + Serialize
+    + Send
+    + Sync
+    + 'static
+{
+    /// The use cases needed to reconcile the resource.
+    type Context: Send + Sync + 'static;
+    /// The result of a successful [`ManagedResource::apply`] that ends up in the status.
+    type Output: Send;
+    type Status: Serialize + Debug + Send;
+
+    /// Name of the controller, used for the finalizer and as event reporter.
+    const NAME: &'static str;
+
+    /// Check the spec before it is applied, invalid objects are not retried until they change.
+    fn validate_spec(&self) -> Result<(), garde::Report>;
+
+    /// Check that the namespace may manage the resource, see `GitHubTenantPolicy`.
+    fn check_tenancy(&self, policy: &TenantPolicy) -> Result<(), String>;
+
+    /// Create or update the resource on GitHub.
+    async fn apply(&self, ctx: &Self::Context) -> Result<(Self::Output, Change), ControllerError>;
+
+    /// Remove the resource from GitHub, called before the finalizer is removed.
+    async fn cleanup(&self, ctx: &Self::Context) -> Result<(), ControllerError>;
+
+    /// Watch related kinds, e.g. the children of a repository.
+    fn watch_related(controller: Controller<Self>, _client: &Client) -> Controller<Self> {
+        controller
+    }
+
+    /// The outcome of the previous reconciliations from the status.
+    fn reconcile_status(&self) -> Option<&ReconcileStatus>;
+
+    /// The conditions from the status.
+    fn conditions(&self) -> &[Condition];
+
+    /// Build the status that is patched after every apply.
+    fn build_status(
+        &self,
+        conditions: Vec<Condition>,
+        healthy: bool,
+        output: Option<Self::Output>,
+        reconcile: ReconcileStatus,
+    ) -> Self::Status;
+}
+
+/// Resolves when the controllers should stop, e.g. on SIGTERM or because the leadership was lost.
+pub type ShutdownSignal = Shared<BoxFuture<'static, ()>>;
+
+pub struct ControllerContext<K: ManagedResource> {
+    /// Kubernetes client
+    pub client: Client,
+    pub api: Api<K>,
+    pub context: K::Context,
+    pub metrics: ReconcileMetrics,
+    pub tenancy: TenantPolicyResolver,
+    pub requeue: Requeue,
+}
+
+pub async fn run<K: ManagedResource>(
+    controller_context: ControllerContext<K>,
+    shutdown: ShutdownSignal,
+    readiness: Arc<Readiness>,
+) -> Result<(), ControllerError> {
+    let (store, writer) = reflector::store();
+    let resources = watcher(
+        controller_context.api.clone(),
+        Config::default().any_semantic(),
+    )
+    .default_backoff()
+    .reflect(writer)
+    .applied_objects()
+    .predicate_filter(spec_or_finalizers);
+    let controller = K::watch_related(
+        Controller::for_stream(resources, store),
+        &controller_context.client,
+    )
+    .graceful_shutdown_on(shutdown);
+
+    // the controller is ready as soon as the watch stream received the initial list
+    readiness.controller_registered(K::NAME);
+    let store = controller.store();
+    tokio::spawn(async move {
+        if store.wait_until_ready().await.is_ok() {
+            readiness.controller_started(K::NAME);
+        }
+    });
+
+    controller
+        .run(reconcile, handle_errors, controller_context.into())
+        .for_each(|res| async move {
+            match res {
+                Ok(o) => log::info!("reconciled {:?}", o),
+                Err(e) => log::warn!("reconcile failed: {:#?}", e),
+            }
+        })
+        .await;
+
+    Ok(())
+}
+
+#[instrument(ret, err, skip(object, ctx), fields(controller = K::NAME))]
+async fn reconcile<K: ManagedResource>(
+    object: Arc<K>,
+    ctx: Arc<ControllerContext<K>>,
+) -> Result<Action, ControllerError> {
+    log::info!("reconcile: {:?}", object.object_ref(&()));
+    let started = Instant::now();
+    let key = format!(
+        "{}/{}",
+        object.namespace().unwrap_or_default(),
+        object.name_any()
+    );
+    let api = Api::<K>::namespaced(
+        ctx.client.clone(),
+        object.namespace().as_ref().ok_or_else(|| {
+            ControllerError::IllegalDocument("the object has no namespace".into())
+        })?,
+    );
+
+    finalizer(
+        &api,
+        finalizer_name(K::NAME).as_str(),
+        object,
+        |event| async {
+            match event {
+                Event::Apply(resource) => {
+                    log::info!("object ref: {:?}", resource.object_ref(&()));
+                    let result = match admit(&*resource, &ctx).await {
+                        Ok(()) => {
+                            resource
+                                .apply(&ctx.context)
+                                .instrument(tracing::info_span!("apply"))
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    ctx.metrics
+                        .reconciled(K::NAME, result.is_ok(), started.elapsed());
+                    ctx.metrics
+                        .set_ready(K::NAME, key.clone(), Some(result.is_ok()));
+                    match result {
+                        Ok((output, change)) => {
+                            if change == Change::Updated {
+                                ctx.metrics.drift_corrected(K::NAME);
+                            }
+                            update_status(&api, &resource, Some((output, change)), None).await?;
+                            Ok(Action::requeue(ctx.requeue.succeeded(&key, &*resource)))
+                        }
+                        Err(e @ ControllerError::InvalidSpec(_)) => {
+                            log::warn!("invalid spec: {}", e);
+                            update_status(&api, &resource, None, Some(&e)).await?;
+                            ctx.requeue.reset(&key);
+                            Ok(Action::await_change())
+                        }
+                        Err(e @ ControllerError::PolicyViolation(_)) => {
+                            log::warn!("denied by tenant policy: {}", e);
+                            update_status(&api, &resource, None, Some(&e)).await?;
+                            // the policy may change
+                            Ok(Action::requeue(ctx.requeue.failed(&key, &e)))
+                        }
+                        Err(e @ ControllerError::WaitingForRepository(_)) => {
+                            log::info!("{}", e);
+                            update_status(&api, &resource, None, Some(&e)).await?;
+                            // the repository is watched as well, this is only a fallback
+                            Ok(Action::requeue(ctx.requeue.failed(&key, &e)))
+                        }
+                        Err(e) => {
+                            log::error!("reconcile failed ({}): {}", e.reason(), e);
+                            update_status(&api, &resource, None, Some(&e)).await?;
+                            Ok(Action::requeue(ctx.requeue.failed(&key, &e)))
+                        }
+                    }
+                }
+                Event::Cleanup(resource) => {
+                    // an invalid spec can't address anything on GitHub and resources of other
+                    // tenants must not be touched, so there is nothing to clean up
+                    let result = match admit(&*resource, &ctx).await {
+                        Ok(()) => {
+                            resource
+                                .cleanup(&ctx.context)
+                                .instrument(tracing::info_span!("cleanup"))
+                                .await
+                        }
+                        Err(
+                            ControllerError::InvalidSpec(_) | ControllerError::PolicyViolation(_),
+                        ) => Ok(()),
+                        Err(e) => Err(e),
+                    };
+                    ctx.metrics.cleaned_up(K::NAME, result.is_ok());
+                    match result {
+                        Ok(_) => {
+                            ctx.metrics.set_ready(K::NAME, key.clone(), None);
+                            ctx.requeue.reset(&key);
+                            Ok(Action::await_change())
+                        }
+                        Err(e @ ControllerError::WaitingForChildren(_)) => {
+                            log::info!("{}", e);
+                            Ok(Action::requeue(ctx.requeue.failed(&key, &e)))
+                        }
+                        Err(e) => {
+                            log::error!("cleanup failed ({}): {}", e.reason(), e);
+                            Ok(Action::requeue(ctx.requeue.failed(&key, &e)))
+                        }
+                    }
+                }
+            }
+        },
+    )
+    .instrument(tracing::info_span!("finalizer"))
+    .await
+    .map_err(|e| ControllerError::FinalizerError(Box::new(e)))
+}
+
+/// The checks that run before any GitHub call.
+async fn admit<K: ManagedResource>(
+    resource: &K,
+    ctx: &ControllerContext<K>,
+) -> Result<(), ControllerError> {
+    check_spec(resource)?;
+    let namespace = resource
+        .namespace()
+        .ok_or_else(|| ControllerError::IllegalDocument("the object has no namespace".into()))?;
+    let policy = ctx.tenancy.resolve(&namespace).await?;
+    resource
+        .check_tenancy(&policy)
+        .map_err(ControllerError::PolicyViolation)
+}
+
+/// An invalid spec is not retried, it stalls until the object changes.
+fn check_spec<K: ManagedResource>(resource: &K) -> Result<(), ControllerError> {
+    resource
+        .validate_spec()
+        .map_err(|report| ControllerError::InvalidSpec(report.to_string()))
+}
+
+fn handle_errors<K: ManagedResource>(
+    object: Arc<K>,
+    error: &ControllerError,
+    ctx: Arc<ControllerContext<K>>,
+) -> Action {
+    log::warn!("reconcile failed: {:?}", error);
+    let key = format!(
+        "{}/{}",
+        object.namespace().unwrap_or_default(),
+        object.name_any()
+    );
+    Action::requeue(ctx.requeue.failed(&key, error))
+}
+
+async fn update_status<K: ManagedResource>(
+    api: &Api<K>,
+    resource: &K,
+    applied: Option<(K::Output, Change)>,
+    e: Option<&ControllerError>,
+) -> Result<(), ControllerError> {
+    let name = resource.name_unchecked();
+    let now = Time(chrono::Utc::now());
+    let generation = resource.meta().generation;
+    let previous = resource.reconcile_status().cloned().unwrap_or_default();
+    let (output, reconcile) = match applied {
+        Some((output, change)) => (Some(output), previous.reconciled(change, now.clone())),
+        None => (None, previous),
+    };
+    let reconcile = ReconcileStatus {
+        observed_generation: generation,
+        ..reconcile
+    };
+    let conditions = conditions::conditions(resource.conditions(), generation, e, now);
+    let status =
+        serde_json::to_value(resource.build_status(conditions, e.is_none(), output, reconcile))
+            .map_err(ControllerError::SerializationError)?;
+    let current = serde_json::to_value(resource)
+        .map_err(ControllerError::SerializationError)?
+        .get("status")
+        .cloned();
+    // neither an unchanged reconciliation nor a failure that is retried change the status again,
+    // the patch would only trigger the watches
+    if current.as_ref() == Some(&status) {
+        log::debug!("status of {} is unchanged", name);
+        return Ok(());
+    }
+    log::debug!("patching {} status with: {:#?}", name, status);
+    api.patch_status(
+        name.as_str(),
+        &PatchParams::default(),
+        &Patch::Merge(&json!({ "status": status })),
+    )
+    .await
+    .map_err(ControllerError::KubeError)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::model::permission::{RepositoryPermission, RepositoryPermissionSpec};
+    use crate::domain::model::repository::{Repository, RepositorySpec};
+    use crate::ErrorClass;
+
+    fn permission(full_team_name: &str, permission: &str) -> RepositoryPermission {
+        RepositoryPermission::new(
+            "test-developers",
+            RepositoryPermissionSpec {
+                full_name: "otto-ec/pdh-da_test".into(),
+                full_team_name: full_team_name.into(),
+                permission: permission.into(),
+                repository_ref: None,
+            },
+        )
+    }
+
+    #[test]
+    fn a_valid_spec_is_admitted() {
+        assert!(check_spec(&permission("otto-ec/developers", "push")).is_ok());
+        assert!(check_spec(&Repository::new(
+            "test",
+            RepositorySpec {
+                full_name: "otto-ec/pdh-da_test".into(),
+                ..RepositorySpec::default()
+            }
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn an_invalid_previous_name_is_rejected() {
+        let repository = Repository::new(
+            "test",
+            RepositorySpec {
+                full_name: "otto-ec/pdh-da_test".into(),
+                previous_full_names: Some(vec!["otto-ec/pdh-da_old".into(), "pdh-da_old".into()]),
+                ..RepositorySpec::default()
+            },
+        );
+
+        let result = check_spec(&repository);
+
+        let Err(ControllerError::InvalidSpec(message)) = result else {
+            panic!("expected an invalid spec, got {:?}", result);
+        };
+        assert!(message.contains("previous_full_names[1]"), "{}", message);
+    }
+
+    #[test]
+    fn an_invalid_spec_stalls() {
+        let result = check_spec(&permission("otto-ec/Developers", "Admin"));
+
+        let Err(e @ ControllerError::InvalidSpec(_)) = result else {
+            panic!("expected an invalid spec, got {:?}", result);
+        };
+        assert_eq!(e.class(), ErrorClass::Permanent);
+        let message = e.to_string();
+        assert!(message.contains("full_team_name"), "{}", message);
+        assert!(message.contains("permission"), "{}", message);
+    }
+}
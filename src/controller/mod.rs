@@ -1,7 +1,42 @@
+use kube::runtime::{predicates, Predicate};
+use kube::{Resource, ResourceExt};
+
+use crate::domain::service::event_publisher::EventSubject;
+
 pub mod autolink_reference_controller;
+pub mod conditions;
+pub mod managed_resource;
+pub mod ownership;
 pub mod permission_controller;
 pub mod repository_controller;
+pub mod requeue;
+pub mod storage_version;
+pub mod tenancy;
 
 pub fn finalizer_name(controller_name: &str) -> String {
     format!("{}.github.platform.benkeil.de/finalizer", controller_name)
 }
+
+/// The reporter of the events of a controller.
+pub fn reporter(controller_name: &str) -> String {
+    format!("{}-github-controller", controller_name)
+}
+
+/// Hash what a reconciliation depends on, the watches ignore the status updates of the
+/// controllers, otherwise every status update would trigger another reconciliation.
+pub fn spec_or_finalizers<K: Resource>(resource: &K) -> Option<u64> {
+    predicates::generation
+        .combine(predicates::finalizers)
+        .hash_property(resource)
+}
+
+/// The object the domain events of a use case are about.
+pub fn event_subject<K: Resource<DynamicType = ()>>(resource: &K) -> EventSubject {
+    EventSubject {
+        api_version: K::api_version(&()).to_string(),
+        kind: K::kind(&()).to_string(),
+        namespace: resource.namespace(),
+        name: resource.name_any(),
+        uid: resource.uid(),
+    }
+}
@@ -0,0 +1,441 @@
+use std::fmt::Debug;
+
+use futures::Stream;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
+use k8s_openapi::NamespaceResourceScope;
+use kube::api::{DeleteParams, ListParams, Patch, PatchParams};
+use kube::runtime::reflector::ObjectRef;
+use kube::runtime::watcher::{self, watcher, Config};
+use kube::runtime::{Controller, Predicate, WatchStreamExt};
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::controller::{conditions, spec_or_finalizers};
+use crate::domain::model::autolink_reference::AutolinkReference;
+use crate::domain::model::permission::RepositoryPermission;
+use crate::domain::model::repository::Repository;
+use crate::domain::model::RepositoryRef;
+use crate::ControllerError;
+
+/// An object that changes a repository, it waits until the `Repository` managing the repository
+/// is ready and is deleted before the repository is archived.
+pub trait RepositoryChild:
+    Resource<DynamicType = (), Scope = NamespaceResourceScope>
+    + Clone
+    + Debug
+    + DeserializeOwned
+    + Send
+    + Sync
+    + 'static
+{
+    fn full_name(&self) -> &str;
+
+    fn repository_ref(&self) -> Option<&RepositoryRef>;
+
+    /// Whether the child belongs to the repository, by owner reference, `repositoryRef` or
+    /// `full_name`, including the previous names of the repository.
+    fn is_child_of(&self, repository: &Repository) -> bool {
+        if self.namespace() != repository.namespace() {
+            return false;
+        }
+        if is_owned_by(self, repository) {
+            return true;
+        }
+        match self.repository_ref() {
+            Some(repository_ref) => repository_ref.name == repository.name_any(),
+            None => repository.spec.manages(self.full_name()),
+        }
+    }
+}
+
+/// Resolves the `Repository` of child objects and deletes the children of a `Repository`.
+#[derive(Clone)]
+pub struct RepositoryOwnership {
+    client: Client,
+}
+
+impl RepositoryOwnership {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Make the `Repository` of the child its owner, fails with
+    /// [`ControllerError::WaitingForRepository`] until the repository is ready.
+    ///
+    /// Returns the name of the repository on GitHub, it differs from the `full_name` of the child
+    /// once the repository was renamed.
+    pub async fn adopt<K: RepositoryChild>(&self, child: &K) -> Result<String, ControllerError> {
+        let Some(repository) = self.find_repository(child).await? else {
+            return Err(ControllerError::WaitingForRepository(
+                match child.repository_ref() {
+                    Some(repository_ref) => {
+                        format!("Repository {} does not exist", repository_ref.name)
+                    }
+                    None => format!(
+                        "no Repository in the namespace manages {}",
+                        child.full_name()
+                    ),
+                },
+            ));
+        };
+        if !repository.spec.manages(child.full_name()) {
+            return Err(ControllerError::InvalidSpec(format!(
+                "repository_ref: Repository {} manages {}, not {}",
+                repository.name_any(),
+                repository.spec.full_name,
+                child.full_name()
+            )));
+        }
+        if repository.meta().deletion_timestamp.is_some() {
+            return Err(ControllerError::WaitingForRepository(format!(
+                "Repository {} is being deleted",
+                repository.name_any()
+            )));
+        }
+        if !is_ready(&repository) {
+            return Err(ControllerError::WaitingForRepository(format!(
+                "Repository {} is not ready",
+                repository.name_any()
+            )));
+        }
+        // references of a previous version are replaced
+        let owner = repository.controller_owner_ref(&());
+        if !child
+            .owner_references()
+            .iter()
+            .any(|reference| Some(reference) == owner.as_ref())
+        {
+            self.set_owner(child, &repository).await?;
+        }
+        Ok(repository.spec.full_name)
+    }
+
+    /// The name of the repository of the child on GitHub, without waiting for the repository.
+    pub async fn full_name<K: RepositoryChild>(
+        &self,
+        child: &K,
+    ) -> Result<String, ControllerError> {
+        Ok(self
+            .find_repository(child)
+            .await?
+            .filter(|repository| repository.spec.manages(child.full_name()))
+            .map_or_else(
+                || child.full_name().to_string(),
+                |repository| repository.spec.full_name,
+            ))
+    }
+
+    async fn find_repository<K: RepositoryChild>(
+        &self,
+        child: &K,
+    ) -> Result<Option<Repository>, ControllerError> {
+        let namespace = child.namespace().ok_or_else(|| {
+            ControllerError::IllegalDocument("the object has no namespace".into())
+        })?;
+        let repositories = Api::<Repository>::namespaced(self.client.clone(), &namespace);
+        match child.repository_ref() {
+            Some(repository_ref) => repositories
+                .get_opt(&repository_ref.name)
+                .await
+                .map_err(ControllerError::KubeError),
+            None => Ok(repositories
+                .list(&ListParams::default())
+                .await
+                .map_err(ControllerError::KubeError)?
+                .into_iter()
+                .find(|repository| repository.spec.manages(child.full_name()))),
+        }
+    }
+
+    /// Delete the children of the repository, fails with [`ControllerError::WaitingForChildren`]
+    /// until their finalizers removed them from GitHub.
+    pub async fn delete_children(&self, repository: &Repository) -> Result<(), ControllerError> {
+        let mut remaining = self
+            .delete_children_of_kind::<AutolinkReference>(repository)
+            .await?;
+        remaining.extend(
+            self.delete_children_of_kind::<RepositoryPermission>(repository)
+                .await?,
+        );
+        if remaining.is_empty() {
+            Ok(())
+        } else {
+            Err(ControllerError::WaitingForChildren(format!(
+                "waiting for {} to be deleted",
+                remaining.join(", ")
+            )))
+        }
+    }
+
+    async fn delete_children_of_kind<K: RepositoryChild>(
+        &self,
+        repository: &Repository,
+    ) -> Result<Vec<String>, ControllerError> {
+        let namespace = repository.namespace().ok_or_else(|| {
+            ControllerError::IllegalDocument("the object has no namespace".into())
+        })?;
+        let api = Api::<K>::namespaced(self.client.clone(), &namespace);
+        let children = api
+            .list(&ListParams::default())
+            .await
+            .map_err(ControllerError::KubeError)?
+            .into_iter()
+            .filter(|child| is_owned_by(child, repository))
+            .collect::<Vec<_>>();
+        for child in &children {
+            if child.meta().deletion_timestamp.is_none() {
+                log::info!(
+                    "deleting {} {} of Repository {}",
+                    K::kind(&()),
+                    child.name_any(),
+                    repository.name_any()
+                );
+                api.delete(&child.name_any(), &DeleteParams::default())
+                    .await
+                    .map_err(ControllerError::KubeError)?;
+            }
+        }
+        Ok(children
+            .iter()
+            .map(|child| format!("{} {}", K::kind(&()), child.name_any()))
+            .collect())
+    }
+
+    async fn set_owner<K: RepositoryChild>(
+        &self,
+        child: &K,
+        repository: &Repository,
+    ) -> Result<(), ControllerError> {
+        let owner = repository.controller_owner_ref(&()).ok_or_else(|| {
+            ControllerError::IllegalDocument("the repository has no name or uid".into())
+        })?;
+        // a child has one repository, the previous one is replaced if the spec changed
+        let owner_references = child
+            .owner_references()
+            .iter()
+            .filter(|reference| !is_repository(reference))
+            .cloned()
+            .chain(std::iter::once(owner))
+            .collect::<Vec<_>>();
+        let patch = json!({
+            "metadata": {
+                "ownerReferences": owner_references
+            }
+        });
+        Api::<K>::namespaced(self.client.clone(), &child.namespace().unwrap_or_default())
+            .patch(
+                &child.name_any(),
+                &PatchParams::default(),
+                &Patch::Merge(&patch),
+            )
+            .await
+            .map_err(ControllerError::KubeError)?;
+        Ok(())
+    }
+}
+
+/// Reconcile the children of a repository when it changes, e.g. once it is ready.
+pub fn watch_repositories<K: RepositoryChild>(
+    controller: Controller<K>,
+    client: &Client,
+) -> Controller<K> {
+    let store = controller.store();
+    let repositories = watcher(
+        Api::<Repository>::all(client.clone()),
+        Config::default().any_semantic(),
+    )
+    .default_backoff()
+    .touched_objects()
+    // the children wait until the repository is ready
+    .predicate_filter(spec_or_finalizers.combine(readiness));
+    controller.watches_stream(repositories, move |repository| {
+        store
+            .state()
+            .into_iter()
+            .filter(|child| child.is_child_of(&repository))
+            .map(|child| ObjectRef::from_obj(&*child))
+            .collect::<Vec<_>>()
+    })
+}
+
+/// Reconcile a repository when one of its children changes, e.g. once it is deleted.
+pub fn watch_children(
+    controller: Controller<Repository>,
+    client: &Client,
+) -> Controller<Repository> {
+    controller
+        .owns_stream(children::<AutolinkReference>(client))
+        .owns_stream(children::<RepositoryPermission>(client))
+}
+
+/// The children whose spec or finalizers changed, the last change of a deleted child is the removal
+/// of its finalizer.
+fn children<K: RepositoryChild>(
+    client: &Client,
+) -> impl Stream<Item = Result<K, watcher::Error>> + Send + 'static {
+    watcher(
+        Api::<K>::all(client.clone()),
+        Config::default().any_semantic(),
+    )
+    .default_backoff()
+    .touched_objects()
+    .predicate_filter(spec_or_finalizers)
+}
+
+fn is_owned_by<K: Resource>(child: &K, repository: &Repository) -> bool {
+    let uid = repository.uid();
+    uid.is_some()
+        && child
+            .owner_references()
+            .iter()
+            .any(|reference| is_repository(reference) && Some(&reference.uid) == uid.as_ref())
+}
+
+/// References of every version, they were set as `v1alpha1` before.
+fn is_repository(reference: &OwnerReference) -> bool {
+    reference.kind == Repository::kind(&())
+        && reference
+            .api_version
+            .split_once('/')
+            .is_some_and(|(group, _)| group == Repository::group(&()))
+}
+
+fn readiness(repository: &Repository) -> Option<u64> {
+    Some(u64::from(is_ready(repository)))
+}
+
+fn is_ready(repository: &Repository) -> bool {
+    repository.status.as_ref().is_some_and(|status| {
+        status
+            .conditions
+            .iter()
+            .any(|condition| condition.type_ == conditions::READY && condition.status == "True")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, ObjectMeta, Time};
+    use k8s_openapi::chrono::Utc;
+
+    use crate::domain::model::permission::RepositoryPermissionSpec;
+    use crate::domain::model::repository::{RepositorySpec, RepositoryStatus};
+    use crate::domain::model::ReconcileStatus;
+
+    use super::*;
+
+    fn repository(name: &str, full_name: &str) -> Repository {
+        let mut repository = Repository::new(
+            name,
+            RepositorySpec {
+                full_name: full_name.into(),
+                ..RepositorySpec::default()
+            },
+        );
+        repository.metadata.namespace = Some("pdh-da".into());
+        repository.metadata.uid = Some(format!("uid-{}", name));
+        repository
+    }
+
+    fn permission(full_name: &str, repository_ref: Option<&str>) -> RepositoryPermission {
+        let mut permission = RepositoryPermission::new(
+            "test",
+            RepositoryPermissionSpec {
+                full_name: full_name.into(),
+                full_team_name: "otto-ec/team".into(),
+                permission: "push".into(),
+                repository_ref: repository_ref.map(|name| RepositoryRef { name: name.into() }),
+            },
+        );
+        permission.metadata.namespace = Some("pdh-da".into());
+        permission
+    }
+
+    #[test]
+    fn children_are_matched_by_full_name() {
+        let repository = repository("a", "otto-ec/pdh-da_a");
+
+        assert!(permission("otto-ec/PDH-DA_A", None).is_child_of(&repository));
+        assert!(!permission("otto-ec/pdh-da_b", None).is_child_of(&repository));
+    }
+
+    #[test]
+    fn children_follow_renamed_repositories() {
+        let mut repository = repository("a", "otto-ec/pdh-da_new");
+        repository.spec.previous_full_names = Some(vec!["otto-ec/pdh-da_old".into()]);
+
+        assert!(permission("otto-ec/pdh-da_old", None).is_child_of(&repository));
+    }
+
+    #[test]
+    fn repository_ref_takes_precedence_over_full_name() {
+        let repository = repository("a", "otto-ec/pdh-da_a");
+
+        assert!(permission("otto-ec/pdh-da_b", Some("a")).is_child_of(&repository));
+        assert!(!permission("otto-ec/pdh-da_a", Some("b")).is_child_of(&repository));
+    }
+
+    #[test]
+    fn children_are_matched_by_owner_reference() {
+        let repository = repository("a", "otto-ec/pdh-da_a");
+        let mut child = permission("otto-ec/pdh-da_renamed", None);
+        child.metadata.owner_references = repository.controller_owner_ref(&()).map(|o| vec![o]);
+
+        assert!(child.is_child_of(&repository));
+    }
+
+    #[test]
+    fn owner_references_of_previous_versions_are_matched() {
+        let repository = repository("a", "otto-ec/pdh-da_a");
+        let mut child = permission("otto-ec/pdh-da_renamed", None);
+        let mut owner = repository.controller_owner_ref(&()).unwrap();
+        owner.api_version = "github.platform.benkeil.de/v1alpha1".into();
+        child.metadata.owner_references = Some(vec![owner]);
+
+        assert!(child.is_child_of(&repository));
+    }
+
+    #[test]
+    fn children_of_other_namespaces_are_ignored() {
+        let repository = repository("a", "otto-ec/pdh-da_a");
+        let mut child = permission("otto-ec/pdh-da_a", None);
+        child.metadata = ObjectMeta {
+            namespace: Some("other".into()),
+            ..child.metadata
+        };
+
+        assert!(!child.is_child_of(&repository));
+    }
+
+    #[test]
+    fn children_only_watch_the_spec_and_the_readiness_of_repositories() {
+        let predicate = spec_or_finalizers.combine(readiness);
+        let mut repository = repository("a", "otto-ec/pdh-da_a");
+        repository.metadata.generation = Some(1);
+        let hash = predicate.hash_property(&repository);
+
+        repository.status = Some(RepositoryStatus {
+            reconcile: ReconcileStatus {
+                drift_corrections: Some(1),
+                ..ReconcileStatus::default()
+            },
+            ..RepositoryStatus::default()
+        });
+        assert_eq!(predicate.hash_property(&repository), hash);
+
+        repository.status.as_mut().unwrap().conditions = vec![Condition {
+            type_: conditions::READY.into(),
+            status: "True".into(),
+            reason: "Reconciled".into(),
+            message: String::new(),
+            last_transition_time: Time(Utc::now()),
+            observed_generation: Some(1),
+        }];
+        let ready = predicate.hash_property(&repository);
+        assert_ne!(ready, hash);
+
+        repository.metadata.generation = Some(2);
+        assert_ne!(predicate.hash_property(&repository), ready);
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use kube::ResourceExt;
+
+use crate::config::{env_duration_or, parse_duration};
+use crate::extensions::DurationExtension;
+use crate::{ControllerError, ErrorClass};
+
+/// Overrides the resync interval of a single object, e.g. `10m`.
+pub const RESYNC_INTERVAL_ANNOTATION: &str = "github.platform.benkeil.de/resync-interval";
+
+#[derive(Clone, Debug)]
+pub struct RequeueConfig {
+    /// How often a ready object is reconciled again to correct drift on GitHub
+    pub resync_interval: Duration,
+    /// The delay after the first failure, doubled with every further failure
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// The delay after errors that won't go away without a change
+    pub permanent_error_interval: Duration,
+}
+
+impl RequeueConfig {
+    /// Read the config of a controller, `APP_RESYNC_INTERVAL_<NAME>` overrides
+    /// `APP_RESYNC_INTERVAL` for a single kind.
+    pub fn from_env(controller_name: &str) -> Result<Self, ControllerError> {
+        let resync_interval = env_duration_or("APP_RESYNC_INTERVAL", Duration::from_minutes(1))?;
+        let config = Self {
+            resync_interval: env_duration_or(
+                &format!(
+                    "APP_RESYNC_INTERVAL_{}",
+                    controller_name.to_uppercase().replace('-', "_")
+                ),
+                resync_interval,
+            )?,
+            initial_backoff: env_duration_or("APP_BACKOFF_INITIAL", Duration::from_secs(5))?,
+            max_backoff: env_duration_or("APP_BACKOFF_MAX", Duration::from_minutes(5))?,
+            permanent_error_interval: env_duration_or(
+                "APP_PERMANENT_ERROR_INTERVAL",
+                Duration::from_minutes(30),
+            )?,
+        };
+        if config.initial_backoff.is_zero() || config.initial_backoff > config.max_backoff {
+            return Err(ControllerError::ConfigurationError(
+                "APP_BACKOFF_INITIAL must be greater than zero and not exceed APP_BACKOFF_MAX"
+                    .into(),
+            ));
+        }
+        Ok(config)
+    }
+}
+
+impl Default for RequeueConfig {
+    fn default() -> Self {
+        Self {
+            resync_interval: Duration::from_minutes(1),
+            initial_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_minutes(5),
+            permanent_error_interval: Duration::from_minutes(30),
+        }
+    }
+}
+
+/// Decides when an object is reconciled again, tracks the consecutive failures per object.
+pub struct Requeue {
+    config: RequeueConfig,
+    failures: Mutex<HashMap<String, u32>>,
+}
+
+impl Requeue {
+    pub fn new(config: RequeueConfig) -> Self {
+        Self {
+            config,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The object was reconciled, the backoff starts over.
+    pub fn succeeded<K: ResourceExt>(&self, key: &str, object: &K) -> Duration {
+        self.reset(key);
+        let interval = object
+            .annotations()
+            .get(RESYNC_INTERVAL_ANNOTATION)
+            .and_then(|value| {
+                let interval = parse_duration(value).filter(|interval| !interval.is_zero());
+                if interval.is_none() {
+                    log::warn!(
+                        "ignoring invalid {} of {}: {}",
+                        RESYNC_INTERVAL_ANNOTATION,
+                        key,
+                        value
+                    );
+                }
+                interval
+            })
+            .unwrap_or(self.config.resync_interval);
+        // spread the resyncs of objects that were created together
+        with_jitter(interval)
+    }
+
+    pub fn failed(&self, key: &str, error: &ControllerError) -> Duration {
+        let failures = {
+            let mut failures = self
+                .failures
+                .lock()
+                .expect("failures should not be poisoned");
+            let count = failures.entry(key.to_string()).or_default();
+            *count = count.saturating_add(1);
+            *count
+        };
+        match error.class() {
+            ErrorClass::Permanent | ErrorClass::PermissionDenied => {
+                with_jitter(self.config.permanent_error_interval)
+            }
+            ErrorClass::Retryable | ErrorClass::Auth => {
+                let backoff = self.backoff(failures);
+                match error {
+                    // GitHub tells when the rate limit resets
+                    ControllerError::GitHubError(error) => error
+                        .retry_after
+                        .map_or(backoff, |retry_after| backoff.max(with_jitter(retry_after))),
+                    _ => backoff,
+                }
+            }
+        }
+    }
+
+    /// Forget the object, e.g. after it was deleted.
+    pub fn reset(&self, key: &str) {
+        self.failures
+            .lock()
+            .expect("failures should not be poisoned")
+            .remove(key);
+    }
+
+    fn backoff(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(16);
+        let backoff = self
+            .config
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.config.max_backoff);
+        with_jitter(backoff)
+    }
+}
+
+/// Add up to 10% to the duration, so failing objects don't retry in lockstep.
+fn with_jitter(duration: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    duration + duration.mul_f64((random % 1000) as f64 / 10_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+
+    use github_operator::GitHubError;
+
+    use super::*;
+
+    fn object(annotations: &[(&str, &str)]) -> ConfigMap {
+        ConfigMap {
+            metadata: ObjectMeta {
+                name: Some("test".into()),
+                annotations: Some(
+                    annotations
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect::<BTreeMap<_, _>>(),
+                ),
+                ..ObjectMeta::default()
+            },
+            ..ConfigMap::default()
+        }
+    }
+
+    fn github_error(status: u16, retry_after: Option<Duration>) -> ControllerError {
+        ControllerError::GitHubError(GitHubError {
+            status,
+            message: "error".into(),
+            documentation_url: None,
+            errors: vec![],
+            rate_limit_remaining: None,
+            retry_after,
+        })
+    }
+
+    fn assert_between(duration: Duration, min: Duration) {
+        assert!(
+            duration >= min && duration <= min.mul_f64(1.1),
+            "{:?} is not between {:?} and 110%",
+            duration,
+            min
+        );
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_max() {
+        let requeue = Requeue::new(RequeueConfig::default());
+        let error = github_error(502, None);
+
+        let delays: Vec<_> = (0..8).map(|_| requeue.failed("ns/a", &error)).collect();
+
+        for (delay, expected) in delays.iter().zip([5, 10, 20, 40, 80, 160, 300, 300]) {
+            assert_between(*delay, Duration::from_secs(expected));
+        }
+    }
+
+    #[test]
+    fn backoff_is_tracked_per_object_and_reset_on_success() {
+        let requeue = Requeue::new(RequeueConfig::default());
+        let error = github_error(502, None);
+        requeue.failed("ns/a", &error);
+        requeue.failed("ns/a", &error);
+
+        assert_between(requeue.failed("ns/b", &error), Duration::from_secs(5));
+        requeue.succeeded("ns/a", &object(&[]));
+        assert_between(requeue.failed("ns/a", &error), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn permanent_errors_are_retried_rarely() {
+        let requeue = Requeue::new(RequeueConfig::default());
+
+        assert_between(
+            requeue.failed("ns/a", &github_error(404, None)),
+            Duration::from_minutes(30),
+        );
+        assert_between(
+            requeue.failed("ns/a", &ControllerError::PolicyViolation("denied".into())),
+            Duration::from_minutes(30),
+        );
+    }
+
+    #[test]
+    fn rate_limited_requests_wait_for_the_reset() {
+        let requeue = Requeue::new(RequeueConfig::default());
+
+        assert_between(
+            requeue.failed("ns/a", &github_error(429, Some(Duration::from_secs(120)))),
+            Duration::from_secs(120),
+        );
+    }
+
+    #[test]
+    fn resync_interval_can_be_overridden_by_annotation() {
+        let requeue = Requeue::new(RequeueConfig::default());
+
+        assert_between(
+            requeue.succeeded("ns/a", &object(&[])),
+            Duration::from_minutes(1),
+        );
+        assert_between(
+            requeue.succeeded("ns/a", &object(&[(RESYNC_INTERVAL_ANNOTATION, "10m")])),
+            Duration::from_minutes(10),
+        );
+        assert_between(
+            requeue.succeeded("ns/a", &object(&[(RESYNC_INTERVAL_ANNOTATION, "soon")])),
+            Duration::from_minutes(1),
+        );
+    }
+}
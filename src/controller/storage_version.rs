@@ -0,0 +1,70 @@
+use std::fmt::Debug;
+
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use k8s_openapi::NamespaceResourceScope;
+use kube::api::{ListParams, Patch, PatchParams};
+use kube::{Api, Client, Resource, ResourceExt};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::ControllerError;
+
+/// Rewrite the objects of a kind in the storage version and remove the older versions from the
+/// `storedVersions` of the CRD, so the older versions can be dropped from the CRD later.
+pub async fn migrate<K>(client: &Client) -> Result<(), ControllerError>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + Clone
+        + Debug
+        + DeserializeOwned,
+{
+    let crds = Api::<CustomResourceDefinition>::all(client.clone());
+    let name = format!("{}.{}", K::plural(&()), K::group(&()));
+    let version = K::version(&()).to_string();
+    let stored_versions = crds
+        .get(&name)
+        .await
+        .map_err(ControllerError::KubeError)?
+        .status
+        .and_then(|status| status.stored_versions)
+        .unwrap_or_default();
+    if stored_versions.iter().all(|stored| *stored == version) {
+        return Ok(());
+    }
+
+    log::info!(
+        "migrating {} from {} to {}",
+        name,
+        stored_versions.join(", "),
+        version
+    );
+    let objects = Api::<K>::all(client.clone())
+        .list(&ListParams::default())
+        .await
+        .map_err(ControllerError::KubeError)?;
+    for object in &objects {
+        let api = Api::<K>::namespaced(client.clone(), &object.namespace().unwrap_or_default());
+        // the API server writes every update in the storage version, even an empty one
+        match api
+            .patch(
+                &object.name_any(),
+                &PatchParams::default(),
+                &Patch::Merge(json!({})),
+            )
+            .await
+        {
+            Ok(_) => {}
+            Err(kube::Error::Api(e)) if e.code == 404 => {}
+            Err(e) => return Err(ControllerError::KubeError(e)),
+        }
+    }
+    crds.patch_status(
+        &name,
+        &PatchParams::default(),
+        &Patch::Merge(json!({ "status": { "storedVersions": [version] } })),
+    )
+    .await
+    .map_err(ControllerError::KubeError)?;
+    log::info!("migrated {} objects of {}", objects.items.len(), name);
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use k8s_openapi::api::core::v1::Namespace;
+use kube::api::ListParams;
+use kube::{Api, Client, ResourceExt};
+
+use crate::domain::model::tenant_policy::{
+    GitHubTenantPolicy, GitHubTenantPolicySpec, TenantPolicy,
+};
+use crate::ControllerError;
+
+/// Comma separated owners the repositories of a namespace may belong to, an alternative to a
+/// `GitHubTenantPolicy`.
+pub const ALLOWED_OWNERS_ANNOTATION: &str = "github.platform.benkeil.de/allowed-owners";
+/// Comma separated prefixes of the repository names, only used with the allowed owners.
+pub const ALLOWED_PREFIXES_ANNOTATION: &str = "github.platform.benkeil.de/allowed-prefixes";
+
+/// Collects the policies of a namespace from the `GitHubTenantPolicy` objects and the namespace
+/// annotations.
+#[derive(Clone)]
+pub struct TenantPolicyResolver {
+    client: Client,
+}
+
+impl TenantPolicyResolver {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    pub async fn resolve(&self, namespace: &str) -> Result<TenantPolicy, ControllerError> {
+        let policies = Api::<GitHubTenantPolicy>::all(self.client.clone())
+            .list(&ListParams::default())
+            .await
+            .map_err(ControllerError::KubeError)?
+            .items;
+        let namespace = Api::<Namespace>::all(self.client.clone())
+            .get(namespace)
+            .await
+            .map_err(ControllerError::KubeError)?;
+        Ok(tenant_policy(&namespace, policies))
+    }
+}
+
+/// The policies that apply to the namespace, any policy in the cluster enforces them.
+fn tenant_policy(namespace: &Namespace, policies: Vec<GitHubTenantPolicy>) -> TenantPolicy {
+    let enforced = !policies.is_empty();
+    let name = namespace.name_any();
+    let mut policies = policies
+        .into_iter()
+        .filter(|policy| {
+            policy
+                .spec
+                .namespaces
+                .iter()
+                .any(|namespace| namespace == &name)
+        })
+        .map(|policy| policy.spec)
+        .collect::<Vec<_>>();
+    if let Some(owners) = namespace.annotations().get(ALLOWED_OWNERS_ANNOTATION) {
+        let prefixes = namespace
+            .annotations()
+            .get(ALLOWED_PREFIXES_ANNOTATION)
+            .map(|prefixes| split(prefixes))
+            .unwrap_or_default();
+        policies.push(GitHubTenantPolicySpec {
+            namespaces: vec![name],
+            owners: split(owners),
+            repository_prefixes: prefixes,
+            admin_teams: None,
+        });
+    }
+    TenantPolicy { policies, enforced }
+}
+
+fn split(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use kube::api::ObjectMeta;
+
+    use super::*;
+
+    fn namespace(annotations: &[(&str, &str)]) -> Namespace {
+        Namespace {
+            metadata: ObjectMeta {
+                name: Some("pdh-da".into()),
+                annotations: Some(
+                    annotations
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect::<BTreeMap<_, _>>(),
+                ),
+                ..ObjectMeta::default()
+            },
+            ..Namespace::default()
+        }
+    }
+
+    fn github_tenant_policy(namespace: &str, owner: &str) -> GitHubTenantPolicy {
+        GitHubTenantPolicy::new(
+            namespace,
+            GitHubTenantPolicySpec {
+                namespaces: vec![namespace.into()],
+                owners: vec![owner.into()],
+                ..GitHubTenantPolicySpec::default()
+            },
+        )
+    }
+
+    #[test]
+    fn uses_the_policies_of_the_namespace() {
+        let policy = tenant_policy(
+            &namespace(&[]),
+            vec![
+                github_tenant_policy("pdh-da", "otto-ec"),
+                github_tenant_policy("other", "other"),
+            ],
+        );
+
+        assert!(policy.enforced);
+        assert_eq!(policy.policies.len(), 1);
+        assert!(policy.check_repository("otto-ec/pdh-da_test").is_ok());
+        assert!(policy.check_repository("other/pdh-da_test").is_err());
+    }
+
+    #[test]
+    fn falls_back_to_the_annotations_of_the_namespace() {
+        let cases = [
+            (&[][..], "otto-ec/anything", true),
+            (
+                &[(ALLOWED_OWNERS_ANNOTATION, "otto-ec, other")][..],
+                "other/anything",
+                true,
+            ),
+            (
+                &[
+                    (ALLOWED_OWNERS_ANNOTATION, "otto-ec"),
+                    (ALLOWED_PREFIXES_ANNOTATION, "pdh-da_,pdh-db_"),
+                ][..],
+                "otto-ec/pdh-db_test",
+                true,
+            ),
+            (
+                &[
+                    (ALLOWED_OWNERS_ANNOTATION, "otto-ec"),
+                    (ALLOWED_PREFIXES_ANNOTATION, "pdh-da_"),
+                ][..],
+                "otto-ec/other",
+                false,
+            ),
+            (
+                &[(ALLOWED_OWNERS_ANNOTATION, "otto-ec")][..],
+                "other/anything",
+                false,
+            ),
+            // the prefixes are only used with the owners
+            (
+                &[(ALLOWED_PREFIXES_ANNOTATION, "pdh-da_")][..],
+                "other/anything",
+                true,
+            ),
+        ];
+        for (annotations, full_name, allowed) in cases {
+            let policy = tenant_policy(&namespace(annotations), Vec::new());
+            assert_eq!(
+                policy.check_repository(full_name).is_ok(),
+                allowed,
+                "{} with {:?}",
+                full_name,
+                annotations
+            );
+        }
+    }
+
+    #[test]
+    fn a_namespace_without_a_policy_is_denied_if_other_namespaces_have_one() {
+        let policy = tenant_policy(
+            &namespace(&[]),
+            vec![github_tenant_policy("other", "otto-ec")],
+        );
+
+        assert!(policy.check_repository("otto-ec/pdh-da_test").is_err());
+
+        let policy = tenant_policy(
+            &namespace(&[(ALLOWED_OWNERS_ANNOTATION, "otto-ec")]),
+            vec![github_tenant_policy("other", "otto-ec")],
+        );
+        assert!(policy.check_repository("otto-ec/pdh-da_test").is_ok());
+    }
+}
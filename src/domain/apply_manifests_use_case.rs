@@ -0,0 +1,466 @@
+use kube::Resource;
+use serde::Serialize;
+
+use crate::domain::archive_repository_use_case::ArchiveRepositoryUseCase;
+use crate::domain::delete_autolink_reference_use_case::DeleteAutolinkReferenceUseCase;
+use crate::domain::delete_permissions_use_case::DeletePermissionUseCase;
+use crate::domain::model::apply_state::{AppliedResource, ApplyState};
+use crate::domain::model::manifest::Manifest;
+use crate::domain::model::repository::Repository;
+use crate::domain::model::Change;
+use crate::domain::reconcile_autolink_reference_use_case::ReconcileAutolinkReferenceUseCase;
+use crate::domain::reconcile_permissions_use_case::ReconcilePermissionUseCase;
+use crate::domain::reconcile_repository_use_case::ReconcileRepositoryUseCase;
+use crate::domain::service::event_publisher::EventSubject;
+use crate::ControllerError;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "outcome", content = "detail", rename_all = "camelCase")]
+pub enum ApplyOutcome {
+    Applied(Change),
+    Failed(String),
+    /// The manifest was removed and the resource deleted on GitHub
+    Deleted,
+    /// The manifest was removed, but the resource is kept on GitHub until it is pruned
+    Orphaned,
+    /// The manifest was removed, but another manifest manages the resource on GitHub now
+    Replaced,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyResult {
+    /// The `kind/namespace/name` of the manifest
+    pub key: String,
+    pub full_name: String,
+    #[serde(flatten)]
+    pub outcome: ApplyOutcome,
+}
+
+/// Reconcile manifests without Kubernetes, the state of the previous run replaces the status.
+pub struct ApplyManifestsUseCase {
+    reconcile_repository_use_case: ReconcileRepositoryUseCase,
+    reconcile_autolink_reference_use_case: ReconcileAutolinkReferenceUseCase,
+    reconcile_permission_use_case: ReconcilePermissionUseCase,
+    archive_repository_use_case: ArchiveRepositoryUseCase,
+    delete_autolink_reference_use_case: DeleteAutolinkReferenceUseCase,
+    delete_permission_use_case: DeletePermissionUseCase,
+}
+
+impl ApplyManifestsUseCase {
+    pub fn new(
+        reconcile_repository_use_case: ReconcileRepositoryUseCase,
+        reconcile_autolink_reference_use_case: ReconcileAutolinkReferenceUseCase,
+        reconcile_permission_use_case: ReconcilePermissionUseCase,
+        archive_repository_use_case: ArchiveRepositoryUseCase,
+        delete_autolink_reference_use_case: DeleteAutolinkReferenceUseCase,
+        delete_permission_use_case: DeletePermissionUseCase,
+    ) -> Self {
+        Self {
+            reconcile_repository_use_case,
+            reconcile_autolink_reference_use_case,
+            reconcile_permission_use_case,
+            archive_repository_use_case,
+            delete_autolink_reference_use_case,
+            delete_permission_use_case,
+        }
+    }
+
+    /// Apply the manifests and handle the resources whose manifests were removed since the
+    /// previous run, they are only deleted on GitHub if `prune` is set.
+    pub async fn execute(
+        &self,
+        manifests: &[Manifest],
+        previous: &ApplyState,
+        prune: bool,
+    ) -> (ApplyState, Vec<ApplyResult>) {
+        let mut state = ApplyState::default();
+        let mut results = Vec::new();
+
+        // repositories first, the other resources are created in them
+        let mut ordered = manifests.iter().collect::<Vec<_>>();
+        ordered.sort_by_key(|manifest| !matches!(manifest, Manifest::Repository(_)));
+        for manifest in ordered {
+            let key = manifest.key();
+            let outcome = match self.apply(manifest, previous.resources.get(&key)).await {
+                Ok(applied) => {
+                    let outcome = ApplyOutcome::Applied(applied.last_change());
+                    state.resources.insert(key.clone(), applied);
+                    outcome
+                }
+                Err(e) => {
+                    // keep the previous state, otherwise the resource would count as removed
+                    if let Some(applied) = previous.resources.get(&key) {
+                        state.resources.insert(key.clone(), applied.clone());
+                    }
+                    ApplyOutcome::Failed(e.to_string())
+                }
+            };
+            results.push(ApplyResult {
+                key,
+                full_name: manifest.full_name().to_string(),
+                outcome,
+            });
+        }
+
+        let mut removed = previous
+            .resources
+            .iter()
+            .filter(|(key, _)| !manifests.iter().any(|manifest| &manifest.key() == *key))
+            .collect::<Vec<_>>();
+        // in reverse order, a repository is archived after the resources in it are deleted
+        removed.sort_by_key(|(_, applied)| !matches!(applied, AppliedResource::Repository { .. }));
+        for (key, applied) in removed.into_iter().rev() {
+            let outcome = if state
+                .resources
+                .values()
+                .any(|current| current.same_github_resource(applied))
+            {
+                // e.g. a renamed manifest adopted the resource, deleting it would remove it twice
+                ApplyOutcome::Replaced
+            } else if !prune {
+                state.resources.insert(key.clone(), applied.clone());
+                ApplyOutcome::Orphaned
+            } else {
+                match self.delete(key, applied).await {
+                    Ok(()) => ApplyOutcome::Deleted,
+                    Err(e) => {
+                        state.resources.insert(key.clone(), applied.clone());
+                        ApplyOutcome::Failed(e.to_string())
+                    }
+                }
+            };
+            results.push(ApplyResult {
+                key: key.clone(),
+                full_name: applied.full_name().to_string(),
+                outcome,
+            });
+        }
+
+        (state, results)
+    }
+
+    async fn apply(
+        &self,
+        manifest: &Manifest,
+        previous: Option<&AppliedResource>,
+    ) -> Result<AppliedResource, ControllerError> {
+        manifest
+            .validate_spec()
+            .map_err(|report| ControllerError::InvalidSpec(report.to_string()))?;
+        let subject = manifest.subject();
+        match manifest {
+            Manifest::Repository(repository) => {
+                let change = self
+                    .reconcile_repository_use_case
+                    .execute(&subject, &repository.spec)
+                    .await?;
+                Ok(AppliedResource::Repository {
+                    full_name: repository.spec.full_name.clone(),
+                    last_change: change,
+                })
+            }
+            Manifest::AutolinkReference(autolink_reference) => {
+                // the id of the previous run takes the place of the status
+                let id = match previous {
+                    Some(AppliedResource::AutolinkReference { id, .. }) => Some(*id),
+                    _ => None,
+                };
+                let (id, change) = self
+                    .reconcile_autolink_reference_use_case
+                    .execute(&subject, &autolink_reference.spec, id)
+                    .await?;
+                Ok(AppliedResource::AutolinkReference {
+                    full_name: autolink_reference.spec.full_name.clone(),
+                    key_prefix: autolink_reference.spec.key_prefix.clone(),
+                    id,
+                    last_change: change,
+                })
+            }
+            Manifest::RepositoryPermission(permission) => {
+                let change = self
+                    .reconcile_permission_use_case
+                    .execute(&subject, &permission.spec)
+                    .await?;
+                Ok(AppliedResource::RepositoryPermission {
+                    full_name: permission.spec.full_name.clone(),
+                    full_team_name: permission.spec.full_team_name.clone(),
+                    last_change: change,
+                })
+            }
+        }
+    }
+
+    async fn delete(&self, key: &str, applied: &AppliedResource) -> Result<(), ControllerError> {
+        let subject = subject(key);
+        match applied {
+            AppliedResource::Repository { full_name, .. } => {
+                self.archive_repository_use_case
+                    .execute(&subject, full_name)
+                    .await
+            }
+            AppliedResource::AutolinkReference { full_name, id, .. } => {
+                self.delete_autolink_reference_use_case
+                    .execute(&subject, full_name, Some(*id))
+                    .await
+            }
+            AppliedResource::RepositoryPermission {
+                full_name,
+                full_team_name,
+                ..
+            } => {
+                self.delete_permission_use_case
+                    .execute(&subject, full_name, full_team_name)
+                    .await
+            }
+        }
+    }
+}
+
+/// The removed manifest of the `kind/namespace/name` key.
+fn subject(key: &str) -> EventSubject {
+    let (kind, name) = key.split_once('/').unwrap_or((key, ""));
+    let (namespace, name) = match name.split_once('/') {
+        Some((namespace, name)) => (Some(namespace), name),
+        None => (None, name),
+    };
+    EventSubject {
+        api_version: Repository::api_version(&()).to_string(),
+        kind: kind.to_string(),
+        namespace: namespace.map(str::to_string),
+        name: name.to_string(),
+        uid: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::capturing_event_publisher::CapturingEventPublisher;
+    use crate::adapter::in_memory_github_service::InMemoryGithubService;
+    use crate::domain::model::autolink_reference::{AutolinkReference, AutolinkReferenceSpec};
+    use crate::domain::model::permission::{RepositoryPermission, RepositoryPermissionSpec};
+    use crate::domain::model::repository::RepositorySpec;
+
+    const FULL_NAME: &str = "otto-ec/pdh-da_test";
+    const FULL_TEAM_NAME: &str = "otto-ec/developers";
+
+    fn use_case(github: &InMemoryGithubService) -> ApplyManifestsUseCase {
+        let events = CapturingEventPublisher::new();
+        ApplyManifestsUseCase::new(
+            ReconcileRepositoryUseCase::new(Box::new(github.clone()), Box::new(events.clone())),
+            ReconcileAutolinkReferenceUseCase::new(
+                Box::new(github.clone()),
+                Box::new(events.clone()),
+            ),
+            ReconcilePermissionUseCase::new(Box::new(github.clone()), Box::new(events.clone())),
+            ArchiveRepositoryUseCase::new(Box::new(github.clone()), Box::new(events.clone())),
+            DeleteAutolinkReferenceUseCase::new(Box::new(github.clone()), Box::new(events.clone())),
+            DeletePermissionUseCase::new(Box::new(github.clone()), Box::new(events)),
+        )
+    }
+
+    fn repository() -> Manifest {
+        Manifest::Repository(Repository::new(
+            "pdh-da-test",
+            RepositorySpec {
+                full_name: FULL_NAME.into(),
+                delete_branch_on_merge: Some(true),
+                ..Default::default()
+            },
+        ))
+    }
+
+    fn autolink_reference() -> Manifest {
+        Manifest::AutolinkReference(AutolinkReference::new(
+            "pdh-da-test-jira",
+            AutolinkReferenceSpec {
+                full_name: FULL_NAME.into(),
+                key_prefix: "JIRA-".into(),
+                url_template: "https://jira.example.com/browse/JIRA-<num>".into(),
+                is_alphanumeric: false,
+                repository_ref: None,
+            },
+        ))
+    }
+
+    fn permission() -> Manifest {
+        Manifest::RepositoryPermission(RepositoryPermission::new(
+            "pdh-da-test-developers",
+            RepositoryPermissionSpec {
+                full_name: FULL_NAME.into(),
+                full_team_name: FULL_TEAM_NAME.into(),
+                permission: "push".into(),
+                repository_ref: None,
+            },
+        ))
+    }
+
+    fn outcomes(results: &[ApplyResult]) -> Vec<(String, ApplyOutcome)> {
+        results
+            .iter()
+            .map(|result| (result.key.clone(), result.outcome.clone()))
+            .collect()
+    }
+
+    fn position(calls: &[String], call: &str) -> usize {
+        calls
+            .iter()
+            .position(|c| c.starts_with(call))
+            .unwrap_or_else(|| panic!("{} should have been called", call))
+    }
+
+    #[tokio::test]
+    async fn creates_the_repository_before_the_resources_in_it() {
+        let github = InMemoryGithubService::new();
+        let manifests = [permission(), autolink_reference(), repository()];
+
+        let (state, results) = use_case(&github)
+            .execute(&manifests, &ApplyState::default(), false)
+            .await;
+
+        assert_eq!(
+            outcomes(&results),
+            vec![
+                (repository().key(), ApplyOutcome::Applied(Change::Created)),
+                (permission().key(), ApplyOutcome::Applied(Change::Created)),
+                (
+                    autolink_reference().key(),
+                    ApplyOutcome::Applied(Change::Created)
+                ),
+            ]
+        );
+        assert_eq!(state.resources.len(), 3);
+        assert_eq!(
+            github.team_permission(FULL_NAME, FULL_TEAM_NAME),
+            Some("push".into())
+        );
+        assert_eq!(github.autolink_references(FULL_NAME).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_second_run_changes_nothing() {
+        let github = InMemoryGithubService::new();
+        let use_case = use_case(&github);
+        let manifests = [repository(), autolink_reference(), permission()];
+        let (state, _) = use_case
+            .execute(&manifests, &ApplyState::default(), false)
+            .await;
+
+        let (second_state, results) = use_case.execute(&manifests, &state, false).await;
+
+        assert!(results
+            .iter()
+            .all(|result| result.outcome == ApplyOutcome::Applied(Change::Unchanged)));
+        assert_eq!(github.autolink_references(FULL_NAME).len(), 1);
+        assert_eq!(
+            second_state.resources.keys().collect::<Vec<_>>(),
+            state.resources.keys().collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_removed_resources_without_prune() {
+        let github = InMemoryGithubService::new();
+        let use_case = use_case(&github);
+        let (state, _) = use_case
+            .execute(&[repository(), permission()], &ApplyState::default(), false)
+            .await;
+
+        let (state, results) = use_case.execute(&[repository()], &state, false).await;
+
+        assert_eq!(
+            outcomes(&results)[1],
+            (permission().key(), ApplyOutcome::Orphaned)
+        );
+        assert!(state.resources.contains_key(&permission().key()));
+        assert_eq!(
+            github.team_permission(FULL_NAME, FULL_TEAM_NAME),
+            Some("push".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn deletes_removed_resources_with_prune() {
+        let github = InMemoryGithubService::new();
+        let use_case = use_case(&github);
+        let (state, _) = use_case
+            .execute(
+                &[repository(), autolink_reference(), permission()],
+                &ApplyState::default(),
+                false,
+            )
+            .await;
+
+        let (state, results) = use_case.execute(&[], &state, true).await;
+
+        assert!(results
+            .iter()
+            .all(|result| result.outcome == ApplyOutcome::Deleted));
+        assert!(state.resources.is_empty());
+        assert!(github.is_archived(FULL_NAME));
+        assert!(github.autolink_references(FULL_NAME).is_empty());
+        assert_eq!(github.team_permission(FULL_NAME, FULL_TEAM_NAME), None);
+        let calls = github.calls();
+        assert!(
+            position(&calls, "delete_team_permission") < position(&calls, "archive_repository")
+        );
+        assert!(
+            position(&calls, "delete_autolink_references") < position(&calls, "archive_repository")
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_resources_of_renamed_manifests_with_prune() {
+        let github = InMemoryGithubService::new();
+        let use_case = use_case(&github);
+        let (state, _) = use_case
+            .execute(
+                &[repository(), autolink_reference(), permission()],
+                &ApplyState::default(),
+                false,
+            )
+            .await;
+        let renamed = |manifest: Manifest| match manifest {
+            Manifest::Repository(repository) => {
+                Manifest::Repository(Repository::new("renamed", repository.spec))
+            }
+            Manifest::AutolinkReference(autolink_reference) => Manifest::AutolinkReference(
+                AutolinkReference::new("renamed-jira", autolink_reference.spec),
+            ),
+            Manifest::RepositoryPermission(permission) => Manifest::RepositoryPermission(
+                RepositoryPermission::new("renamed-developers", permission.spec),
+            ),
+        };
+        let manifests = [
+            renamed(repository()),
+            renamed(autolink_reference()),
+            renamed(permission()),
+        ];
+
+        let (state, results) = use_case.execute(&manifests, &state, true).await;
+
+        assert_eq!(
+            outcomes(&results)[3..],
+            [
+                (permission().key(), ApplyOutcome::Replaced),
+                (autolink_reference().key(), ApplyOutcome::Replaced),
+                (repository().key(), ApplyOutcome::Replaced),
+            ]
+        );
+        assert_eq!(state.resources.len(), 3);
+        assert!(manifests
+            .iter()
+            .all(|manifest| state.resources.contains_key(&manifest.key())));
+        assert_eq!(github.autolink_references(FULL_NAME).len(), 1);
+        assert_eq!(
+            github.team_permission(FULL_NAME, FULL_TEAM_NAME),
+            Some("push".into())
+        );
+        assert!(!github.is_archived(FULL_NAME));
+        assert!(!github
+            .calls()
+            .iter()
+            .any(|call| call.starts_with("delete_")));
+    }
+}
@@ -1,20 +1,86 @@
-use crate::domain::model::repository::RepositorySpec;
+use crate::domain::service::event_publisher::{DomainEvent, EventPublisher, EventSubject};
 use crate::domain::service::github_service::GitHubService;
 use crate::ControllerError;
 
 pub struct ArchiveRepositoryUseCase {
     github_service: Box<dyn GitHubService + Send + Sync>,
+    event_publisher: Box<dyn EventPublisher + Send + Sync>,
 }
 
 impl ArchiveRepositoryUseCase {
-    pub fn new(github_service: Box<dyn GitHubService + Send + Sync>) -> Self {
-        Self { github_service }
+    pub fn new(
+        github_service: Box<dyn GitHubService + Send + Sync>,
+        event_publisher: Box<dyn EventPublisher + Send + Sync>,
+    ) -> Self {
+        Self {
+            github_service,
+            event_publisher,
+        }
     }
 
-    pub async fn execute(&self, spec: &RepositorySpec) -> Result<(), ControllerError> {
-        log::info!("archive repository: {}", &spec.full_name);
-        self.github_service
-            .archive_repository(&spec.full_name)
-            .await
+    pub async fn execute(
+        &self,
+        subject: &EventSubject,
+        full_name: &str,
+    ) -> Result<(), ControllerError> {
+        log::info!("archive repository: {}", full_name);
+        self.github_service.archive_repository(full_name).await?;
+        let event = DomainEvent::RepositoryArchived {
+            full_name: full_name.to_string(),
+        };
+        self.event_publisher.publish(subject, &event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::capturing_event_publisher::CapturingEventPublisher;
+    use crate::adapter::in_memory_github_service::InMemoryGithubService;
+    use crate::domain::model::manifest::Manifest;
+    use crate::domain::model::repository::{Repository, RepositoryResponse, RepositorySpec};
+
+    const FULL_NAME: &str = "otto-ec/pdh-da_test";
+
+    fn subject() -> EventSubject {
+        Manifest::Repository(Repository::new(
+            "test",
+            RepositorySpec {
+                full_name: FULL_NAME.into(),
+                ..Default::default()
+            },
+        ))
+        .subject()
+    }
+
+    #[tokio::test]
+    async fn archives_repository() {
+        let github =
+            InMemoryGithubService::new().with_repository(FULL_NAME, RepositoryResponse::default());
+        let events = CapturingEventPublisher::new();
+        let use_case =
+            ArchiveRepositoryUseCase::new(Box::new(github.clone()), Box::new(events.clone()));
+
+        use_case.execute(&subject(), FULL_NAME).await.unwrap();
+
+        assert!(github.is_archived(FULL_NAME));
+        assert_eq!(
+            events.events(),
+            vec![DomainEvent::RepositoryArchived {
+                full_name: FULL_NAME.into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_for_missing_repository() {
+        let use_case = ArchiveRepositoryUseCase::new(
+            Box::new(InMemoryGithubService::new()),
+            Box::new(CapturingEventPublisher::new()),
+        );
+
+        let result = use_case.execute(&subject(), FULL_NAME).await;
+
+        assert!(matches!(result, Err(ControllerError::NotFound)));
     }
 }
@@ -0,0 +1,174 @@
+use serde::Serialize;
+
+use crate::domain::diff::{diff, FieldDiff};
+use crate::domain::model::repository::RepositoryResponse;
+use crate::domain::service::github_service::GitHubService;
+use crate::ControllerError;
+
+/// The violations of a repository against the policy.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditResult {
+    pub full_name: String,
+    pub violations: Vec<FieldDiff>,
+    /// The repository couldn't be audited, e.g. because the token lacks permissions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditResult {
+    pub fn is_compliant(&self) -> bool {
+        self.violations.is_empty() && self.error.is_none()
+    }
+}
+
+/// Check repositories against required settings, e.g. that secret scanning is enabled.
+pub struct AuditRepositoriesUseCase {
+    github_service: Box<dyn GitHubService + Send + Sync>,
+}
+
+impl AuditRepositoriesUseCase {
+    pub fn new(github_service: Box<dyn GitHubService + Send + Sync>) -> Self {
+        Self { github_service }
+    }
+
+    /// Settings that are not set in the policy are not checked.
+    pub async fn execute(
+        &self,
+        full_names: &[String],
+        policy: &RepositoryResponse,
+    ) -> Vec<AuditResult> {
+        let mut results = Vec::new();
+        for full_name in full_names {
+            log::info!("audit repository: {}", full_name);
+            let result = match self.github_service.get_repository(full_name).await {
+                Ok(Some(repository)) => AuditResult {
+                    full_name: full_name.clone(),
+                    violations: diff(&repository, policy),
+                    error: None,
+                },
+                Ok(None) => AuditResult {
+                    full_name: full_name.clone(),
+                    violations: Vec::new(),
+                    error: Some(ControllerError::NotFound.to_string()),
+                },
+                Err(e) => AuditResult {
+                    full_name: full_name.clone(),
+                    violations: Vec::new(),
+                    error: Some(e.to_string()),
+                },
+            };
+            results.push(result);
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::adapter::in_memory_github_service::InMemoryGithubService;
+    use crate::domain::model::repository::{
+        SecurityAndAnalysisResponse, SecurityAndAnalysisStatusResponse, Status,
+    };
+
+    const COMPLIANT: &str = "otto-ec/compliant";
+    const VIOLATING: &str = "otto-ec/violating";
+
+    fn repository(delete_branch_on_merge: bool, secret_scanning: Status) -> RepositoryResponse {
+        RepositoryResponse {
+            security_and_analysis: Some(SecurityAndAnalysisResponse {
+                advanced_security: None,
+                secret_scanning: Some(SecurityAndAnalysisStatusResponse {
+                    status: secret_scanning,
+                }),
+                secret_scanning_push_protection: None,
+                dependabot_security_updates: None,
+                secret_scanning_validity_checks: None,
+            }),
+            delete_branch_on_merge: Some(delete_branch_on_merge),
+            allow_auto_merge: Some(true),
+            ..Default::default()
+        }
+    }
+
+    /// Requires branches to be deleted on merge and secret scanning, nothing else.
+    fn policy() -> RepositoryResponse {
+        RepositoryResponse {
+            allow_auto_merge: None,
+            ..repository(true, Status::Enabled)
+        }
+    }
+
+    async fn audit(github: &InMemoryGithubService, full_names: &[&str]) -> Vec<AuditResult> {
+        let full_names = full_names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+        AuditRepositoriesUseCase::new(Box::new(github.clone()))
+            .execute(&full_names, &policy())
+            .await
+    }
+
+    #[tokio::test]
+    async fn reports_the_settings_violating_the_policy() {
+        let github = InMemoryGithubService::new()
+            .with_repository(COMPLIANT, repository(true, Status::Enabled))
+            .with_repository(VIOLATING, repository(false, Status::Disabled));
+
+        let results = audit(&github, &[COMPLIANT, VIOLATING]).await;
+
+        assert!(results[0].is_compliant());
+        assert!(!results[1].is_compliant());
+        assert_eq!(
+            results[1].violations,
+            vec![
+                FieldDiff {
+                    path: "delete_branch_on_merge".into(),
+                    actual: json!(false),
+                    desired: json!(true),
+                },
+                FieldDiff {
+                    path: "security_and_analysis.secret_scanning.status".into(),
+                    actual: json!("disabled"),
+                    desired: json!("enabled"),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn settings_missing_in_the_policy_are_not_checked() {
+        let mut repository = repository(true, Status::Enabled);
+        repository.allow_auto_merge = Some(false);
+        repository.allow_merge_commit = Some(true);
+        let github = InMemoryGithubService::new().with_repository(COMPLIANT, repository);
+
+        let results = audit(&github, &[COMPLIANT]).await;
+
+        assert!(results[0].is_compliant());
+    }
+
+    #[tokio::test]
+    async fn repositories_that_cant_be_audited_are_not_compliant() {
+        let github = InMemoryGithubService::new()
+            .with_repository(VIOLATING, repository(true, Status::Enabled));
+        github.fail_next(
+            "get_repository",
+            ControllerError::UseCaseError("forbidden".into()),
+        );
+
+        let results = audit(&github, &[VIOLATING, COMPLIANT]).await;
+
+        assert_eq!(results[0].error, Some("UseCaseError: forbidden".into()));
+        assert!(results[0].violations.is_empty());
+        assert!(!results[0].is_compliant());
+        assert_eq!(
+            results[1].error,
+            Some(ControllerError::NotFound.to_string())
+        );
+        assert!(!results[1].is_compliant());
+    }
+}
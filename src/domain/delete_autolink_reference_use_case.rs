@@ -1,50 +1,126 @@
-use kube::runtime::events::{Event, EventType, Recorder};
-use kube::Resource;
-
-use crate::domain::model::autolink_reference::AutolinkReference;
+use crate::domain::service::event_publisher::{DomainEvent, EventPublisher, EventSubject};
 use crate::domain::service::github_service::GitHubService;
 use crate::ControllerError;
 
 pub struct DeleteAutolinkReferenceUseCase {
     github_service: Box<dyn GitHubService + Send + Sync>,
+    event_publisher: Box<dyn EventPublisher + Send + Sync>,
 }
 
 impl DeleteAutolinkReferenceUseCase {
-    pub fn new(github_service: Box<dyn GitHubService + Send + Sync>) -> Self {
-        Self { github_service }
+    pub fn new(
+        github_service: Box<dyn GitHubService + Send + Sync>,
+        event_publisher: Box<dyn EventPublisher + Send + Sync>,
+    ) -> Self {
+        Self {
+            github_service,
+            event_publisher,
+        }
     }
 
+    /// Delete the autolink reference on GitHub, without an id it was never created.
     pub async fn execute(
         &self,
-        autolink_reference: &AutolinkReference,
-        recorder: Recorder,
+        subject: &EventSubject,
+        full_name: &str,
+        id: Option<u32>,
     ) -> Result<(), ControllerError> {
-        if let Some(status) = &autolink_reference.status {
-            if let Some(id) = &status.id {
-                log::info!(
-                    "delete autolink reference: {}/{}",
-                    &autolink_reference.spec.full_name,
-                    id
-                );
-                self.github_service
-                    .delete_autolink_references(&autolink_reference.spec.full_name, id)
-                    .await?;
-                let reference = autolink_reference.object_ref(&());
-                recorder
-                    .publish(
-                        &Event {
-                            action: "autolink-reference-deleted".into(),
-                            reason: "Reconciling".into(),
-                            note: Some("Autolink reference deleted".into()),
-                            type_: EventType::Normal,
-                            secondary: None,
-                        },
-                        &reference,
-                    )
-                    .await
-                    .map_err(ControllerError::KubeError)?;
-            }
-        }
-        Ok(())
+        let Some(id) = id else {
+            return Ok(());
+        };
+        log::info!("delete autolink reference: {}/{}", full_name, id);
+        self.github_service
+            .delete_autolink_references(full_name, &id)
+            .await?;
+        let event = DomainEvent::AutolinkReferenceDeleted {
+            full_name: full_name.to_string(),
+            id,
+        };
+        self.event_publisher.publish(subject, &event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::capturing_event_publisher::CapturingEventPublisher;
+    use crate::adapter::in_memory_github_service::InMemoryGithubService;
+    use crate::domain::model::autolink_reference::{
+        AutolinkReference, AutolinkReferenceRequest, AutolinkReferenceSpec,
+    };
+    use crate::domain::model::manifest::Manifest;
+    use crate::domain::model::repository::RepositoryResponse;
+
+    const FULL_NAME: &str = "otto-ec/pdh-da_test";
+
+    fn subject() -> EventSubject {
+        Manifest::AutolinkReference(AutolinkReference::new(
+            "test",
+            AutolinkReferenceSpec {
+                full_name: FULL_NAME.into(),
+                key_prefix: "JIRA-".into(),
+                url_template: "https://jira/browse/JIRA-<num>".into(),
+                is_alphanumeric: false,
+                repository_ref: None,
+            },
+        ))
+        .subject()
+    }
+
+    #[tokio::test]
+    async fn deletes_autolink_reference() {
+        let github =
+            InMemoryGithubService::new().with_repository(FULL_NAME, RepositoryResponse::default());
+        let id = github.add_autolink(
+            FULL_NAME,
+            &AutolinkReferenceRequest {
+                key_prefix: "JIRA-".into(),
+                url_template: "https://jira/browse/JIRA-<num>".into(),
+                is_alphanumeric: false,
+            },
+        );
+        let events = CapturingEventPublisher::new();
+        let use_case =
+            DeleteAutolinkReferenceUseCase::new(Box::new(github.clone()), Box::new(events.clone()));
+
+        use_case
+            .execute(&subject(), FULL_NAME, Some(id))
+            .await
+            .unwrap();
+
+        assert!(github.autolink_references(FULL_NAME).is_empty());
+        assert_eq!(
+            events.events(),
+            vec![DomainEvent::AutolinkReferenceDeleted {
+                full_name: FULL_NAME.into(),
+                id,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_missing_autolink_reference() {
+        let use_case = DeleteAutolinkReferenceUseCase::new(
+            Box::new(InMemoryGithubService::new()),
+            Box::new(CapturingEventPublisher::new()),
+        );
+
+        assert!(use_case
+            .execute(&subject(), FULL_NAME, Some(42))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn skips_autolink_reference_without_id() {
+        let github = InMemoryGithubService::new();
+        let events = CapturingEventPublisher::new();
+        let use_case =
+            DeleteAutolinkReferenceUseCase::new(Box::new(github.clone()), Box::new(events.clone()));
+
+        use_case.execute(&subject(), FULL_NAME, None).await.unwrap();
+
+        assert!(github.calls().is_empty());
+        assert!(events.events().is_empty());
     }
 }
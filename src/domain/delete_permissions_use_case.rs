@@ -1,41 +1,103 @@
-use crate::domain::model::permission::RepositoryPermission;
+use crate::domain::service::event_publisher::{DomainEvent, EventPublisher, EventSubject};
 use crate::domain::service::github_service::GitHubService;
 use crate::ControllerError;
 
-use kube::runtime::events::{Event, EventType, Recorder};
-use kube::Resource;
-
 pub struct DeletePermissionUseCase {
     github_service: Box<dyn GitHubService + Send + Sync>,
+    event_publisher: Box<dyn EventPublisher + Send + Sync>,
 }
 
 impl DeletePermissionUseCase {
-    pub fn new(github_service: Box<dyn GitHubService + Send + Sync>) -> Self {
-        Self { github_service }
+    pub fn new(
+        github_service: Box<dyn GitHubService + Send + Sync>,
+        event_publisher: Box<dyn EventPublisher + Send + Sync>,
+    ) -> Self {
+        Self {
+            github_service,
+            event_publisher,
+        }
     }
 
+    /// Remove the permission on GitHub.
     pub async fn execute(
         &self,
-        permission: &RepositoryPermission,
-        recorder: Recorder,
+        subject: &EventSubject,
+        full_name: &str,
+        full_team_name: &str,
     ) -> Result<(), ControllerError> {
-        log::info!("delete: {}", &permission.spec.full_name);
+        log::info!("delete: {} {}", full_name, full_team_name);
         self.github_service
-            .delete_team_permission(&permission.spec.full_name, &permission.spec.full_team_name)
+            .delete_team_permission(full_name, full_team_name)
             .await?;
-        let reference = permission.object_ref(&());
-        recorder
-            .publish(
-                &Event {
-                    action: "permission-deleted".into(),
-                    reason: "Reconciling".into(),
-                    note: Some("Permission deleted".into()),
-                    type_: EventType::Normal,
-                    secondary: None,
-                },
-                &reference,
-            )
-            .await
-            .map_err(ControllerError::KubeError)
+        let event = DomainEvent::PermissionRevoked {
+            full_name: full_name.to_string(),
+            full_team_name: full_team_name.to_string(),
+        };
+        self.event_publisher.publish(subject, &event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::capturing_event_publisher::CapturingEventPublisher;
+    use crate::adapter::in_memory_github_service::InMemoryGithubService;
+    use crate::domain::model::manifest::Manifest;
+    use crate::domain::model::permission::{RepositoryPermission, RepositoryPermissionSpec};
+
+    const FULL_NAME: &str = "otto-ec/pdh-da_test";
+    const TEAM: &str = "otto-ec/pdh-da";
+
+    fn subject() -> EventSubject {
+        Manifest::RepositoryPermission(RepositoryPermission::new(
+            "test",
+            RepositoryPermissionSpec {
+                full_name: FULL_NAME.into(),
+                full_team_name: TEAM.into(),
+                permission: "push".into(),
+                repository_ref: None,
+            },
+        ))
+        .subject()
+    }
+
+    #[tokio::test]
+    async fn deletes_permission() {
+        let github = InMemoryGithubService::new().with_team_permission(FULL_NAME, TEAM, "push");
+        let events = CapturingEventPublisher::new();
+        let use_case =
+            DeletePermissionUseCase::new(Box::new(github.clone()), Box::new(events.clone()));
+
+        use_case.execute(&subject(), FULL_NAME, TEAM).await.unwrap();
+
+        assert_eq!(github.team_permission(FULL_NAME, TEAM), None);
+        assert_eq!(
+            events.events(),
+            vec![DomainEvent::PermissionRevoked {
+                full_name: FULL_NAME.into(),
+                full_team_name: TEAM.into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_when_github_fails() {
+        let github = InMemoryGithubService::new().with_team_permission(FULL_NAME, TEAM, "push");
+        github.fail_next(
+            "delete_team_permission",
+            ControllerError::UseCaseError("injected".into()),
+        );
+        let events = CapturingEventPublisher::new();
+        let use_case =
+            DeletePermissionUseCase::new(Box::new(github.clone()), Box::new(events.clone()));
+
+        let result = use_case.execute(&subject(), FULL_NAME, TEAM).await;
+
+        assert!(matches!(result, Err(ControllerError::UseCaseError(_))));
+        assert_eq!(
+            github.team_permission(FULL_NAME, TEAM).as_deref(),
+            Some("push")
+        );
+        assert!(events.events().is_empty());
     }
 }
@@ -0,0 +1,78 @@
+use std::fmt::{Display, Formatter};
+
+use differ_from_spec::DifferFromSpec;
+use serde::Serialize;
+use serde_json::Value;
+
+/// A field whose actual value on GitHub differs from the desired value of the spec.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FieldDiff {
+    /// Dotted path of the field, e.g. `security_and_analysis.secret_scanning.status`
+    pub path: String,
+    pub actual: Value,
+    pub desired: Value,
+}
+
+impl Display for FieldDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} -> {}", self.path, self.actual, self.desired)
+    }
+}
+
+/// Compare the fields like [`differ_from_spec::DifferFromSpec`], fields that are not set in the
+/// desired value are ignored.
+pub fn diff<T: Serialize>(actual: &T, desired: &T) -> Vec<FieldDiff> {
+    let actual = serde_json::to_value(actual).unwrap_or(Value::Null);
+    let desired = serde_json::to_value(desired).unwrap_or(Value::Null);
+    let mut diffs = Vec::new();
+    diff_values("", &actual, &desired, &mut diffs);
+    diffs
+}
+
+fn diff_values(path: &str, actual: &Value, desired: &Value, diffs: &mut Vec<FieldDiff>) {
+    match (actual, desired) {
+        (_, Value::Null) => {}
+        (Value::Object(actual_fields), Value::Object(desired_fields)) => {
+            for (name, desired_value) in desired_fields {
+                let path = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", path, name)
+                };
+                let actual_value = actual_fields.get(name).unwrap_or(&Value::Null);
+                diff_values(&path, actual_value, desired_value, diffs);
+            }
+        }
+        (actual, desired) if actual != desired => diffs.push(FieldDiff {
+            path: path.to_string(),
+            actual: actual.clone(),
+            desired: desired.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// How a resource on GitHub differs from its manifest.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "state", content = "fields", rename_all = "camelCase")]
+pub enum Drift {
+    InSync,
+    /// The resource does not exist on GitHub
+    Missing,
+    Changed(Vec<FieldDiff>),
+}
+
+impl Drift {
+    pub fn is_in_sync(&self) -> bool {
+        matches!(self, Drift::InSync)
+    }
+}
+
+/// Whether the actual value differs from the spec, with the same comparison as the reconciliation.
+pub fn drift<T: Serialize + DifferFromSpec>(actual: &T, desired: &T) -> Drift {
+    if actual.differ_from_spec(desired) {
+        Drift::Changed(diff(actual, desired))
+    } else {
+        Drift::InSync
+    }
+}
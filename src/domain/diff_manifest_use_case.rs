@@ -0,0 +1,81 @@
+use serde_json::json;
+
+use crate::domain::diff::{drift, Drift, FieldDiff};
+use crate::domain::model::autolink_reference::{AutolinkReference, AutolinkReferenceRequest};
+use crate::domain::model::manifest::Manifest;
+use crate::domain::model::permission::RepositoryPermission;
+use crate::domain::model::repository::{Repository, RepositoryResponse};
+use crate::domain::model::AutoConfigureSpec;
+use crate::domain::service::github_service::GitHubService;
+use crate::ControllerError;
+
+/// Compare a manifest with the live state on GitHub without changing anything.
+pub struct DiffManifestUseCase {
+    github_service: Box<dyn GitHubService + Send + Sync>,
+}
+
+impl DiffManifestUseCase {
+    pub fn new(github_service: Box<dyn GitHubService + Send + Sync>) -> Self {
+        Self { github_service }
+    }
+
+    pub async fn execute(&self, manifest: &Manifest) -> Result<Drift, ControllerError> {
+        log::info!("diff {}: {}", manifest.kind(), manifest.name());
+        match manifest {
+            Manifest::Repository(repository) => self.diff_repository(repository).await,
+            Manifest::AutolinkReference(autolink_reference) => {
+                self.diff_autolink_reference(autolink_reference).await
+            }
+            Manifest::RepositoryPermission(permission) => self.diff_permission(permission).await,
+        }
+    }
+
+    async fn diff_repository(&self, repository: &Repository) -> Result<Drift, ControllerError> {
+        // enable additional settings if necessary
+        let spec_repository: RepositoryResponse = repository.spec.clone().auto_configure().into();
+        match self
+            .github_service
+            .get_repository(&repository.spec.full_name)
+            .await?
+        {
+            Some(actual) => Ok(drift(&actual, &spec_repository)),
+            None => Ok(Drift::Missing),
+        }
+    }
+
+    async fn diff_autolink_reference(
+        &self,
+        autolink_reference: &AutolinkReference,
+    ) -> Result<Drift, ControllerError> {
+        let spec: AutolinkReferenceRequest = autolink_reference.spec.clone().into();
+        let actual = self
+            .github_service
+            .get_autolink_references(&autolink_reference.spec.full_name)
+            .await?
+            .into_iter()
+            .find(|actual| actual.key_prefix == spec.key_prefix);
+        match actual {
+            Some(actual) => Ok(drift(&AutolinkReferenceRequest::from(actual), &spec)),
+            None => Ok(Drift::Missing),
+        }
+    }
+
+    async fn diff_permission(
+        &self,
+        permission: &RepositoryPermission,
+    ) -> Result<Drift, ControllerError> {
+        let actual = self
+            .github_service
+            .get_team_permission(&permission.spec.full_name, &permission.spec.full_team_name)
+            .await?;
+        match actual {
+            Some(actual) if actual == permission.spec.permission => Ok(Drift::InSync),
+            Some(actual) => Ok(Drift::Changed(vec![FieldDiff {
+                path: "permission".to_string(),
+                actual: json!(actual),
+                desired: json!(permission.spec.permission),
+            }])),
+            None => Ok(Drift::Missing),
+        }
+    }
+}
@@ -25,15 +25,22 @@ impl GetRepositoryUseCase {
                     .github_service
                     .get_autolink_references(full_name)
                     .await?;
-                let autolink_references = if autolink_references.is_empty() {
-                    None
-                } else {
-                    Some(autolink_references)
-                };
+                let teams = self.github_service.get_repository_teams(full_name).await?;
+                let collaborators = self
+                    .github_service
+                    .get_repository_collaborators(full_name)
+                    .await?;
+                let rulesets = self
+                    .github_service
+                    .get_repository_rulesets(full_name)
+                    .await?;
                 Ok(Some(RepositoryFullView {
                     full_name: full_name.into(),
                     repository: Some(repository),
-                    autolink_references,
+                    autolink_references: non_empty(autolink_references),
+                    teams: non_empty(teams),
+                    collaborators: non_empty(collaborators),
+                    rulesets: non_empty(rulesets),
                 }))
             }
             Ok(None) => Ok(None),
@@ -41,3 +48,11 @@ impl GetRepositoryUseCase {
         }
     }
 }
+
+fn non_empty<T>(items: Vec<T>) -> Option<Vec<T>> {
+    if items.is_empty() {
+        None
+    } else {
+        Some(items)
+    }
+}
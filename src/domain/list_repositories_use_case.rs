@@ -0,0 +1,66 @@
+use crate::domain::service::github_service::GitHubService;
+use crate::ControllerError;
+
+pub struct ListRepositoriesUseCase {
+    github_service: Box<dyn GitHubService + Send + Sync>,
+}
+
+impl ListRepositoriesUseCase {
+    pub fn new(github_service: Box<dyn GitHubService + Send + Sync>) -> Self {
+        Self { github_service }
+    }
+
+    /// The full names of the repositories of the organization whose name matches the filter,
+    /// e.g. `pdh-da_*`.
+    pub async fn execute(
+        &self,
+        org: &str,
+        filter: Option<&str>,
+    ) -> Result<Vec<String>, ControllerError> {
+        log::info!("list repositories: {}", org);
+        let mut repositories = self
+            .github_service
+            .list_organization_repositories(org)
+            .await?;
+        if let Some(filter) = filter {
+            repositories.retain(|full_name| {
+                let name = full_name
+                    .split_once('/')
+                    .map_or(full_name.as_str(), |(_, name)| name);
+                matches_glob(filter, name)
+            });
+        }
+        repositories.sort();
+        Ok(repositories)
+    }
+}
+
+/// Match a pattern where `*` matches any sequence and `?` matches a single character.
+fn matches_glob(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    // position of the last `*` and the value position it was matched against
+    let mut backtrack: Option<(usize, usize)> = None;
+    while v < value.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, v));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == value[v] => {
+                p += 1;
+                v += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    v = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
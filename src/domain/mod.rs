@@ -1,12 +1,19 @@
+pub mod apply_manifests_use_case;
 pub mod archive_repository_use_case;
+pub mod audit_repositories_use_case;
 pub mod delete_autolink_reference_use_case;
 pub mod delete_permissions_use_case;
+pub mod diff;
+pub mod diff_manifest_use_case;
 pub mod get_repository_use_case;
+pub mod list_repositories_use_case;
 pub mod model;
 pub mod reconcile_autolink_reference_use_case;
 pub mod reconcile_permissions_use_case;
 pub mod reconcile_repository_use_case;
 pub mod service;
+pub mod set_repository_use_case;
+pub mod validation;
 
 use schemars::gen::SchemaGenerator;
 use schemars::schema::Schema;
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::model::Change;
+
+/// What `cli apply` created on GitHub, replaces the status of the custom resources between runs.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyState {
+    /// By `kind/namespace/name` of the manifest
+    #[serde(default)]
+    pub resources: BTreeMap<String, AppliedResource>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all_fields = "camelCase")]
+pub enum AppliedResource {
+    Repository {
+        full_name: String,
+        last_change: Change,
+    },
+    AutolinkReference {
+        full_name: String,
+        key_prefix: String,
+        id: u32,
+        last_change: Change,
+    },
+    RepositoryPermission {
+        full_name: String,
+        full_team_name: String,
+        last_change: Change,
+    },
+}
+
+impl AppliedResource {
+    pub fn full_name(&self) -> &str {
+        match self {
+            AppliedResource::Repository { full_name, .. }
+            | AppliedResource::AutolinkReference { full_name, .. }
+            | AppliedResource::RepositoryPermission { full_name, .. } => full_name,
+        }
+    }
+
+    /// Whether both refer to the same resource on GitHub, e.g. after the manifest was renamed.
+    pub fn same_github_resource(&self, other: &AppliedResource) -> bool {
+        match (self, other) {
+            (
+                AppliedResource::Repository { full_name, .. },
+                AppliedResource::Repository {
+                    full_name: other_full_name,
+                    ..
+                },
+            ) => full_name.eq_ignore_ascii_case(other_full_name),
+            (
+                AppliedResource::AutolinkReference { full_name, id, .. },
+                AppliedResource::AutolinkReference {
+                    full_name: other_full_name,
+                    id: other_id,
+                    ..
+                },
+            ) => full_name.eq_ignore_ascii_case(other_full_name) && id == other_id,
+            (
+                AppliedResource::RepositoryPermission {
+                    full_name,
+                    full_team_name,
+                    ..
+                },
+                AppliedResource::RepositoryPermission {
+                    full_name: other_full_name,
+                    full_team_name: other_full_team_name,
+                    ..
+                },
+            ) => {
+                full_name.eq_ignore_ascii_case(other_full_name)
+                    && full_team_name.eq_ignore_ascii_case(other_full_team_name)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn last_change(&self) -> Change {
+        match self {
+            AppliedResource::Repository { last_change, .. }
+            | AppliedResource::AutolinkReference { last_change, .. }
+            | AppliedResource::RepositoryPermission { last_change, .. } => *last_change,
+        }
+    }
+}
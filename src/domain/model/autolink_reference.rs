@@ -6,30 +6,48 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::domain::conditions_schema;
-use crate::domain::model::immutable_string;
+use crate::domain::model::{
+    full_name_string, key_prefix_string, url_template_string, ReconcileStatus, RepositoryRef,
+};
+use crate::domain::validation;
 
 // see https://github.com/kubernetes/community/blob/master/contributors/devel/sig-architecture/api-conventions.md#events
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, Validate, JsonSchema, Default)]
 #[kube(
     group = "github.platform.benkeil.de",
-    version = "v1alpha1",
+    version = "v1beta1",
     kind = "AutolinkReference",
-    namespaced
+    namespaced,
+    shortname = "autolink",
+    category = "github",
+    printcolumn = r#"{"name":"Full Name","type":"string","jsonPath":".spec.fullName"}"#,
+    printcolumn = r#"{"name":"Prefix","type":"string","jsonPath":".spec.keyPrefix"}"#,
+    printcolumn = r#"{"name":"ID","type":"integer","jsonPath":".status.id"}"#,
+    printcolumn = r#"{"name":"Ready","type":"string","jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Healthy","type":"boolean","jsonPath":".status.healthy"}"#,
+    printcolumn = r#"{"name":"Drift","type":"integer","jsonPath":".status.driftCorrections"}"#,
+    printcolumn = r#"{"name":"Last Reconcile","type":"date","jsonPath":".status.lastReconcileTime"}"#,
+    printcolumn = r#"{"name":"Age","type":"date","jsonPath":".metadata.creationTimestamp"}"#
 )]
 #[kube(status = "AutolinkReferenceStatus")]
-//#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
 pub struct AutolinkReferenceSpec {
-    #[garde(skip)]
-    #[schemars(schema_with = "immutable_string")]
+    #[garde(custom(validation::full_name))]
+    #[schemars(schema_with = "full_name_string")]
     pub full_name: String,
-    #[garde(skip)]
-    #[schemars(schema_with = "immutable_string")]
+    #[garde(length(min = 1, max = 32), custom(validation::key_prefix))]
+    #[schemars(schema_with = "key_prefix_string")]
     pub key_prefix: String,
-    #[garde(skip)]
+    /// The URL of a reference, must contain `<num>`
+    #[garde(custom(validation::url_template))]
+    #[schemars(schema_with = "url_template_string")]
     pub url_template: String,
     #[garde(skip)]
     pub is_alphanumeric: bool,
+    /// The `Repository` that manages `fullName`, by default the one with the same `fullName`
+    #[garde(skip)]
+    pub repository_ref: Option<RepositoryRef>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema, PartialEq)]
@@ -40,6 +58,15 @@ pub struct AutolinkReferenceStatus {
     pub conditions: Vec<Condition>,
     pub healthy: Option<bool>,
     pub id: Option<u32>,
+    #[serde(flatten)]
+    pub reconcile: ReconcileStatus,
+}
+
+impl AutolinkReference {
+    /// The id on GitHub of the last reconciliation.
+    pub fn id(&self) -> Option<u32> {
+        self.status.as_ref().and_then(|status| status.id)
+    }
 }
 
 impl From<AutolinkReferenceSpec> for AutolinkReferenceRequest {
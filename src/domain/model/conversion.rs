@@ -0,0 +1,229 @@
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
+use kube::core::crd::merge_crds;
+use kube::CustomResourceExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::domain::model::{autolink_reference, permission, repository, v1alpha1};
+
+pub const V1ALPHA1: &str = "github.platform.benkeil.de/v1alpha1";
+/// The storage version, the controllers work with it
+pub const V1BETA1: &str = "github.platform.benkeil.de/v1beta1";
+
+/// The CRD of a kind with all served versions, `None` for unknown kinds.
+pub fn crd(kind: &str) -> Option<CustomResourceDefinition> {
+    let (previous, latest) = match kind {
+        "Repository" => (v1alpha1::Repository::crd(), repository::Repository::crd()),
+        "AutolinkReference" => (
+            v1alpha1::AutolinkReference::crd(),
+            autolink_reference::AutolinkReference::crd(),
+        ),
+        "RepositoryPermission" => (
+            v1alpha1::RepositoryPermission::crd(),
+            permission::RepositoryPermission::crd(),
+        ),
+        _ => return None,
+    };
+    let storage = latest.spec.versions[0].name.clone();
+    Some(
+        merge_crds(vec![previous, latest], &storage)
+            .expect("the versions of a kind should have the same names and scope"),
+    )
+}
+
+/// Convert an object to another served version of its kind, the versions only differ in the spec.
+pub fn convert(mut object: Value, desired_api_version: &str) -> Result<Value, String> {
+    let field = |name: &str| {
+        object
+            .get(name)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+    let (api_version, kind) = (field("apiVersion"), field("kind"));
+    if api_version == desired_api_version {
+        return Ok(object);
+    }
+    let spec = object.get_mut("spec").map(Value::take).unwrap_or_default();
+    let spec = match (kind.as_str(), api_version.as_str(), desired_api_version) {
+        ("Repository", V1ALPHA1, V1BETA1) => {
+            convert_spec::<v1alpha1::RepositorySpec, repository::RepositorySpec>(spec)
+        }
+        ("Repository", V1BETA1, V1ALPHA1) => {
+            convert_spec::<repository::RepositorySpec, v1alpha1::RepositorySpec>(spec)
+        }
+        ("AutolinkReference", V1ALPHA1, V1BETA1) => convert_spec::<
+            v1alpha1::AutolinkReferenceSpec,
+            autolink_reference::AutolinkReferenceSpec,
+        >(spec),
+        ("AutolinkReference", V1BETA1, V1ALPHA1) => convert_spec::<
+            autolink_reference::AutolinkReferenceSpec,
+            v1alpha1::AutolinkReferenceSpec,
+        >(spec),
+        ("RepositoryPermission", V1ALPHA1, V1BETA1) => convert_spec::<
+            v1alpha1::RepositoryPermissionSpec,
+            permission::RepositoryPermissionSpec,
+        >(spec),
+        ("RepositoryPermission", V1BETA1, V1ALPHA1) => convert_spec::<
+            permission::RepositoryPermissionSpec,
+            v1alpha1::RepositoryPermissionSpec,
+        >(spec),
+        _ => {
+            return Err(format!(
+                "can't convert {} from {:?} to {:?}",
+                kind, api_version, desired_api_version
+            ))
+        }
+    }
+    .map_err(|e| format!("invalid spec of {} {}: {}", api_version, kind, e))?;
+    object["apiVersion"] = json!(desired_api_version);
+    object["spec"] = spec;
+    Ok(object)
+}
+
+fn convert_spec<Source, Target>(spec: Value) -> Result<Value, serde_json::Error>
+where
+    Source: DeserializeOwned + Into<Target>,
+    Target: Serialize,
+{
+    serde_json::to_value(serde_json::from_value::<Source>(spec)?.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use kube::Resource;
+
+    use super::*;
+
+    #[test]
+    fn versions_match_the_kinds() {
+        assert_eq!(v1alpha1::Repository::api_version(&()), V1ALPHA1);
+        assert_eq!(repository::Repository::api_version(&()), V1BETA1);
+        assert_eq!(
+            autolink_reference::AutolinkReference::api_version(&()),
+            V1BETA1
+        );
+        assert_eq!(permission::RepositoryPermission::api_version(&()), V1BETA1);
+    }
+
+    #[test]
+    fn crds_serve_both_versions_and_store_the_latest() {
+        for kind in ["Repository", "AutolinkReference", "RepositoryPermission"] {
+            let crd = crd(kind).unwrap();
+            let mut versions = crd
+                .spec
+                .versions
+                .iter()
+                .map(|version| (version.name.as_str(), version.served, version.storage))
+                .collect::<Vec<_>>();
+            versions.sort();
+
+            assert_eq!(
+                versions,
+                vec![("v1alpha1", true, false), ("v1beta1", true, true)],
+                "{}",
+                kind
+            );
+        }
+        assert!(crd("GitHubTenantPolicy").is_none());
+    }
+
+    #[test]
+    fn printer_columns_use_the_fields_of_their_version() {
+        let crd = crd("AutolinkReference").unwrap();
+        let columns = |name: &str| {
+            crd.spec
+                .versions
+                .iter()
+                .find(|version| version.name == name)
+                .unwrap()
+                .additional_printer_columns
+                .iter()
+                .flatten()
+                .map(|column| column.json_path.as_str())
+                .take(2)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(crd.spec.names.categories, Some(vec!["github".to_string()]));
+        assert_eq!(
+            columns("v1alpha1"),
+            vec![".spec.full_name", ".spec.key_prefix"]
+        );
+        assert_eq!(
+            columns("v1beta1"),
+            vec![".spec.fullName", ".spec.keyPrefix"]
+        );
+    }
+
+    #[test]
+    fn converts_v1alpha1_to_camel_case() {
+        let object = json!({
+            "apiVersion": V1ALPHA1,
+            "kind": "Repository",
+            "metadata": { "name": "test", "namespace": "pdh-da" },
+            "spec": {
+                "full_name": "otto-ec/pdh-da_test",
+                "security_and_analysis": { "secret_scanning": { "status": "enabled" } },
+                "delete_branch_on_merge": true
+            },
+            "status": { "healthy": true }
+        });
+
+        let converted = convert(object, V1BETA1).unwrap();
+
+        assert_eq!(converted["apiVersion"], V1BETA1);
+        assert_eq!(converted["metadata"]["name"], "test");
+        assert_eq!(converted["status"]["healthy"], true);
+        assert_eq!(converted["spec"]["fullName"], "otto-ec/pdh-da_test");
+        assert_eq!(converted["spec"]["deleteBranchOnMerge"], true);
+        assert_eq!(
+            converted["spec"]["securityAndAnalysis"]["secretScanning"]["status"],
+            "enabled"
+        );
+    }
+
+    #[test]
+    fn conversion_round_trips() {
+        let object = json!({
+            "apiVersion": V1BETA1,
+            "kind": "AutolinkReference",
+            "metadata": { "name": "test" },
+            "spec": {
+                "fullName": "otto-ec/pdh-da_test",
+                "keyPrefix": "JIRA-",
+                "urlTemplate": "https://jira.example.com/<num>",
+                "isAlphanumeric": false,
+                "repositoryRef": { "name": "test" }
+            }
+        });
+
+        let previous = convert(object.clone(), V1ALPHA1).unwrap();
+        assert_eq!(previous["spec"]["key_prefix"], "JIRA-");
+
+        assert_eq!(convert(previous, V1BETA1).unwrap(), object);
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let object = json!({
+            "apiVersion": "github.platform.benkeil.de/v2",
+            "kind": "RepositoryPermission",
+            "spec": {}
+        });
+
+        assert!(convert(object, V1BETA1).is_err());
+    }
+
+    #[test]
+    fn invalid_specs_are_rejected() {
+        let object = json!({
+            "apiVersion": V1ALPHA1,
+            "kind": "RepositoryPermission",
+            "spec": { "full_name": "otto-ec/pdh-da_test" }
+        });
+
+        assert!(convert(object, V1BETA1).is_err());
+    }
+}
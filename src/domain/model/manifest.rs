@@ -0,0 +1,163 @@
+use garde::Validate;
+use kube::{Resource, ResourceExt};
+use serde::Serialize;
+
+use crate::domain::model::autolink_reference::{AutolinkReference, AutolinkReferenceSpec};
+use crate::domain::model::conversion::V1BETA1;
+use crate::domain::model::permission::{RepositoryPermission, RepositoryPermissionSpec};
+use crate::domain::model::repository::{Repository, RepositorySpec};
+use crate::domain::model::RepositoryFullView;
+use crate::domain::service::event_publisher::EventSubject;
+
+/// A custom resource read from or written to a YAML document.
+#[derive(Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Manifest {
+    Repository(Repository),
+    AutolinkReference(AutolinkReference),
+    RepositoryPermission(RepositoryPermission),
+}
+
+impl Manifest {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Manifest::Repository(_) => "Repository",
+            Manifest::AutolinkReference(_) => "AutolinkReference",
+            Manifest::RepositoryPermission(_) => "RepositoryPermission",
+        }
+    }
+
+    /// The `namespace/name` of the resource.
+    pub fn name(&self) -> String {
+        let (namespace, name) = match self {
+            Manifest::Repository(resource) => (resource.namespace(), resource.name_any()),
+            Manifest::AutolinkReference(resource) => (resource.namespace(), resource.name_any()),
+            Manifest::RepositoryPermission(resource) => (resource.namespace(), resource.name_any()),
+        };
+        match namespace {
+            Some(namespace) => format!("{}/{}", namespace, name),
+            None => name,
+        }
+    }
+
+    /// The `kind/namespace/name` of the resource, unique within a set of manifests.
+    pub fn key(&self) -> String {
+        format!("{}/{}", self.kind(), self.name())
+    }
+
+    /// The object the events of applying the manifest are about, in the storage version.
+    pub fn subject(&self) -> EventSubject {
+        let meta = match self {
+            Manifest::Repository(resource) => resource.meta(),
+            Manifest::AutolinkReference(resource) => resource.meta(),
+            Manifest::RepositoryPermission(resource) => resource.meta(),
+        };
+        EventSubject {
+            api_version: V1BETA1.to_string(),
+            kind: self.kind().to_string(),
+            namespace: meta.namespace.clone(),
+            name: meta.name.clone().unwrap_or_default(),
+            uid: meta.uid.clone(),
+        }
+    }
+
+    /// The repository the resource belongs to.
+    pub fn full_name(&self) -> &str {
+        match self {
+            Manifest::Repository(resource) => &resource.spec.full_name,
+            Manifest::AutolinkReference(resource) => &resource.spec.full_name,
+            Manifest::RepositoryPermission(resource) => &resource.spec.full_name,
+        }
+    }
+
+    pub fn validate_spec(&self) -> Result<(), garde::Report> {
+        match self {
+            Manifest::Repository(resource) => resource.spec.validate(),
+            Manifest::AutolinkReference(resource) => resource.spec.validate(),
+            Manifest::RepositoryPermission(resource) => resource.spec.validate(),
+        }
+    }
+
+    /// The manifests that reproduce the current state of a repository on GitHub.
+    pub fn from_view(view: &RepositoryFullView, namespace: &str, name: &str) -> Vec<Manifest> {
+        let mut manifests = Vec::new();
+        if let Some(repository) = &view.repository {
+            let spec = RepositorySpec {
+                full_name: view.full_name.clone(),
+                previous_full_names: None,
+                security_and_analysis: repository.security_and_analysis.clone().map(Into::into),
+                delete_branch_on_merge: repository.delete_branch_on_merge,
+                allow_auto_merge: repository.allow_auto_merge,
+                allow_squash_merge: repository.allow_squash_merge,
+                allow_merge_commit: repository.allow_merge_commit,
+                allow_rebase_merge: repository.allow_rebase_merge,
+                allow_update_branch: repository.allow_update_branch,
+            };
+            manifests.push(Manifest::Repository(namespaced(
+                Repository::new(name, spec),
+                namespace,
+            )));
+        }
+        for autolink_reference in view.autolink_references.iter().flatten() {
+            let object_name = object_name(&format!("{}-{}", name, autolink_reference.key_prefix));
+            let spec = AutolinkReferenceSpec {
+                full_name: view.full_name.clone(),
+                key_prefix: autolink_reference.key_prefix.clone(),
+                url_template: autolink_reference.url_template.clone(),
+                is_alphanumeric: autolink_reference.is_alphanumeric,
+                repository_ref: None,
+            };
+            manifests.push(Manifest::AutolinkReference(namespaced(
+                AutolinkReference::new(&object_name, spec),
+                namespace,
+            )));
+        }
+        for team in view.teams.iter().flatten() {
+            let object_name = object_name(&format!("{}-{}", name, team.team_slug));
+            let spec = RepositoryPermissionSpec {
+                full_name: view.full_name.clone(),
+                full_team_name: format!("{}/{}", team.org, team.team_slug),
+                permission: team.permission.clone(),
+                repository_ref: None,
+            };
+            manifests.push(Manifest::RepositoryPermission(namespaced(
+                RepositoryPermission::new(&object_name, spec),
+                namespace,
+            )));
+        }
+        manifests
+    }
+}
+
+fn namespaced<K: Resource>(mut resource: K, namespace: &str) -> K {
+    resource.meta_mut().namespace = Some(namespace.to_string());
+    resource
+}
+
+/// Render a template like `{owner}-{repo}` for a repository as valid object name.
+pub fn render_name_template(template: &str, full_name: &str) -> String {
+    let (owner, repo) = full_name.split_once('/').unwrap_or(("", full_name));
+    object_name(
+        &template
+            .replace("{owner}", owner)
+            .replace("{repo}", repo)
+            .replace("{full_name}", full_name),
+    )
+}
+
+/// Turn a value into a valid object name (RFC 1123 subdomain).
+pub fn object_name(value: &str) -> String {
+    value
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>()
+        .trim_matches(|c| c == '-' || c == '.')
+        .to_string()
+}
@@ -1,40 +1,186 @@
 use crate::domain::model::autolink_reference::AutolinkReferenceResponse;
-use crate::domain::model::repository::RepositoryResponse;
-use kube_derive::CustomResource;
+use crate::domain::model::repository::{
+    CollaboratorPermission, RepositoryResponse, RulesetResponse, TeamPermission,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
 use schemars::gen::SchemaGenerator;
 use schemars::schema::Schema;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
+use crate::domain::validation::{
+    FULL_NAME_PATTERN, FULL_TEAM_NAME_PATTERN, KEY_PREFIX_MAX_LENGTH, KEY_PREFIX_PATTERN,
+    ROLE_NAME_PATTERN, URL_TEMPLATE_PATTERN,
+};
+use crate::ControllerError;
+
+pub mod apply_state;
 pub mod autolink_reference;
+pub mod conversion;
+pub mod manifest;
 pub mod permission;
 pub mod repository;
+pub mod tenant_policy;
+pub mod v1alpha1;
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq)]
 pub struct RepositoryFullView {
     pub full_name: String,
     pub repository: Option<RepositoryResponse>,
     pub autolink_references: Option<Vec<AutolinkReferenceResponse>>,
+    pub teams: Option<Vec<TeamPermission>>,
+    pub collaborators: Option<Vec<CollaboratorPermission>>,
+    pub rulesets: Option<Vec<RulesetResponse>>,
+}
+
+/// The `Repository` object in the namespace of a child object, e.g. an `AutolinkReference`.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq)]
+pub struct RepositoryRef {
+    pub name: String,
+}
+
+/// Split `owner/repo` or `org/team_slug`.
+pub fn split_full_name(full_name: &str) -> Result<(&str, &str), ControllerError> {
+    full_name
+        .split_once('/')
+        .ok_or_else(|| ControllerError::InvalidSpec(format!("{:?} contains no /", full_name)))
+}
+
+/// The outcome of the reconciliations, part of the status of every kind.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileStatus {
+    /// The time of the last reconciliation that changed the resource on GitHub, or of the first
+    /// one if nothing had to be changed
+    pub last_reconcile_time: Option<Time>,
+    /// How often the resource on GitHub differed from the spec and was updated
+    pub drift_corrections: Option<u64>,
+    /// The generation of the spec the status belongs to
+    pub observed_generation: Option<i64>,
+}
+
+impl ReconcileStatus {
+    /// The status after a successful reconciliation, it only changes if GitHub was changed, so the
+    /// periodic reconciliations don't update the status every time.
+    pub fn reconciled(&self, change: Change, now: Time) -> Self {
+        let drift_corrections =
+            self.drift_corrections.unwrap_or_default() + u64::from(change == Change::Updated);
+        let last_reconcile_time = match &self.last_reconcile_time {
+            Some(time) if change == Change::Unchanged => time.clone(),
+            _ => now,
+        };
+        Self {
+            last_reconcile_time: Some(last_reconcile_time),
+            drift_corrections: Some(drift_corrections),
+            ..self.clone()
+        }
+    }
+}
+
+/// The change a reconciliation made on GitHub.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Change {
+    Created,
+    /// The resource differed from the spec and was updated
+    Updated,
+    Unchanged,
 }
 
 pub trait AutoConfigureSpec {
     fn auto_configure(&mut self) -> &Self;
 }
 
+pub fn full_name_string(_: &mut SchemaGenerator) -> Schema {
+    string_schema(json!({ "pattern": FULL_NAME_PATTERN }), true)
+}
+
+/// A `full_name` that may change, see `previous_full_names`.
+pub fn renamable_full_name_string(_: &mut SchemaGenerator) -> Schema {
+    string_schema(json!({ "pattern": FULL_NAME_PATTERN }), false)
+}
+
+pub fn full_team_name_string(_: &mut SchemaGenerator) -> Schema {
+    string_schema(json!({ "pattern": FULL_TEAM_NAME_PATTERN }), true)
+}
+
+pub fn key_prefix_string(_: &mut SchemaGenerator) -> Schema {
+    string_schema(
+        json!({
+            "pattern": KEY_PREFIX_PATTERN,
+            "minLength": 1,
+            "maxLength": KEY_PREFIX_MAX_LENGTH,
+        }),
+        true,
+    )
+}
+
+pub fn url_template_string(_: &mut SchemaGenerator) -> Schema {
+    string_schema(
+        json!({
+            "pattern": URL_TEMPLATE_PATTERN,
+            "x-kubernetes-validations": [{
+                "rule": "self.contains('<num>')",
+                "message": "must contain <num>"
+            }]
+        }),
+        false,
+    )
+}
+
+pub fn permission_string(_: &mut SchemaGenerator) -> Schema {
+    string_schema(json!({ "pattern": ROLE_NAME_PATTERN }), false)
+}
+
 // https://kubernetes.io/docs/tasks/extend-kubernetes/custom-resources/custom-resource-definitions/#validation-rules
-pub fn immutable_string(_: &mut SchemaGenerator) -> Schema {
-    serde_json::from_value(serde_json::json!({
-        "type": "string",
-        "x-kubernetes-validations": [{
+fn string_schema(mut schema: serde_json::Value, immutable: bool) -> Schema {
+    schema["type"] = json!("string");
+    if immutable {
+        let rules = schema
+            .as_object_mut()
+            .unwrap()
+            .entry("x-kubernetes-validations")
+            .or_insert_with(|| json!([]));
+        rules.as_array_mut().unwrap().push(json!({
             "rule": "self == oldSelf",
             "message": "Value is immutable"
-        }]
-    }))
-    .unwrap()
+        }));
+    }
+    serde_json::from_value(schema).unwrap()
 }
 
-#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
-#[kube(group = "clux.dev", version = "v1", kind = "Foo", namespaced)]
-struct FooSpec {
-    info: String,
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn reconciled_counts_the_drift_corrections() {
+        let now = Time(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+
+        let status = ReconcileStatus::default().reconciled(Change::Created, now.clone());
+        assert_eq!(status.drift_corrections, Some(0));
+        assert_eq!(status.last_reconcile_time, Some(now.clone()));
+
+        let status = status
+            .reconciled(Change::Updated, now.clone())
+            .reconciled(Change::Unchanged, now.clone())
+            .reconciled(Change::Updated, now);
+        assert_eq!(status.drift_corrections, Some(2));
+    }
+
+    #[test]
+    fn an_unchanged_reconciliation_keeps_the_status() {
+        let created = Time(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap());
+        let later = Time(Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap());
+
+        let status = ReconcileStatus::default().reconciled(Change::Unchanged, created.clone());
+        assert_eq!(status.last_reconcile_time, Some(created.clone()));
+        assert_eq!(status.reconciled(Change::Unchanged, later.clone()), status);
+
+        let status = status.reconciled(Change::Updated, later.clone());
+        assert_eq!(status.last_reconcile_time, Some(later));
+    }
 }
@@ -6,29 +6,46 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::domain::conditions_schema;
-use crate::domain::model::immutable_string;
+use crate::domain::model::{
+    full_name_string, full_team_name_string, permission_string, ReconcileStatus, RepositoryRef,
+};
+use crate::domain::validation;
 
 // see https://github.com/kubernetes/community/blob/master/contributors/devel/sig-architecture/api-conventions.md#events
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, Validate, JsonSchema, Default)]
 #[kube(
     group = "github.platform.benkeil.de",
-    version = "v1alpha1",
+    version = "v1beta1",
     kind = "RepositoryPermission",
     namespaced,
-    shortname = "permission"
+    shortname = "permission",
+    category = "github",
+    printcolumn = r#"{"name":"Full Name","type":"string","jsonPath":".spec.fullName"}"#,
+    printcolumn = r#"{"name":"Team","type":"string","jsonPath":".spec.fullTeamName"}"#,
+    printcolumn = r#"{"name":"Permission","type":"string","jsonPath":".spec.permission"}"#,
+    printcolumn = r#"{"name":"Ready","type":"string","jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Healthy","type":"boolean","jsonPath":".status.healthy"}"#,
+    printcolumn = r#"{"name":"Drift","type":"integer","jsonPath":".status.driftCorrections"}"#,
+    printcolumn = r#"{"name":"Last Reconcile","type":"date","jsonPath":".status.lastReconcileTime"}"#,
+    printcolumn = r#"{"name":"Age","type":"date","jsonPath":".metadata.creationTimestamp"}"#
 )]
 #[kube(status = "RepositoryPermissionStatus")]
 #[serde(rename_all = "camelCase")]
 pub struct RepositoryPermissionSpec {
-    #[garde(skip)]
-    #[schemars(schema_with = "immutable_string")]
+    #[garde(custom(validation::full_name))]
+    #[schemars(schema_with = "full_name_string")]
     pub full_name: String,
-    #[garde(skip)]
-    #[schemars(schema_with = "immutable_string")]
+    #[garde(custom(validation::full_team_name))]
+    #[schemars(schema_with = "full_team_name_string")]
     pub full_team_name: String,
-    #[garde(skip)]
+    /// One of `pull`, `triage`, `push`, `maintain`, `admin` or the name of a custom repository role
+    #[garde(custom(validation::permission))]
+    #[schemars(schema_with = "permission_string")]
     pub permission: String,
+    /// The `Repository` that manages `fullName`, by default the one with the same `fullName`
+    #[garde(skip)]
+    pub repository_ref: Option<RepositoryRef>,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema, PartialEq)]
@@ -38,6 +55,8 @@ pub struct RepositoryPermissionStatus {
     #[schemars(schema_with = "conditions_schema")]
     pub conditions: Vec<Condition>,
     pub healthy: Option<bool>,
+    #[serde(flatten)]
+    pub reconcile: ReconcileStatus,
 }
 
 impl From<RepositoryPermissionSpec> for RepositoryPermissionResponse {
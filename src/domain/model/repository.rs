@@ -1,33 +1,45 @@
 use std::fmt::Debug;
 
+use crate::domain::conditions_schema;
+use crate::domain::model::renamable_full_name_string;
+use crate::domain::model::{AutoConfigureSpec, ReconcileStatus};
+use crate::domain::validation;
 use differ_from_spec::DifferFromSpec;
 use garde::Validate;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::Condition;
 use kube::CustomResource;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use crate::domain::conditions_schema;
-use crate::domain::model::immutable_string;
-use crate::domain::model::AutoConfigureSpec;
 
 // see https://github.com/kubernetes/community/blob/master/contributors/devel/sig-architecture/api-conventions.md#events
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, Validate, JsonSchema, Default)]
 #[kube(
     group = "github.platform.benkeil.de",
-    version = "v1alpha1",
+    version = "v1beta1",
     kind = "Repository",
     namespaced,
-    shortname = "repo"
+    shortname = "repo",
+    category = "github",
+    printcolumn = r#"{"name":"Full Name","type":"string","jsonPath":".spec.fullName"}"#,
+    printcolumn = r#"{"name":"Ready","type":"string","jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Healthy","type":"boolean","jsonPath":".status.healthy"}"#,
+    printcolumn = r#"{"name":"Drift","type":"integer","jsonPath":".status.driftCorrections"}"#,
+    printcolumn = r#"{"name":"Last Reconcile","type":"date","jsonPath":".status.lastReconcileTime"}"#,
+    printcolumn = r#"{"name":"Age","type":"date","jsonPath":".metadata.creationTimestamp"}"#
 )]
 #[kube(status = "RepositoryStatus")]
 #[serde(rename_all = "camelCase")]
 pub struct RepositorySpec {
-    #[garde(skip)]
-    #[schemars(schema_with = "immutable_string")]
+    #[garde(custom(validation::full_name))]
+    #[schemars(schema_with = "renamable_full_name_string")]
     pub full_name: String,
+    /// Names the repository had before, it is renamed or transferred to `fullName` if only a
+    /// previous repository exists
+    #[garde(inner(inner(custom(validation::full_name))))]
+    pub previous_full_names: Option<Vec<String>>,
     #[garde(skip)]
-    pub security_and_analysis: Option<SecurityAndAnalysisResponse>,
+    pub security_and_analysis: Option<SecurityAndAnalysisSpec>,
     #[garde(skip)]
     pub delete_branch_on_merge: Option<bool>,
     #[garde(skip)]
@@ -49,6 +61,23 @@ pub struct RepositoryStatus {
     #[schemars(schema_with = "conditions_schema")]
     pub conditions: Vec<Condition>,
     pub healthy: Option<bool>,
+    /// The name of the repository on GitHub of the last successful reconciliation
+    pub full_name: Option<String>,
+    #[serde(flatten)]
+    pub reconcile: ReconcileStatus,
+}
+
+impl RepositorySpec {
+    /// The current and the previous names.
+    pub fn full_names(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.full_name).chain(self.previous_full_names.iter().flatten())
+    }
+
+    /// Whether the repository is or was named `full_name`.
+    pub fn manages(&self, full_name: &str) -> bool {
+        self.full_names()
+            .any(|name| name.eq_ignore_ascii_case(full_name))
+    }
 }
 
 impl AutoConfigureSpec for RepositorySpec {
@@ -59,9 +88,9 @@ impl AutoConfigureSpec for RepositorySpec {
     }
 }
 
-impl AutoConfigureSpec for Option<SecurityAndAnalysisResponse> {
+impl AutoConfigureSpec for Option<SecurityAndAnalysisSpec> {
     fn auto_configure(&mut self) -> &Self {
-        self.as_mut().map(|s| {
+        if let Some(s) = self.as_mut() {
             if let Some(SecurityAndAnalysisStatusResponse {
                 status: Status::Enabled,
             }) = s.secret_scanning
@@ -71,13 +100,23 @@ impl AutoConfigureSpec for Option<SecurityAndAnalysisResponse> {
                     status: Status::Enabled,
                 });
             };
-            s
-        });
+        }
         self
     }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, DifferFromSpec)]
+// the security features in the spec, GitHub names the fields like `SecurityAndAnalysisResponse`
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityAndAnalysisSpec {
+    pub advanced_security: Option<SecurityAndAnalysisStatusResponse>,
+    pub secret_scanning: Option<SecurityAndAnalysisStatusResponse>,
+    pub secret_scanning_push_protection: Option<SecurityAndAnalysisStatusResponse>,
+    pub dependabot_security_updates: Option<SecurityAndAnalysisStatusResponse>,
+    pub secret_scanning_validity_checks: Option<SecurityAndAnalysisStatusResponse>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, JsonSchema, PartialEq, DifferFromSpec)]
 pub struct RepositoryResponse {
     pub security_and_analysis: Option<SecurityAndAnalysisResponse>,
     pub delete_branch_on_merge: Option<bool>,
@@ -121,10 +160,29 @@ pub struct TeamPermission {
     pub permission: String,
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Hash)]
+pub struct CollaboratorPermission {
+    pub login: String,
+    /// The role of the collaborator, e.g. `write` or the name of a custom repository role
+    pub role_name: String,
+}
+
+// https://docs.github.com/en/rest/repos/rules?apiVersion=2022-11-28#get-all-repository-rulesets
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq)]
+pub struct RulesetResponse {
+    pub id: u64,
+    pub name: String,
+    /// `branch`, `tag` or `push`
+    pub target: Option<String>,
+    /// `disabled`, `active` or `evaluate`
+    pub enforcement: String,
+}
+
 impl From<&RepositorySpec> for RepositoryResponse {
     fn from(spec: &RepositorySpec) -> Self {
         Self {
-            security_and_analysis: spec.security_and_analysis.clone(),
+            security_and_analysis: spec.security_and_analysis.clone().map(Into::into),
             delete_branch_on_merge: spec.delete_branch_on_merge,
             allow_auto_merge: spec.allow_auto_merge,
             allow_squash_merge: spec.allow_squash_merge,
@@ -134,3 +192,27 @@ impl From<&RepositorySpec> for RepositoryResponse {
         }
     }
 }
+
+impl From<SecurityAndAnalysisSpec> for SecurityAndAnalysisResponse {
+    fn from(spec: SecurityAndAnalysisSpec) -> Self {
+        Self {
+            advanced_security: spec.advanced_security,
+            secret_scanning: spec.secret_scanning,
+            secret_scanning_push_protection: spec.secret_scanning_push_protection,
+            dependabot_security_updates: spec.dependabot_security_updates,
+            secret_scanning_validity_checks: spec.secret_scanning_validity_checks,
+        }
+    }
+}
+
+impl From<SecurityAndAnalysisResponse> for SecurityAndAnalysisSpec {
+    fn from(response: SecurityAndAnalysisResponse) -> Self {
+        Self {
+            advanced_security: response.advanced_security,
+            secret_scanning: response.secret_scanning,
+            secret_scanning_push_protection: response.secret_scanning_push_protection,
+            dependabot_security_updates: response.dependabot_security_updates,
+            secret_scanning_validity_checks: response.secret_scanning_validity_checks,
+        }
+    }
+}
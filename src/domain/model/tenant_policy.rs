@@ -0,0 +1,240 @@
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Which repositories the namespaces of a tenant may manage.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[kube(
+    group = "github.platform.benkeil.de",
+    version = "v1alpha1",
+    kind = "GitHubTenantPolicy",
+    shortname = "tenantpolicy",
+    category = "github"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct GitHubTenantPolicySpec {
+    /// The namespaces the policy applies to
+    pub namespaces: Vec<String>,
+    /// The owners the repositories may belong to
+    pub owners: Vec<String>,
+    /// The repository names must start with one of the prefixes, e.g. `pdh-da_`, any name is allowed if empty. Like the owners, the prefixes are case insensitive
+    #[serde(default)]
+    pub repository_prefixes: Vec<String>,
+    /// The teams of the tenant as `org/slug`, if set only these teams may be granted `admin`
+    pub admin_teams: Option<Vec<String>>,
+}
+
+impl GitHubTenantPolicySpec {
+    fn allows(&self, full_name: &str) -> bool {
+        let (owner, repo) = full_name.split_once('/').unwrap_or((full_name, ""));
+        self.owners
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(owner))
+            && (self.repository_prefixes.is_empty()
+                || self
+                    .repository_prefixes
+                    .iter()
+                    .any(|prefix| starts_with_ignore_case(repo, prefix)))
+    }
+}
+
+/// GitHub names are case insensitive.
+fn starts_with_ignore_case(value: &str, prefix: &str) -> bool {
+    value
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+/// The policies that apply to a namespace, a repository is allowed if any of them allows it.
+#[derive(Clone, Debug, Default)]
+pub struct TenantPolicy {
+    pub policies: Vec<GitHubTenantPolicySpec>,
+    /// Any `GitHubTenantPolicy` exists in the cluster, a namespace without a policy may not
+    /// manage any repository then. Otherwise everything is allowed without a policy.
+    pub enforced: bool,
+}
+
+impl TenantPolicy {
+    pub fn check_repository(&self, full_name: &str) -> Result<(), String> {
+        if self.policies.is_empty() {
+            return if self.enforced {
+                Err(format!(
+                    "repository {} is not allowed, no tenant policy applies to the namespace",
+                    full_name
+                ))
+            } else {
+                Ok(())
+            };
+        }
+        if self.policies.iter().any(|policy| policy.allows(full_name)) {
+            return Ok(());
+        }
+        let allowed = self
+            .policies
+            .iter()
+            .flat_map(|policy| {
+                policy.owners.iter().flat_map(|owner| {
+                    if policy.repository_prefixes.is_empty() {
+                        vec![format!("{}/*", owner)]
+                    } else {
+                        policy
+                            .repository_prefixes
+                            .iter()
+                            .map(|prefix| format!("{}/{}*", owner, prefix))
+                            .collect()
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        Err(format!(
+            "repository {} is not allowed in the namespace, allowed are {}",
+            full_name,
+            allowed.join(", ")
+        ))
+    }
+
+    /// Besides the repository, `admin` is only granted to the admin teams of the policies that
+    /// allow the repository.
+    pub fn check_permission(
+        &self,
+        full_name: &str,
+        full_team_name: &str,
+        permission: &str,
+    ) -> Result<(), String> {
+        self.check_repository(full_name)?;
+        if permission != "admin" {
+            return Ok(());
+        }
+        let admin_teams = self
+            .policies
+            .iter()
+            .filter(|policy| policy.allows(full_name))
+            .filter_map(|policy| policy.admin_teams.as_ref())
+            .flatten()
+            .collect::<Vec<_>>();
+        let restricted = self
+            .policies
+            .iter()
+            .any(|policy| policy.allows(full_name) && policy.admin_teams.is_some());
+        if restricted
+            && !admin_teams
+                .iter()
+                .any(|team| team.eq_ignore_ascii_case(full_team_name))
+        {
+            return Err(format!(
+                "team {} may not be admin of {}, only {}",
+                full_team_name,
+                full_name,
+                admin_teams
+                    .iter()
+                    .map(|team| team.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(owners: &[&str], prefixes: &[&str], admin_teams: Option<&[&str]>) -> TenantPolicy {
+        let strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        TenantPolicy {
+            policies: vec![GitHubTenantPolicySpec {
+                namespaces: vec!["pdh-da".into()],
+                owners: strings(owners),
+                repository_prefixes: strings(prefixes),
+                admin_teams: admin_teams.map(strings),
+            }],
+            enforced: true,
+        }
+    }
+
+    #[test]
+    fn matches_owners_and_prefixes_ignoring_the_case() {
+        let cases = [
+            (&[][..], "otto-ec/pdh-da_test", true),
+            (&[][..], "OTTO-EC/anything", true),
+            (&[][..], "other/pdh-da_test", false),
+            (&["pdh-da_"][..], "otto-ec/pdh-da_test", true),
+            (&["pdh-da_"][..], "otto-ec/PDH-DA_test", true),
+            (&["pdh-da_"][..], "otto-ec/pdh-db_test", false),
+            (&["pdh-da_"][..], "otto-ec/pdh", false),
+            (&["pdh-da_", "pdh-db_"][..], "otto-ec/pdh-db_test", true),
+        ];
+        for (prefixes, full_name, allowed) in cases {
+            let policy = policy(&["otto-ec"], prefixes, None);
+            assert_eq!(
+                policy.check_repository(full_name).is_ok(),
+                allowed,
+                "{} with prefixes {:?}",
+                full_name,
+                prefixes
+            );
+        }
+    }
+
+    #[test]
+    fn restricts_admin_to_the_admin_teams() {
+        let cases = [
+            (None, "otto-ec/anyone", "admin", true),
+            (
+                Some(&["otto-ec/pdh-da"][..]),
+                "otto-ec/pdh-da",
+                "admin",
+                true,
+            ),
+            (
+                Some(&["otto-ec/pdh-da"][..]),
+                "OTTO-EC/PDH-DA",
+                "admin",
+                true,
+            ),
+            (
+                Some(&["otto-ec/pdh-da"][..]),
+                "otto-ec/other",
+                "admin",
+                false,
+            ),
+            (
+                Some(&["otto-ec/pdh-da"][..]),
+                "otto-ec/other",
+                "maintain",
+                true,
+            ),
+            (Some(&[][..]), "otto-ec/pdh-da", "admin", false),
+        ];
+        for (admin_teams, full_team_name, permission, allowed) in cases {
+            let policy = policy(&["otto-ec"], &[], admin_teams);
+            assert_eq!(
+                policy
+                    .check_permission("otto-ec/pdh-da_test", full_team_name, permission)
+                    .is_ok(),
+                allowed,
+                "{} as {} with admin teams {:?}",
+                full_team_name,
+                permission,
+                admin_teams
+            );
+        }
+    }
+
+    #[test]
+    fn a_namespace_without_a_policy_is_denied_once_policies_are_enforced() {
+        assert!(TenantPolicy::default()
+            .check_repository("otto-ec/pdh-da_test")
+            .is_ok());
+
+        let enforced = TenantPolicy {
+            policies: Vec::new(),
+            enforced: true,
+        };
+        assert!(enforced.check_repository("otto-ec/pdh-da_test").is_err());
+        assert!(enforced
+            .check_permission("otto-ec/pdh-da_test", "otto-ec/pdh-da", "pull")
+            .is_err());
+    }
+}
@@ -0,0 +1,184 @@
+// The first version of the kinds with snake_case fields, it is still served and converted to the
+// storage version by the conversion webhook.
+
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::model::autolink_reference::{self, AutolinkReferenceStatus};
+use crate::domain::model::permission::{self, RepositoryPermissionStatus};
+use crate::domain::model::repository::{self, RepositoryStatus, SecurityAndAnalysisResponse};
+use crate::domain::model::{
+    full_name_string, full_team_name_string, key_prefix_string, permission_string,
+    renamable_full_name_string, url_template_string, RepositoryRef,
+};
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[kube(
+    group = "github.platform.benkeil.de",
+    version = "v1alpha1",
+    kind = "Repository",
+    namespaced,
+    shortname = "repo",
+    category = "github",
+    printcolumn = r#"{"name":"Full Name","type":"string","jsonPath":".spec.full_name"}"#,
+    printcolumn = r#"{"name":"Ready","type":"string","jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Healthy","type":"boolean","jsonPath":".status.healthy"}"#,
+    printcolumn = r#"{"name":"Drift","type":"integer","jsonPath":".status.driftCorrections"}"#,
+    printcolumn = r#"{"name":"Last Reconcile","type":"date","jsonPath":".status.lastReconcileTime"}"#,
+    printcolumn = r#"{"name":"Age","type":"date","jsonPath":".metadata.creationTimestamp"}"#
+)]
+#[kube(status = "RepositoryStatus")]
+pub struct RepositorySpec {
+    #[schemars(schema_with = "renamable_full_name_string")]
+    pub full_name: String,
+    /// Names the repository had before, it is renamed or transferred to `full_name` if only a
+    /// previous repository exists
+    pub previous_full_names: Option<Vec<String>>,
+    pub security_and_analysis: Option<SecurityAndAnalysisResponse>,
+    pub delete_branch_on_merge: Option<bool>,
+    pub allow_auto_merge: Option<bool>,
+    pub allow_squash_merge: Option<bool>,
+    pub allow_merge_commit: Option<bool>,
+    pub allow_rebase_merge: Option<bool>,
+    pub allow_update_branch: Option<bool>,
+}
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[kube(
+    group = "github.platform.benkeil.de",
+    version = "v1alpha1",
+    kind = "AutolinkReference",
+    namespaced,
+    shortname = "autolink",
+    category = "github",
+    printcolumn = r#"{"name":"Full Name","type":"string","jsonPath":".spec.full_name"}"#,
+    printcolumn = r#"{"name":"Prefix","type":"string","jsonPath":".spec.key_prefix"}"#,
+    printcolumn = r#"{"name":"ID","type":"integer","jsonPath":".status.id"}"#,
+    printcolumn = r#"{"name":"Ready","type":"string","jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Healthy","type":"boolean","jsonPath":".status.healthy"}"#,
+    printcolumn = r#"{"name":"Drift","type":"integer","jsonPath":".status.driftCorrections"}"#,
+    printcolumn = r#"{"name":"Last Reconcile","type":"date","jsonPath":".status.lastReconcileTime"}"#,
+    printcolumn = r#"{"name":"Age","type":"date","jsonPath":".metadata.creationTimestamp"}"#
+)]
+#[kube(status = "AutolinkReferenceStatus")]
+pub struct AutolinkReferenceSpec {
+    #[schemars(schema_with = "full_name_string")]
+    pub full_name: String,
+    #[schemars(schema_with = "key_prefix_string")]
+    pub key_prefix: String,
+    /// The URL of a reference, must contain `<num>`
+    #[schemars(schema_with = "url_template_string")]
+    pub url_template: String,
+    pub is_alphanumeric: bool,
+    /// The `Repository` that manages `full_name`, by default the one with the same `full_name`
+    pub repository_ref: Option<RepositoryRef>,
+}
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[kube(
+    group = "github.platform.benkeil.de",
+    version = "v1alpha1",
+    kind = "RepositoryPermission",
+    namespaced,
+    shortname = "permission",
+    category = "github",
+    printcolumn = r#"{"name":"Full Name","type":"string","jsonPath":".spec.full_name"}"#,
+    printcolumn = r#"{"name":"Team","type":"string","jsonPath":".spec.full_team_name"}"#,
+    printcolumn = r#"{"name":"Permission","type":"string","jsonPath":".spec.permission"}"#,
+    printcolumn = r#"{"name":"Ready","type":"string","jsonPath":".status.conditions[?(@.type==\"Ready\")].status"}"#,
+    printcolumn = r#"{"name":"Healthy","type":"boolean","jsonPath":".status.healthy"}"#,
+    printcolumn = r#"{"name":"Drift","type":"integer","jsonPath":".status.driftCorrections"}"#,
+    printcolumn = r#"{"name":"Last Reconcile","type":"date","jsonPath":".status.lastReconcileTime"}"#,
+    printcolumn = r#"{"name":"Age","type":"date","jsonPath":".metadata.creationTimestamp"}"#
+)]
+#[kube(status = "RepositoryPermissionStatus")]
+pub struct RepositoryPermissionSpec {
+    #[schemars(schema_with = "full_name_string")]
+    pub full_name: String,
+    #[schemars(schema_with = "full_team_name_string")]
+    pub full_team_name: String,
+    /// One of `pull`, `triage`, `push`, `maintain`, `admin` or the name of a custom repository role
+    #[schemars(schema_with = "permission_string")]
+    pub permission: String,
+    /// The `Repository` that manages `full_name`, by default the one with the same `full_name`
+    pub repository_ref: Option<RepositoryRef>,
+}
+
+impl From<RepositorySpec> for repository::RepositorySpec {
+    fn from(spec: RepositorySpec) -> Self {
+        Self {
+            full_name: spec.full_name,
+            previous_full_names: spec.previous_full_names,
+            security_and_analysis: spec.security_and_analysis.map(Into::into),
+            delete_branch_on_merge: spec.delete_branch_on_merge,
+            allow_auto_merge: spec.allow_auto_merge,
+            allow_squash_merge: spec.allow_squash_merge,
+            allow_merge_commit: spec.allow_merge_commit,
+            allow_rebase_merge: spec.allow_rebase_merge,
+            allow_update_branch: spec.allow_update_branch,
+        }
+    }
+}
+
+impl From<repository::RepositorySpec> for RepositorySpec {
+    fn from(spec: repository::RepositorySpec) -> Self {
+        Self {
+            full_name: spec.full_name,
+            previous_full_names: spec.previous_full_names,
+            security_and_analysis: spec.security_and_analysis.map(Into::into),
+            delete_branch_on_merge: spec.delete_branch_on_merge,
+            allow_auto_merge: spec.allow_auto_merge,
+            allow_squash_merge: spec.allow_squash_merge,
+            allow_merge_commit: spec.allow_merge_commit,
+            allow_rebase_merge: spec.allow_rebase_merge,
+            allow_update_branch: spec.allow_update_branch,
+        }
+    }
+}
+
+impl From<AutolinkReferenceSpec> for autolink_reference::AutolinkReferenceSpec {
+    fn from(spec: AutolinkReferenceSpec) -> Self {
+        Self {
+            full_name: spec.full_name,
+            key_prefix: spec.key_prefix,
+            url_template: spec.url_template,
+            is_alphanumeric: spec.is_alphanumeric,
+            repository_ref: spec.repository_ref,
+        }
+    }
+}
+
+impl From<autolink_reference::AutolinkReferenceSpec> for AutolinkReferenceSpec {
+    fn from(spec: autolink_reference::AutolinkReferenceSpec) -> Self {
+        Self {
+            full_name: spec.full_name,
+            key_prefix: spec.key_prefix,
+            url_template: spec.url_template,
+            is_alphanumeric: spec.is_alphanumeric,
+            repository_ref: spec.repository_ref,
+        }
+    }
+}
+
+impl From<RepositoryPermissionSpec> for permission::RepositoryPermissionSpec {
+    fn from(spec: RepositoryPermissionSpec) -> Self {
+        Self {
+            full_name: spec.full_name,
+            full_team_name: spec.full_team_name,
+            permission: spec.permission,
+            repository_ref: spec.repository_ref,
+        }
+    }
+}
+
+impl From<permission::RepositoryPermissionSpec> for RepositoryPermissionSpec {
+    fn from(spec: permission::RepositoryPermissionSpec) -> Self {
+        Self {
+            full_name: spec.full_name,
+            full_team_name: spec.full_team_name,
+            permission: spec.permission,
+            repository_ref: spec.repository_ref,
+        }
+    }
+}
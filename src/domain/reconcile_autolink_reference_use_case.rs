@@ -1,37 +1,67 @@
 use differ_from_spec::DifferFromSpec;
-use k8s_openapi::api::core::v1::ObjectReference;
-use kube::runtime::events::{Event, EventType, Recorder};
-use kube::Resource;
 
-use crate::domain::model::autolink_reference::{
-    AutolinkReference, AutolinkReferenceRequest, AutolinkReferenceStatus,
-};
+use crate::domain::model::autolink_reference::{AutolinkReferenceRequest, AutolinkReferenceSpec};
+use crate::domain::model::Change;
+use crate::domain::service::event_publisher::{DomainEvent, EventPublisher, EventSubject};
 use crate::domain::service::github_service::GitHubService;
 use crate::ControllerError;
 
 pub struct ReconcileAutolinkReferenceUseCase {
     github_service: Box<dyn GitHubService + Send + Sync>,
+    event_publisher: Box<dyn EventPublisher + Send + Sync>,
 }
 
 impl ReconcileAutolinkReferenceUseCase {
-    pub fn new(github_service: Box<dyn GitHubService + Send + Sync>) -> Self {
-        Self { github_service }
+    pub fn new(
+        github_service: Box<dyn GitHubService + Send + Sync>,
+        event_publisher: Box<dyn EventPublisher + Send + Sync>,
+    ) -> Self {
+        Self {
+            github_service,
+            event_publisher,
+        }
     }
 
+    /// Create or update the autolink reference with the known id on GitHub, returns the id of
+    /// the autolink reference on GitHub.
     pub async fn execute(
         &self,
-        autolink_reference: &AutolinkReference,
-        recorder: Recorder,
-    ) -> Result<u32, ControllerError> {
-        log::info!("reconcile: {}", &autolink_reference.spec.full_name);
+        subject: &EventSubject,
+        autolink_reference: &AutolinkReferenceSpec,
+        id: Option<u32>,
+    ) -> Result<(u32, Change), ControllerError> {
+        let (id, change) = self.reconcile(autolink_reference, id).await?;
+        let event = match change {
+            Change::Created => DomainEvent::AutolinkReferenceCreated {
+                full_name: autolink_reference.full_name.clone(),
+                key_prefix: autolink_reference.key_prefix.clone(),
+                id,
+            },
+            Change::Updated => DomainEvent::AutolinkReferenceUpdated {
+                full_name: autolink_reference.full_name.clone(),
+                key_prefix: autolink_reference.key_prefix.clone(),
+                id,
+            },
+            Change::Unchanged => return Ok((id, change)),
+        };
+        self.event_publisher.publish(subject, &event).await?;
+        Ok((id, change))
+    }
 
-        let spec: AutolinkReferenceRequest = autolink_reference.spec.clone().into();
+    async fn reconcile(
+        &self,
+        autolink_reference: &AutolinkReferenceSpec,
+        id: Option<u32>,
+    ) -> Result<(u32, Change), ControllerError> {
+        log::info!("reconcile: {}", &autolink_reference.full_name);
+
+        let spec: AutolinkReferenceRequest = autolink_reference.clone().into();
 
         // if we have an id, we need to update the autolink reference
-        if let Some(AutolinkReferenceStatus { id: Some(id), .. }) = &autolink_reference.status {
+        if let Some(id) = id {
             let result = self
                 .github_service
-                .get_autolink_reference(&autolink_reference.spec.full_name, id)
+                .get_autolink_reference(&autolink_reference.full_name, &id)
                 .await;
 
             return match result {
@@ -42,28 +72,24 @@ impl ReconcileAutolinkReferenceUseCase {
                     // if the spec differs from the actual autolink reference, we need to update it
                     if spec.differ_from_spec(&actual_autolink_reference) {
                         self.github_service
-                            .delete_autolink_references(&autolink_reference.spec.full_name, id)
+                            .delete_autolink_references(&autolink_reference.full_name, &id)
                             .await?;
                         let response = self
                             .github_service
-                            .add_autolink_reference(&autolink_reference.spec.full_name, &spec)
+                            .add_autolink_reference(&autolink_reference.full_name, &spec)
                             .await?;
-                        let reference = autolink_reference.object_ref(&());
-                        self.publish_updated_event(recorder, &reference).await?;
-                        Ok(response.id)
+                        Ok((response.id, Change::Updated))
                     } else {
-                        Ok(*id)
+                        Ok((id, Change::Unchanged))
                     }
                 }
                 Err(ControllerError::NotFound) => {
                     // if we have an id and the autolink reference does not exist, we need to create it
                     let response = self
                         .github_service
-                        .add_autolink_reference(&autolink_reference.spec.full_name, &spec)
+                        .add_autolink_reference(&autolink_reference.full_name, &spec)
                         .await?;
-                    let reference = autolink_reference.object_ref(&());
-                    self.publish_created_event(recorder, &reference).await?;
-                    Ok(response.id)
+                    Ok((response.id, Change::Created))
                 }
                 Err(e) => Err(e),
             };
@@ -72,7 +98,7 @@ impl ReconcileAutolinkReferenceUseCase {
         // if we don't have an id, we need to create the autolink reference
         let autolink_references = self
             .github_service
-            .get_autolink_references(&autolink_reference.spec.full_name)
+            .get_autolink_references(&autolink_reference.full_name)
             .await?;
 
         // the autolink reference already exists
@@ -80,57 +106,154 @@ impl ReconcileAutolinkReferenceUseCase {
             .iter()
             .find(|&v| v.key_prefix == spec.key_prefix)
         {
-            return Ok(existing.id);
+            return Ok((existing.id, Change::Unchanged));
         }
 
         // the autolink reference does not exist
         let response = self
             .github_service
-            .add_autolink_reference(&autolink_reference.spec.full_name, &spec)
+            .add_autolink_reference(&autolink_reference.full_name, &spec)
             .await?;
-        let reference = autolink_reference.object_ref(&());
-        self.publish_created_event(recorder, &reference).await?;
 
-        Ok(response.id)
+        Ok((response.id, Change::Created))
     }
+}
 
-    async fn publish_created_event(
-        &self,
-        recorder: Recorder,
-        reference: &ObjectReference,
-    ) -> Result<(), ControllerError> {
-        recorder
-            .publish(
-                &Event {
-                    action: "autolink-reference-created".into(),
-                    reason: "Reconciling".into(),
-                    note: Some("Autolink reference created".into()),
-                    type_: EventType::Normal,
-                    secondary: None,
-                },
-                reference,
-            )
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::capturing_event_publisher::CapturingEventPublisher;
+    use crate::adapter::in_memory_github_service::InMemoryGithubService;
+    use crate::domain::model::autolink_reference::AutolinkReference;
+    use crate::domain::model::manifest::Manifest;
+    use crate::domain::model::repository::RepositoryResponse;
+
+    const FULL_NAME: &str = "otto-ec/pdh-da_test";
+    const URL_TEMPLATE: &str = "https://jira/browse/JIRA-<num>";
+
+    fn spec(url_template: &str) -> AutolinkReferenceSpec {
+        AutolinkReferenceSpec {
+            full_name: FULL_NAME.into(),
+            key_prefix: "JIRA-".into(),
+            url_template: url_template.into(),
+            is_alphanumeric: false,
+            repository_ref: None,
+        }
+    }
+
+    fn subject() -> EventSubject {
+        Manifest::AutolinkReference(AutolinkReference::new("test", spec(URL_TEMPLATE))).subject()
+    }
+
+    fn github() -> InMemoryGithubService {
+        InMemoryGithubService::new().with_repository(FULL_NAME, RepositoryResponse::default())
+    }
+
+    fn use_case(
+        github: &InMemoryGithubService,
+    ) -> (ReconcileAutolinkReferenceUseCase, CapturingEventPublisher) {
+        let events = CapturingEventPublisher::new();
+        let use_case = ReconcileAutolinkReferenceUseCase::new(
+            Box::new(github.clone()),
+            Box::new(events.clone()),
+        );
+        (use_case, events)
+    }
+
+    #[tokio::test]
+    async fn creates_missing_autolink_reference() {
+        let github = github();
+        let (use_case, events) = use_case(&github);
+
+        let (id, change) = use_case
+            .execute(&subject(), &spec(URL_TEMPLATE), None)
             .await
-            .map_err(ControllerError::KubeError)
+            .unwrap();
+
+        assert_eq!(change, Change::Created);
+        assert_eq!(github.autolink_references(FULL_NAME)[0].id, id);
+        assert_eq!(
+            events.events(),
+            vec![DomainEvent::AutolinkReferenceCreated {
+                full_name: FULL_NAME.into(),
+                key_prefix: "JIRA-".into(),
+                id,
+            }]
+        );
     }
 
-    async fn publish_updated_event(
-        &self,
-        recorder: Recorder,
-        reference: &ObjectReference,
-    ) -> Result<(), ControllerError> {
-        recorder
-            .publish(
-                &Event {
-                    action: "autolink-reference-updated".into(),
-                    reason: "Reconciling".into(),
-                    note: Some("Autolink reference updated".into()),
-                    type_: EventType::Normal,
-                    secondary: None,
-                },
-                reference,
+    #[tokio::test]
+    async fn adopts_existing_autolink_reference_without_id() {
+        let github = github();
+        let id = github.add_autolink(FULL_NAME, &spec(URL_TEMPLATE).into());
+        let (use_case, events) = use_case(&github);
+
+        let result = use_case
+            .execute(&subject(), &spec(URL_TEMPLATE), None)
+            .await
+            .unwrap();
+
+        assert_eq!(result, (id, Change::Unchanged));
+        assert_eq!(github.autolink_references(FULL_NAME).len(), 1);
+        assert!(events.events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn replaces_differing_autolink_reference() {
+        let github = github();
+        let id = github.add_autolink(FULL_NAME, &spec(URL_TEMPLATE).into());
+        let (use_case, events) = use_case(&github);
+
+        let (new_id, change) = use_case
+            .execute(
+                &subject(),
+                &spec("https://jira.otto.de/browse/JIRA-<num>"),
+                Some(id),
             )
             .await
-            .map_err(ControllerError::KubeError)
+            .unwrap();
+
+        assert_eq!(change, Change::Updated);
+        assert_ne!(new_id, id);
+        let autolink_references = github.autolink_references(FULL_NAME);
+        assert_eq!(autolink_references.len(), 1);
+        assert_eq!(
+            autolink_references[0].url_template,
+            "https://jira.otto.de/browse/JIRA-<num>"
+        );
+        assert_eq!(
+            events.events(),
+            vec![DomainEvent::AutolinkReferenceUpdated {
+                full_name: FULL_NAME.into(),
+                key_prefix: "JIRA-".into(),
+                id: new_id,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn recreates_autolink_reference_deleted_on_github() {
+        let github = github();
+        let (use_case, _) = use_case(&github);
+
+        let (id, change) = use_case
+            .execute(&subject(), &spec(URL_TEMPLATE), Some(42))
+            .await
+            .unwrap();
+
+        assert_eq!(change, Change::Created);
+        assert_ne!(id, 42);
+    }
+
+    #[tokio::test]
+    async fn fails_for_missing_repository() {
+        let (use_case, events) = use_case(&InMemoryGithubService::new());
+
+        let result = use_case
+            .execute(&subject(), &spec(URL_TEMPLATE), None)
+            .await;
+
+        assert!(matches!(result, Err(ControllerError::NotFound)));
+        assert!(events.events().is_empty());
     }
 }
@@ -1,90 +1,185 @@
-use k8s_openapi::api::core::v1::ObjectReference;
-use kube::runtime::events::{Event, EventType, Recorder};
-use kube::Resource;
-
-use crate::domain::model::permission::{
-    RepositoryPermission, RepositoryPermissionResponse, RepositoryPermissionSpec,
-};
+use crate::domain::model::permission::{RepositoryPermissionResponse, RepositoryPermissionSpec};
+use crate::domain::model::Change;
+use crate::domain::service::event_publisher::{DomainEvent, EventPublisher, EventSubject};
 use crate::domain::service::github_service::GitHubService;
 use crate::ControllerError;
 
 pub struct ReconcilePermissionUseCase {
     github_service: Box<dyn GitHubService + Send + Sync>,
+    event_publisher: Box<dyn EventPublisher + Send + Sync>,
 }
 
 impl ReconcilePermissionUseCase {
-    pub fn new(github_service: Box<dyn GitHubService + Send + Sync>) -> Self {
-        Self { github_service }
+    pub fn new(
+        github_service: Box<dyn GitHubService + Send + Sync>,
+        event_publisher: Box<dyn EventPublisher + Send + Sync>,
+    ) -> Self {
+        Self {
+            github_service,
+            event_publisher,
+        }
     }
 
+    /// Grant the permission on GitHub.
     pub async fn execute(
         &self,
-        repository_permission: &RepositoryPermission,
-        recorder: Recorder,
-    ) -> Result<(), ControllerError> {
-        log::info!("reconcile: {}", &repository_permission.spec.full_name);
+        subject: &EventSubject,
+        repository_permission: &RepositoryPermissionSpec,
+    ) -> Result<Change, ControllerError> {
+        log::info!("reconcile: {}", &repository_permission.full_name);
 
         // TODO double clone
-        // enable additional settings if necessary
-        let spec_permission: RepositoryPermissionResponse =
-            repository_permission.spec.clone().into();
+        let spec_permission: RepositoryPermissionResponse = repository_permission.clone().into();
 
         let permission = self
             .github_service
             .get_team_permission(
-                &repository_permission.spec.full_name,
+                &repository_permission.full_name,
                 &spec_permission.full_team_name,
             )
             .await?;
-        let reference = repository_permission.object_ref(&());
 
-        match permission {
+        let (change, event) = match permission {
             // no permission, create
-            None => {
-                self.github_service
-                    .update_team_permission(
-                        &repository_permission.spec.full_name,
-                        &spec_permission.full_team_name,
-                        &repository_permission.spec.permission,
-                    )
-                    .await?;
-                self.publish_updated_event(&recorder, &reference).await?;
-            }
-            Some(permission) => {
-                // permission exists and differ
-                if spec_permission.permission != permission {
-                    self.github_service
-                        .update_team_permission(
-                            &repository_permission.spec.full_name,
-                            &spec_permission.full_team_name,
-                            &repository_permission.spec.permission,
-                        )
-                        .await?;
-                    self.publish_updated_event(&recorder, &reference).await?;
-                }
-            }
+            None => (
+                Change::Created,
+                DomainEvent::PermissionGranted {
+                    full_name: repository_permission.full_name.clone(),
+                    full_team_name: spec_permission.full_team_name.clone(),
+                    permission: repository_permission.permission.clone(),
+                },
+            ),
+            // permission exists and differ
+            Some(permission) if spec_permission.permission != permission => (
+                Change::Updated,
+                DomainEvent::PermissionUpdated {
+                    full_name: repository_permission.full_name.clone(),
+                    full_team_name: spec_permission.full_team_name.clone(),
+                    previous: permission,
+                    permission: repository_permission.permission.clone(),
+                },
+            ),
+            Some(_) => return Ok(Change::Unchanged),
+        };
+        self.github_service
+            .update_team_permission(
+                &repository_permission.full_name,
+                &spec_permission.full_team_name,
+                &repository_permission.permission,
+            )
+            .await?;
+        self.event_publisher.publish(subject, &event).await?;
+        Ok(change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::capturing_event_publisher::CapturingEventPublisher;
+    use crate::adapter::in_memory_github_service::InMemoryGithubService;
+    use crate::domain::model::manifest::Manifest;
+    use crate::domain::model::permission::RepositoryPermission;
+    use crate::domain::model::repository::RepositoryResponse;
+
+    const FULL_NAME: &str = "otto-ec/pdh-da_test";
+    const TEAM: &str = "otto-ec/pdh-da";
+
+    fn spec(permission: &str) -> RepositoryPermissionSpec {
+        RepositoryPermissionSpec {
+            full_name: FULL_NAME.into(),
+            full_team_name: TEAM.into(),
+            permission: permission.into(),
+            repository_ref: None,
         }
+    }
 
-        Ok(())
+    fn subject() -> EventSubject {
+        Manifest::RepositoryPermission(RepositoryPermission::new("test", spec("push"))).subject()
     }
 
-    async fn publish_updated_event(
-        &self,
-        recorder: &Recorder,
-        reference: &ObjectReference,
-    ) -> Result<(), ControllerError> {
-        recorder
-            .publish(
-                &Event {
-                    action: "permission-updated".into(),
-                    reason: "Reconciling".into(),
-                    note: Some("Permission updated".into()),
-                    type_: EventType::Normal,
-                    secondary: None,
-                },
-                reference,
-            )
+    fn github() -> InMemoryGithubService {
+        InMemoryGithubService::new().with_repository(FULL_NAME, RepositoryResponse::default())
+    }
+
+    fn use_case(
+        github: &InMemoryGithubService,
+    ) -> (ReconcilePermissionUseCase, CapturingEventPublisher) {
+        let events = CapturingEventPublisher::new();
+        let use_case =
+            ReconcilePermissionUseCase::new(Box::new(github.clone()), Box::new(events.clone()));
+        (use_case, events)
+    }
+
+    #[tokio::test]
+    async fn grants_missing_permission() {
+        let github = github();
+        let (use_case, events) = use_case(&github);
+
+        let change = use_case.execute(&subject(), &spec("push")).await.unwrap();
+
+        assert_eq!(change, Change::Created);
+        assert_eq!(
+            github.team_permission(FULL_NAME, TEAM).as_deref(),
+            Some("push")
+        );
+        assert_eq!(
+            events.events(),
+            vec![DomainEvent::PermissionGranted {
+                full_name: FULL_NAME.into(),
+                full_team_name: TEAM.into(),
+                permission: "push".into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn updates_differing_permission() {
+        let github = github().with_team_permission(FULL_NAME, TEAM, "pull");
+        let (use_case, events) = use_case(&github);
+
+        let change = use_case
+            .execute(&subject(), &spec("maintain"))
             .await
-            .map_err(ControllerError::KubeError)
+            .unwrap();
+
+        assert_eq!(change, Change::Updated);
+        assert_eq!(
+            github.team_permission(FULL_NAME, TEAM).as_deref(),
+            Some("maintain")
+        );
+        assert_eq!(
+            events.events(),
+            vec![DomainEvent::PermissionUpdated {
+                full_name: FULL_NAME.into(),
+                full_team_name: TEAM.into(),
+                previous: "pull".into(),
+                permission: "maintain".into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn leaves_matching_permission_unchanged() {
+        let github = github().with_team_permission(FULL_NAME, TEAM, "push");
+        let (use_case, events) = use_case(&github);
+
+        let change = use_case.execute(&subject(), &spec("push")).await.unwrap();
+
+        assert_eq!(change, Change::Unchanged);
+        assert!(!github
+            .calls()
+            .contains(&format!("update_team_permission {}", FULL_NAME)));
+        assert!(events.events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn fails_for_missing_repository() {
+        let (use_case, events) = use_case(&InMemoryGithubService::new());
+
+        let result = use_case.execute(&subject(), &spec("push")).await;
+
+        assert!(matches!(result, Err(ControllerError::NotFound)));
+        assert!(events.events().is_empty());
     }
 }
@@ -1,116 +1,419 @@
 use differ_from_spec::DifferFromSpec;
-use k8s_openapi::api::core::v1::ObjectReference;
-use kube::runtime::events::{Event, EventType, Recorder};
-use kube::Resource;
 
-use crate::domain::model::repository::{Repository, RepositoryResponse, RepositorySpec};
-use crate::domain::model::AutoConfigureSpec;
+use crate::domain::diff::{diff, FieldDiff};
+use crate::domain::model::repository::{RepositoryResponse, RepositorySpec};
+use crate::domain::model::{split_full_name, AutoConfigureSpec, Change};
+use crate::domain::service::event_publisher::{DomainEvent, EventPublisher, EventSubject};
 use crate::domain::service::github_service::GitHubService;
 use crate::ControllerError;
 
 pub struct ReconcileRepositoryUseCase {
     github_service: Box<dyn GitHubService + Send + Sync>,
+    event_publisher: Box<dyn EventPublisher + Send + Sync>,
 }
 
 impl ReconcileRepositoryUseCase {
-    pub fn new(github_service: Box<dyn GitHubService + Send + Sync>) -> Self {
-        Self { github_service }
+    pub fn new(
+        github_service: Box<dyn GitHubService + Send + Sync>,
+        event_publisher: Box<dyn EventPublisher + Send + Sync>,
+    ) -> Self {
+        Self {
+            github_service,
+            event_publisher,
+        }
     }
 
+    /// Create or update the repository on GitHub.
     pub async fn execute(
         &self,
-        github_repository: &Repository,
-        recorder: Recorder,
-    ) -> Result<(), ControllerError> {
-        log::info!("reconcile: {}", &github_repository.spec.full_name);
-
-        let reference = github_repository.object_ref(&());
+        subject: &EventSubject,
+        spec: &RepositorySpec,
+    ) -> Result<Change, ControllerError> {
+        log::info!("reconcile: {}", &spec.full_name);
 
         // TODO double clone
         // enable additional settings if necessary
-        let spec_repository: RepositoryResponse =
-            github_repository.spec.clone().auto_configure().into();
+        let spec_repository: RepositoryResponse = spec.clone().auto_configure().into();
 
-        let repository = self
-            .get_or_create_repository(
-                &github_repository.spec.full_name,
-                &spec_repository,
-                &reference,
-                &recorder,
-            )
-            .await?;
+        let (repository, change) = match self.github_service.get_repository(&spec.full_name).await?
+        {
+            Some(repository) => (repository, Change::Unchanged),
+            None => match self.move_previous_repository(subject, spec).await? {
+                Moved::Renamed(repository) => (repository, Change::Updated),
+                // the settings are applied once GitHub finished the transfer
+                Moved::Transferred => return Ok(Change::Updated),
+                Moved::Nothing => {
+                    let repository = self
+                        .github_service
+                        .create_repository(&spec.full_name, &spec_repository)
+                        .await?;
+                    (repository, Change::Created)
+                }
+            },
+        };
         log::debug!("repository: {:#?}", repository);
 
-        if repository.differ_from_spec(&spec_repository) {
-            log::info!("repository needs to be updated");
-            self.github_service
-                .update_repository(&github_repository.spec.full_name, &spec_repository)
-                .await?;
-            self.publish_updated_event(&recorder, &reference).await?;
+        if let Some(fields) = self
+            .update_settings(&spec.full_name, &repository, &spec_repository)
+            .await?
+        {
+            if change != Change::Created {
+                let event = DomainEvent::RepositoryUpdated {
+                    full_name: spec.full_name.clone(),
+                    fields: fields.into_iter().map(|field| field.path).collect(),
+                };
+                self.event_publisher.publish(subject, &event).await?;
+                return Ok(Change::Updated);
+            }
         }
 
-        Ok(())
+        if change == Change::Created {
+            let event = DomainEvent::RepositoryCreated {
+                full_name: spec.full_name.clone(),
+            };
+            self.event_publisher.publish(subject, &event).await?;
+        }
+        Ok(change)
     }
 
-    async fn get_or_create_repository(
+    /// Update the settings of an existing repository without creating, renaming or transferring it.
+    /// Returns the fields that were changed, empty if the repository already matched the spec.
+    pub async fn update_existing(
+        &self,
+        spec: &RepositorySpec,
+    ) -> Result<Vec<FieldDiff>, ControllerError> {
+        // enable additional settings if necessary
+        let spec_repository: RepositoryResponse = spec.clone().auto_configure().into();
+
+        let repository = self
+            .github_service
+            .get_repository(&spec.full_name)
+            .await?
+            .ok_or(ControllerError::NotFound)?;
+        log::debug!("repository: {:#?}", repository);
+
+        Ok(self
+            .update_settings(&spec.full_name, &repository, &spec_repository)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Update the repository if it differs from the spec, returns the changed fields if it did.
+    async fn update_settings(
         &self,
         full_name: &str,
-        repository_spec: &RepositoryResponse,
-        reference: &ObjectReference,
-        recorder: &Recorder,
-    ) -> Result<RepositoryResponse, ControllerError> {
-        let repository = self.github_service.get_repository(full_name).await;
-        match repository {
-            Ok(Some(repository)) => Ok(repository),
-            Ok(None) => {
+        repository: &RepositoryResponse,
+        spec_repository: &RepositoryResponse,
+    ) -> Result<Option<Vec<FieldDiff>>, ControllerError> {
+        if !repository.differ_from_spec(spec_repository) {
+            return Ok(None);
+        }
+        log::info!("repository needs to be updated");
+        self.github_service
+            .update_repository(full_name, spec_repository)
+            .await?;
+        Ok(Some(diff(repository, spec_repository)))
+    }
+
+    /// Rename or transfer the first previous repository that still exists to `full_name`.
+    async fn move_previous_repository(
+        &self,
+        subject: &EventSubject,
+        spec: &RepositorySpec,
+    ) -> Result<Moved, ControllerError> {
+        let (owner, name) = split_full_name(&spec.full_name)?;
+        for previous_full_name in spec.previous_full_names.iter().flatten() {
+            if previous_full_name.eq_ignore_ascii_case(&spec.full_name)
+                || self
+                    .github_service
+                    .get_repository(previous_full_name)
+                    .await?
+                    .is_none()
+            {
+                continue;
+            }
+            let (previous_owner, _) = split_full_name(previous_full_name)?;
+            if previous_owner.eq_ignore_ascii_case(owner) {
+                log::info!("renaming {} to {}", previous_full_name, spec.full_name);
                 let repository = self
                     .github_service
-                    .create_repository(full_name, repository_spec)
+                    .rename_repository(previous_full_name, name)
                     .await?;
-                self.publish_created_event(recorder, reference).await?;
-                Ok(repository)
+                let event = DomainEvent::RepositoryRenamed {
+                    previous_full_name: previous_full_name.clone(),
+                    full_name: spec.full_name.clone(),
+                };
+                self.event_publisher.publish(subject, &event).await?;
+                return Ok(Moved::Renamed(repository));
             }
-            Err(e) => Err(e),
+            log::info!("transferring {} to {}", previous_full_name, spec.full_name);
+            self.github_service
+                .transfer_repository(previous_full_name, owner, name)
+                .await?;
+            let event = DomainEvent::RepositoryTransferred {
+                previous_full_name: previous_full_name.clone(),
+                full_name: spec.full_name.clone(),
+            };
+            self.event_publisher.publish(subject, &event).await?;
+            return Ok(Moved::Transferred);
         }
+        Ok(Moved::Nothing)
     }
+}
 
-    async fn publish_created_event(
-        &self,
-        recorder: &Recorder,
-        reference: &ObjectReference,
-    ) -> Result<(), ControllerError> {
-        recorder
-            .publish(
-                &Event {
-                    action: "repository-created".into(),
-                    reason: "Reconciling".into(),
-                    note: Some("GitHub repository created".into()),
-                    type_: EventType::Normal,
-                    secondary: None,
-                },
-                reference,
-            )
+/// What happened to a previous repository.
+enum Moved {
+    Renamed(RepositoryResponse),
+    Transferred,
+    Nothing,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::adapter::capturing_event_publisher::CapturingEventPublisher;
+    use crate::adapter::in_memory_github_service::InMemoryGithubService;
+    use crate::domain::model::manifest::Manifest;
+    use crate::domain::model::repository::Repository;
+
+    const FULL_NAME: &str = "otto-ec/pdh-da_test";
+
+    fn spec() -> RepositorySpec {
+        RepositorySpec {
+            full_name: FULL_NAME.into(),
+            delete_branch_on_merge: Some(true),
+            allow_merge_commit: Some(false),
+            ..Default::default()
+        }
+    }
+
+    fn subject() -> EventSubject {
+        Manifest::Repository(Repository::new("test", spec())).subject()
+    }
+
+    fn repository(delete_branch_on_merge: bool) -> RepositoryResponse {
+        RepositoryResponse {
+            security_and_analysis: None,
+            delete_branch_on_merge: Some(delete_branch_on_merge),
+            allow_auto_merge: Some(false),
+            allow_squash_merge: Some(true),
+            allow_merge_commit: Some(false),
+            allow_rebase_merge: Some(true),
+            allow_update_branch: Some(false),
+        }
+    }
+
+    fn use_case(
+        github: &InMemoryGithubService,
+    ) -> (ReconcileRepositoryUseCase, CapturingEventPublisher) {
+        let events = CapturingEventPublisher::new();
+        let use_case =
+            ReconcileRepositoryUseCase::new(Box::new(github.clone()), Box::new(events.clone()));
+        (use_case, events)
+    }
+
+    #[tokio::test]
+    async fn creates_missing_repository() {
+        let github = InMemoryGithubService::new();
+        let (use_case, events) = use_case(&github);
+
+        let change = use_case.execute(&subject(), &spec()).await.unwrap();
+
+        assert_eq!(change, Change::Created);
+        assert_eq!(github.repository(FULL_NAME), Some(repository(true)));
+        assert!(!github
+            .calls()
+            .contains(&format!("update_repository {}", FULL_NAME)));
+        assert_eq!(
+            events.published(),
+            vec![(
+                subject(),
+                DomainEvent::RepositoryCreated {
+                    full_name: FULL_NAME.into()
+                }
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn updates_differing_repository() {
+        let github = InMemoryGithubService::new().with_repository(FULL_NAME, repository(false));
+        let (use_case, events) = use_case(&github);
+
+        let change = use_case.execute(&subject(), &spec()).await.unwrap();
+
+        assert_eq!(change, Change::Updated);
+        assert_eq!(github.repository(FULL_NAME), Some(repository(true)));
+        assert_eq!(
+            events.events(),
+            vec![DomainEvent::RepositoryUpdated {
+                full_name: FULL_NAME.into(),
+                fields: vec!["delete_branch_on_merge".into()],
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn leaves_matching_repository_unchanged() {
+        let github = InMemoryGithubService::new().with_repository(FULL_NAME, repository(true));
+        let (use_case, events) = use_case(&github);
+
+        let change = use_case.execute(&subject(), &spec()).await.unwrap();
+
+        assert_eq!(change, Change::Unchanged);
+        assert_eq!(
+            github.calls(),
+            vec![format!("get_repository {}", FULL_NAME)]
+        );
+        assert!(events.events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn fails_when_github_fails() {
+        let github = InMemoryGithubService::new().with_repository(FULL_NAME, repository(false));
+        github.fail_next(
+            "update_repository",
+            ControllerError::UseCaseError("injected".into()),
+        );
+        let (use_case, events) = use_case(&github);
+
+        let result = use_case.execute(&subject(), &spec()).await;
+
+        assert!(matches!(result, Err(ControllerError::UseCaseError(_))));
+        assert_eq!(github.repository(FULL_NAME), Some(repository(false)));
+        assert!(events.events().is_empty());
+        let change = use_case.execute(&subject(), &spec()).await.unwrap();
+        assert_eq!(change, Change::Updated);
+    }
+
+    #[tokio::test]
+    async fn update_existing_returns_changed_fields() {
+        let github = InMemoryGithubService::new().with_repository(FULL_NAME, repository(false));
+        let (use_case, events) = use_case(&github);
+
+        let fields = use_case.update_existing(&spec()).await.unwrap();
+
+        assert_eq!(
+            fields
+                .iter()
+                .map(|field| field.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["delete_branch_on_merge"]
+        );
+        assert_eq!(github.repository(FULL_NAME), Some(repository(true)));
+        assert!(events.events().is_empty());
+        assert!(use_case.update_existing(&spec()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_existing_does_not_create_missing_repository() {
+        let github = InMemoryGithubService::new();
+        let (use_case, _) = use_case(&github);
+
+        let result = use_case.update_existing(&spec()).await;
+
+        assert!(matches!(result, Err(ControllerError::NotFound)));
+        assert_eq!(github.repository(FULL_NAME), None);
+    }
+
+    #[tokio::test]
+    async fn a_slow_github_times_out_without_changes() {
+        let github = InMemoryGithubService::new()
+            .with_repository(FULL_NAME, repository(false))
+            .with_latency(Duration::from_secs(5));
+        let (use_case, events) = use_case(&github);
+
+        let result = tokio::time::timeout(
+            Duration::from_millis(10),
+            use_case.execute(&subject(), &spec()),
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(github.repository(FULL_NAME), Some(repository(false)));
+        assert!(github.calls().is_empty());
+        assert!(events.events().is_empty());
+    }
+
+    fn moved_spec(previous_full_name: &str) -> RepositorySpec {
+        RepositorySpec {
+            previous_full_names: Some(vec!["otto-ec/missing".into(), previous_full_name.into()]),
+            ..spec()
+        }
+    }
+
+    #[tokio::test]
+    async fn renames_previous_repository() {
+        let github = InMemoryGithubService::new()
+            .with_repository("otto-ec/pdh-da_old", repository(true))
+            .with_team_permission("otto-ec/pdh-da_old", "otto-ec/team", "push");
+        let (use_case, events) = use_case(&github);
+
+        let change = use_case
+            .execute(&subject(), &moved_spec("otto-ec/pdh-da_old"))
             .await
-            .map_err(ControllerError::KubeError)
+            .unwrap();
+
+        assert_eq!(change, Change::Updated);
+        assert_eq!(github.repository("otto-ec/pdh-da_old"), None);
+        assert_eq!(github.repository(FULL_NAME), Some(repository(true)));
+        assert_eq!(
+            github.team_permission(FULL_NAME, "otto-ec/team"),
+            Some("push".into())
+        );
+        assert!(!github
+            .calls()
+            .iter()
+            .any(|call| call.starts_with("create_repository")));
+        assert_eq!(
+            events.events(),
+            vec![DomainEvent::RepositoryRenamed {
+                previous_full_name: "otto-ec/pdh-da_old".into(),
+                full_name: FULL_NAME.into(),
+            }]
+        );
     }
 
-    async fn publish_updated_event(
-        &self,
-        recorder: &Recorder,
-        reference: &ObjectReference,
-    ) -> Result<(), ControllerError> {
-        recorder
-            .publish(
-                &Event {
-                    action: "repository-updated".into(),
-                    reason: "Reconciling".into(),
-                    note: Some("GitHub repository updated".into()),
-                    type_: EventType::Normal,
-                    secondary: None,
-                },
-                reference,
-            )
+    #[tokio::test]
+    async fn transfers_previous_repository_of_another_owner() {
+        let github =
+            InMemoryGithubService::new().with_repository("otto-eg/pdh-da_test", repository(true));
+        let (use_case, events) = use_case(&github);
+
+        let change = use_case
+            .execute(&subject(), &moved_spec("otto-eg/pdh-da_test"))
+            .await
+            .unwrap();
+
+        assert_eq!(change, Change::Updated);
+        assert!(github
+            .calls()
+            .contains(&"transfer_repository otto-eg/pdh-da_test".to_string()));
+        assert_eq!(github.repository(FULL_NAME), Some(repository(true)));
+        assert_eq!(
+            events.events(),
+            vec![DomainEvent::RepositoryTransferred {
+                previous_full_name: "otto-eg/pdh-da_test".into(),
+                full_name: FULL_NAME.into(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn previous_repository_is_ignored_once_renamed() {
+        let github = InMemoryGithubService::new()
+            .with_repository(FULL_NAME, repository(true))
+            .with_repository("otto-ec/pdh-da_old", repository(true));
+        let (use_case, _) = use_case(&github);
+
+        let change = use_case
+            .execute(&subject(), &moved_spec("otto-ec/pdh-da_old"))
             .await
-            .map_err(ControllerError::KubeError)
+            .unwrap();
+
+        assert_eq!(change, Change::Unchanged);
+        assert!(github.repository("otto-ec/pdh-da_old").is_some());
     }
 }
@@ -0,0 +1,181 @@
+use std::fmt::{Display, Formatter};
+
+use async_trait::async_trait;
+
+use crate::ControllerError;
+
+/// What a use case changed on GitHub.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DomainEvent {
+    RepositoryCreated {
+        full_name: String,
+    },
+    RepositoryUpdated {
+        full_name: String,
+        /// The settings that differed from the spec, e.g. `security_and_analysis.secret_scanning`
+        fields: Vec<String>,
+    },
+    RepositoryArchived {
+        full_name: String,
+    },
+    RepositoryRenamed {
+        previous_full_name: String,
+        full_name: String,
+    },
+    /// The repository was moved to another owner, GitHub may not have finished it yet
+    RepositoryTransferred {
+        previous_full_name: String,
+        full_name: String,
+    },
+    AutolinkReferenceCreated {
+        full_name: String,
+        key_prefix: String,
+        id: u32,
+    },
+    /// The autolink reference was replaced, GitHub can't update them
+    AutolinkReferenceUpdated {
+        full_name: String,
+        key_prefix: String,
+        id: u32,
+    },
+    AutolinkReferenceDeleted {
+        full_name: String,
+        id: u32,
+    },
+    PermissionGranted {
+        full_name: String,
+        full_team_name: String,
+        permission: String,
+    },
+    PermissionUpdated {
+        full_name: String,
+        full_team_name: String,
+        previous: String,
+        permission: String,
+    },
+    PermissionRevoked {
+        full_name: String,
+        full_team_name: String,
+    },
+}
+
+impl DomainEvent {
+    /// A machine readable name, e.g. `repository-updated`.
+    pub fn action(&self) -> &'static str {
+        match self {
+            DomainEvent::RepositoryCreated { .. } => "repository-created",
+            DomainEvent::RepositoryUpdated { .. } => "repository-updated",
+            DomainEvent::RepositoryArchived { .. } => "repository-archived",
+            DomainEvent::RepositoryRenamed { .. } => "repository-renamed",
+            DomainEvent::RepositoryTransferred { .. } => "repository-transferred",
+            DomainEvent::AutolinkReferenceCreated { .. } => "autolink-reference-created",
+            DomainEvent::AutolinkReferenceUpdated { .. } => "autolink-reference-updated",
+            DomainEvent::AutolinkReferenceDeleted { .. } => "autolink-reference-deleted",
+            DomainEvent::PermissionGranted { .. } => "permission-granted",
+            DomainEvent::PermissionUpdated { .. } => "permission-updated",
+            DomainEvent::PermissionRevoked { .. } => "permission-revoked",
+        }
+    }
+
+    /// A description for humans.
+    pub fn note(&self) -> String {
+        match self {
+            DomainEvent::RepositoryCreated { full_name } => {
+                format!("GitHub repository {} created", full_name)
+            }
+            DomainEvent::RepositoryUpdated { full_name, fields } => format!(
+                "GitHub repository {} updated: {}",
+                full_name,
+                fields.join(", ")
+            ),
+            DomainEvent::RepositoryArchived { full_name } => {
+                format!("GitHub repository {} archived", full_name)
+            }
+            DomainEvent::RepositoryRenamed {
+                previous_full_name,
+                full_name,
+            } => format!(
+                "GitHub repository {} renamed to {}",
+                previous_full_name, full_name
+            ),
+            DomainEvent::RepositoryTransferred {
+                previous_full_name,
+                full_name,
+            } => format!(
+                "GitHub repository {} transferred to {}",
+                previous_full_name, full_name
+            ),
+            DomainEvent::AutolinkReferenceCreated {
+                full_name,
+                key_prefix,
+                id,
+            } => format!(
+                "Autolink reference {} ({}) of {} created",
+                key_prefix, id, full_name
+            ),
+            DomainEvent::AutolinkReferenceUpdated {
+                full_name,
+                key_prefix,
+                id,
+            } => format!(
+                "Autolink reference {} of {} updated, the new id is {}",
+                key_prefix, full_name, id
+            ),
+            DomainEvent::AutolinkReferenceDeleted { full_name, id } => {
+                format!("Autolink reference {} of {} deleted", id, full_name)
+            }
+            DomainEvent::PermissionGranted {
+                full_name,
+                full_team_name,
+                permission,
+            } => format!(
+                "Team {} granted {} on {}",
+                full_team_name, permission, full_name
+            ),
+            DomainEvent::PermissionUpdated {
+                full_name,
+                full_team_name,
+                previous,
+                permission,
+            } => format!(
+                "Permission of team {} on {} updated from {} to {}",
+                full_team_name, full_name, previous, permission
+            ),
+            DomainEvent::PermissionRevoked {
+                full_name,
+                full_team_name,
+            } => format!(
+                "Permission of team {} on {} deleted",
+                full_team_name, full_name
+            ),
+        }
+    }
+}
+
+/// The object the event is about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventSubject {
+    pub api_version: String,
+    pub kind: String,
+    pub namespace: Option<String>,
+    pub name: String,
+    pub uid: Option<String>,
+}
+
+impl Display for EventSubject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.namespace {
+            Some(namespace) => write!(f, "{} {}/{}", self.kind, namespace, self.name),
+            None => write!(f, "{} {}", self.kind, self.name),
+        }
+    }
+}
+
+#[async_trait]
+pub trait EventPublisher {
+    async fn publish(
+        &self,
+        subject: &EventSubject,
+        event: &DomainEvent,
+    ) -> Result<(), ControllerError>;
+}
@@ -3,11 +3,15 @@ use async_trait::async_trait;
 use crate::domain::model::autolink_reference::{
     AutolinkReferenceRequest, AutolinkReferenceResponse,
 };
-use crate::domain::model::repository::RepositoryResponse;
+use crate::domain::model::repository::{
+    CollaboratorPermission, RepositoryResponse, RulesetResponse, TeamPermission,
+};
 use crate::ControllerError;
 
 #[async_trait]
 pub trait GitHubService {
+    /// Check that the configured credentials are accepted by GitHub.
+    async fn verify_credentials(&self) -> Result<(), ControllerError>;
     async fn create_repository(
         &self,
         full_name: &str,
@@ -23,6 +27,24 @@ pub trait GitHubService {
         repository: &RepositoryResponse,
     ) -> Result<RepositoryResponse, ControllerError>;
     async fn archive_repository(&self, full_name: &str) -> Result<(), ControllerError>;
+    /// Rename the repository within its owner, e.g. `otto-ec/old` to `otto-ec/new`.
+    async fn rename_repository(
+        &self,
+        full_name: &str,
+        new_name: &str,
+    ) -> Result<RepositoryResponse, ControllerError>;
+    /// Move the repository to another owner, GitHub finishes the transfer asynchronously.
+    async fn transfer_repository(
+        &self,
+        full_name: &str,
+        new_owner: &str,
+        new_name: &str,
+    ) -> Result<(), ControllerError>;
+    /// The full names of all repositories of the organization.
+    async fn list_organization_repositories(
+        &self,
+        org: &str,
+    ) -> Result<Vec<String>, ControllerError>;
     async fn get_autolink_references(
         &self,
         full_name: &str,
@@ -58,4 +80,17 @@ pub trait GitHubService {
         full_name: &str,
         full_team_name: &str,
     ) -> Result<(), ControllerError>;
+    async fn get_repository_teams(
+        &self,
+        full_name: &str,
+    ) -> Result<Vec<TeamPermission>, ControllerError>;
+    /// The direct collaborators, without the members of the organization.
+    async fn get_repository_collaborators(
+        &self,
+        full_name: &str,
+    ) -> Result<Vec<CollaboratorPermission>, ControllerError>;
+    async fn get_repository_rulesets(
+        &self,
+        full_name: &str,
+    ) -> Result<Vec<RulesetResponse>, ControllerError>;
 }
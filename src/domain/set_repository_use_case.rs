@@ -0,0 +1,26 @@
+use crate::domain::diff::FieldDiff;
+use crate::domain::model::repository::RepositorySpec;
+use crate::domain::reconcile_repository_use_case::ReconcileRepositoryUseCase;
+use crate::ControllerError;
+
+/// Update the settings of an existing repository without a `Repository` resource, the same way
+/// the controller does.
+pub struct SetRepositoryUseCase {
+    reconcile_repository_use_case: ReconcileRepositoryUseCase,
+}
+
+impl SetRepositoryUseCase {
+    pub fn new(reconcile_repository_use_case: ReconcileRepositoryUseCase) -> Self {
+        Self {
+            reconcile_repository_use_case,
+        }
+    }
+
+    /// Returns the fields that were changed, empty if the repository already matched the spec.
+    pub async fn execute(&self, spec: &RepositorySpec) -> Result<Vec<FieldDiff>, ControllerError> {
+        log::info!("set repository: {}", &spec.full_name);
+        self.reconcile_repository_use_case
+            .update_existing(spec)
+            .await
+    }
+}
@@ -0,0 +1,640 @@
+use std::fmt::{Display, Formatter};
+use std::sync::LazyLock;
+
+use garde::Validate;
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+    CustomResourceDefinition, JSONSchemaProps, JSONSchemaPropsOrArray, JSONSchemaPropsOrBool,
+};
+use kube::CustomResourceExt;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::domain::model::autolink_reference::AutolinkReferenceSpec;
+use crate::domain::model::conversion::{self, V1BETA1};
+use crate::domain::model::permission::RepositoryPermissionSpec;
+use crate::domain::model::repository::{Repository, RepositorySpec};
+
+pub const FULL_NAME_PATTERN: &str = r"^[A-Za-z0-9][A-Za-z0-9-]{0,38}/[A-Za-z0-9._-]{1,100}$";
+pub const FULL_TEAM_NAME_PATTERN: &str = r"^[A-Za-z0-9][A-Za-z0-9-]{0,38}/[a-z0-9][a-z0-9_-]*$";
+pub const KEY_PREFIX_PATTERN: &str = r"^[A-Za-z0-9.=+:/#_-]+$";
+pub const KEY_PREFIX_MAX_LENGTH: usize = 32;
+pub const URL_TEMPLATE_PATTERN: &str = r"^https?://";
+/// Custom repository roles are created by the organization, only the shape can be checked.
+pub const ROLE_NAME_PATTERN: &str = r"^\S(.{0,98}\S)?$";
+pub const PERMISSIONS: [&str; 5] = ["pull", "triage", "push", "maintain", "admin"];
+
+static FULL_NAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(FULL_NAME_PATTERN).unwrap());
+static FULL_TEAM_NAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(FULL_TEAM_NAME_PATTERN).unwrap());
+static KEY_PREFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(KEY_PREFIX_PATTERN).unwrap());
+static URL_TEMPLATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(URL_TEMPLATE_PATTERN).unwrap());
+static ROLE_NAME: LazyLock<Regex> = LazyLock::new(|| Regex::new(ROLE_NAME_PATTERN).unwrap());
+static OBJECT_NAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^[a-z0-9]([-a-z0-9]*[a-z0-9])?(\.[a-z0-9]([-a-z0-9]*[a-z0-9])?)*$").unwrap()
+});
+
+/// A problem of a manifest, found without access to GitHub or a cluster.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    /// Dotted path of the field, e.g. `spec.full_name`
+    pub path: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    fn new(path: &str, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// The repository as `owner/repo`.
+pub fn full_name(value: &str, _: &()) -> garde::Result {
+    match value.split_once('/') {
+        Some((_, "." | "..")) => Err(garde::Error::new("repository name is reserved")),
+        _ if FULL_NAME.is_match(value) => Ok(()),
+        _ => Err(garde::Error::new(format!(
+            "{:?} is not shaped owner/repo",
+            value
+        ))),
+    }
+}
+
+/// The team as `org/slug`.
+pub fn full_team_name(value: &str, _: &()) -> garde::Result {
+    if FULL_TEAM_NAME.is_match(value) {
+        Ok(())
+    } else {
+        Err(garde::Error::new(format!(
+            "{:?} is not shaped org/slug",
+            value
+        )))
+    }
+}
+
+pub fn key_prefix(value: &str, _: &()) -> garde::Result {
+    if KEY_PREFIX.is_match(value) {
+        Ok(())
+    } else {
+        Err(garde::Error::new(
+            "may only contain letters, numbers and .=+:/#_-",
+        ))
+    }
+}
+
+/// The URL of an autolink reference, `<num>` is replaced by the reference.
+pub fn url_template(value: &str, _: &()) -> garde::Result {
+    if !URL_TEMPLATE.is_match(value) {
+        return Err(garde::Error::new("must be a http or https URL"));
+    }
+    if !value.contains("<num>") {
+        return Err(garde::Error::new("must contain <num>"));
+    }
+    Ok(())
+}
+
+/// One of the built-in permissions or the name of a custom repository role.
+pub fn permission(value: &str, _: &()) -> garde::Result {
+    if PERMISSIONS.contains(&value) {
+        return Ok(());
+    }
+    if let Some(builtin) = PERMISSIONS
+        .iter()
+        .find(|builtin| builtin.eq_ignore_ascii_case(value))
+    {
+        return Err(garde::Error::new(format!(
+            "built-in permissions are lowercase, use {:?}",
+            builtin
+        )));
+    }
+    if ROLE_NAME.is_match(value) {
+        Ok(())
+    } else {
+        Err(garde::Error::new(format!(
+            "{:?} is neither one of {} nor a custom role name",
+            value,
+            PERMISSIONS.join(", ")
+        )))
+    }
+}
+
+/// The `kind/namespace/name` of a document, to find its previous version.
+pub fn document_key(document: &Value) -> String {
+    let field = |pointer: &str| document.pointer(pointer).and_then(Value::as_str);
+    let kind = field("/kind").unwrap_or_default();
+    let name = field("/metadata/name").unwrap_or_default();
+    match field("/metadata/namespace") {
+        Some(namespace) => format!("{}/{}/{}", kind, namespace, name),
+        None => format!("{}/{}", kind, name),
+    }
+}
+
+/// Validate a document like the API server would, the previous version enables the immutability
+/// rules. Documents of other API groups are ignored.
+pub fn validate_document(document: &Value, previous: Option<&Value>) -> Vec<ValidationError> {
+    let kind = document
+        .get("kind")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let Some(crd) = conversion::crd(kind) else {
+        let group = Repository::crd().spec.group;
+        let api_version = document
+            .get("apiVersion")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if api_version.starts_with(&format!("{}/", group)) {
+            return vec![ValidationError::new(
+                "kind",
+                format!("unknown kind {:?}", kind),
+            )];
+        }
+        log::warn!("skipping document of kind {:?}", kind);
+        return Vec::new();
+    };
+
+    let mut errors = Vec::new();
+    check_api_version(&crd, document, &mut errors);
+    check_metadata(document, &mut errors);
+    check_document_spec(&crd, document, previous, &mut errors);
+    errors
+}
+
+/// Validate a spec of the storage version that isn't part of a manifest, e.g. one built from the
+/// flags of the CLI.
+pub fn validate_spec(kind: &str, spec: &Value) -> Vec<ValidationError> {
+    let Some(crd) = conversion::crd(kind) else {
+        return vec![ValidationError::new(
+            "kind",
+            format!("unknown kind {:?}", kind),
+        )];
+    };
+    let document = json!({ "apiVersion": V1BETA1, "kind": kind, "spec": spec });
+    let mut errors = Vec::new();
+    check_document_spec(&crd, &document, None, &mut errors);
+    errors
+}
+
+fn check_document_spec(
+    crd: &CustomResourceDefinition,
+    document: &Value,
+    previous: Option<&Value>,
+    errors: &mut Vec<ValidationError>,
+) {
+    let spec = document.get("spec").unwrap_or(&Value::Null);
+    // the previous version may use another API version
+    let previous = previous.and_then(|previous| {
+        let api_version = document.get("apiVersion").and_then(Value::as_str)?;
+        conversion::convert(previous.clone(), api_version).ok()
+    });
+    match spec_schema(crd, document) {
+        Some(schema) if !spec.is_null() => check_schema(
+            schema,
+            spec,
+            previous.as_ref().and_then(|previous| previous.get("spec")),
+            "spec",
+            errors,
+        ),
+        Some(_) => errors.push(ValidationError::new("spec", "required")),
+        None => {}
+    }
+    if !errors.is_empty() {
+        // the spec can't be deserialized
+        return;
+    }
+
+    // the rules are checked on the storage version
+    let spec = match conversion::convert(document.clone(), V1BETA1) {
+        Ok(document) => document["spec"].clone(),
+        Err(e) => return errors.push(ValidationError::new("spec", e)),
+    };
+    match crd.spec.names.kind.as_str() {
+        "Repository" => check_spec::<RepositorySpec>(&spec, errors),
+        "AutolinkReference" => check_spec::<AutolinkReferenceSpec>(&spec, errors),
+        "RepositoryPermission" => check_spec::<RepositoryPermissionSpec>(&spec, errors),
+        kind => unreachable!("no CRD for kind {}", kind),
+    }
+}
+
+fn check_api_version(
+    crd: &CustomResourceDefinition,
+    document: &Value,
+    errors: &mut Vec<ValidationError>,
+) {
+    let api_version = document
+        .get("apiVersion")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let served = crd
+        .spec
+        .versions
+        .iter()
+        .filter(|version| version.served)
+        .map(|version| format!("{}/{}", crd.spec.group, version.name))
+        .collect::<Vec<_>>();
+    if !served.iter().any(|served| served == api_version) {
+        errors.push(ValidationError::new(
+            "apiVersion",
+            format!("{:?} is not one of {}", api_version, served.join(", ")),
+        ));
+    }
+}
+
+fn check_metadata(document: &Value, errors: &mut Vec<ValidationError>) {
+    match document.pointer("/metadata/name").and_then(Value::as_str) {
+        None => errors.push(ValidationError::new("metadata.name", "required")),
+        Some(name) if name.len() > 253 || !OBJECT_NAME.is_match(name) => {
+            errors.push(ValidationError::new(
+                "metadata.name",
+                format!("{:?} is not a valid object name (RFC 1123 subdomain)", name),
+            ))
+        }
+        Some(_) => {}
+    }
+}
+
+/// The schema of the spec in the version of the document.
+fn spec_schema<'a>(
+    crd: &'a CustomResourceDefinition,
+    document: &Value,
+) -> Option<&'a JSONSchemaProps> {
+    let api_version = document.get("apiVersion").and_then(Value::as_str)?;
+    crd.spec
+        .versions
+        .iter()
+        .find(|version| format!("{}/{}", crd.spec.group, version.name) == api_version)?
+        .schema
+        .as_ref()?
+        .open_api_v3_schema
+        .as_ref()?
+        .properties
+        .as_ref()?
+        .get("spec")
+}
+
+/// Check a value against the structural schema of a CRD, CEL rules other than the immutability
+/// rule `self == oldSelf` are not evaluated.
+fn check_schema(
+    schema: &JSONSchemaProps,
+    value: &Value,
+    old: Option<&Value>,
+    path: &str,
+    errors: &mut Vec<ValidationError>,
+) {
+    if value.is_null() {
+        if schema.nullable != Some(true) {
+            errors.push(ValidationError::new(path, "must not be null"));
+        }
+        return;
+    }
+
+    let type_matches = match schema.type_.as_deref() {
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("boolean") => value.is_boolean(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some("number") => value.is_number(),
+        _ => true,
+    };
+    if !type_matches {
+        errors.push(ValidationError::new(
+            path,
+            format!(
+                "must be of type {}",
+                schema.type_.as_deref().unwrap_or_default()
+            ),
+        ));
+        return;
+    }
+
+    if let Some(values) = &schema.enum_ {
+        if !values.iter().any(|allowed| &allowed.0 == value) {
+            let allowed = values
+                .iter()
+                .map(|allowed| allowed.0.to_string())
+                .collect::<Vec<_>>();
+            errors.push(ValidationError::new(
+                path,
+                format!("{} is not one of {}", value, allowed.join(", ")),
+            ));
+        }
+    }
+
+    match value {
+        Value::String(value) => check_string(schema, value, path, errors),
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if schema.minimum.is_some_and(|minimum| number < minimum) {
+                errors.push(ValidationError::new(
+                    path,
+                    format!("must be at least {}", schema.minimum.unwrap_or_default()),
+                ));
+            }
+            if schema.maximum.is_some_and(|maximum| number > maximum) {
+                errors.push(ValidationError::new(
+                    path,
+                    format!("must be at most {}", schema.maximum.unwrap_or_default()),
+                ));
+            }
+        }
+        Value::Object(fields) => {
+            for required in schema.required.iter().flatten() {
+                if fields.get(required).is_none_or(Value::is_null) {
+                    errors.push(ValidationError::new(
+                        &format!("{}.{}", path, required),
+                        "required",
+                    ));
+                }
+            }
+            let preserve_unknown_fields = schema.x_kubernetes_preserve_unknown_fields == Some(true);
+            for (name, field) in fields {
+                let field_path = format!("{}.{}", path, name);
+                let old_field = old.and_then(|old| old.get(name));
+                match (
+                    schema
+                        .properties
+                        .as_ref()
+                        .and_then(|properties| properties.get(name)),
+                    &schema.additional_properties,
+                ) {
+                    (Some(field_schema), _) => {
+                        check_schema(field_schema, field, old_field, &field_path, errors)
+                    }
+                    (None, Some(JSONSchemaPropsOrBool::Schema(field_schema))) => {
+                        check_schema(field_schema, field, old_field, &field_path, errors)
+                    }
+                    (None, Some(JSONSchemaPropsOrBool::Bool(true))) => {}
+                    _ if preserve_unknown_fields => {}
+                    _ => errors.push(ValidationError::new(&field_path, "unknown field")),
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(JSONSchemaPropsOrArray::Schema(item_schema)) = &schema.items {
+                for (index, item) in items.iter().enumerate() {
+                    let old_item = old.and_then(|old| old.get(index));
+                    check_schema(
+                        item_schema,
+                        item,
+                        old_item,
+                        &format!("{}[{}]", path, index),
+                        errors,
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+
+    // transition rules only apply on updates
+    if let Some(old) = old {
+        for rule in schema.x_kubernetes_validations.iter().flatten() {
+            if rule.rule == "self == oldSelf" && old != value {
+                errors.push(ValidationError::new(
+                    path,
+                    rule.message
+                        .clone()
+                        .unwrap_or_else(|| "Value is immutable".to_string()),
+                ));
+            }
+        }
+    }
+}
+
+fn check_string(
+    schema: &JSONSchemaProps,
+    value: &str,
+    path: &str,
+    errors: &mut Vec<ValidationError>,
+) {
+    let length = value.chars().count() as i64;
+    if let Some(min_length) = schema.min_length.filter(|min_length| length < *min_length) {
+        errors.push(ValidationError::new(
+            path,
+            format!("must be at least {} characters long", min_length),
+        ));
+    }
+    if let Some(max_length) = schema.max_length.filter(|max_length| length > *max_length) {
+        errors.push(ValidationError::new(
+            path,
+            format!("must be at most {} characters long", max_length),
+        ));
+    }
+    if let Some(pattern) = &schema.pattern {
+        match Regex::new(pattern) {
+            Ok(regex) if !regex.is_match(value) => errors.push(ValidationError::new(
+                path,
+                format!("{:?} does not match {}", value, pattern),
+            )),
+            Ok(_) => {}
+            Err(e) => log::warn!("skipping invalid pattern {:?} of {}: {}", pattern, path, e),
+        }
+    }
+}
+
+/// Deserialize the spec and run its garde rules.
+fn check_spec<T: DeserializeOwned + Validate<Context = ()>>(
+    spec: &Value,
+    errors: &mut Vec<ValidationError>,
+) {
+    let spec = match serde_json::from_value::<T>(spec.clone()) {
+        Ok(spec) => spec,
+        Err(e) => {
+            errors.push(ValidationError::new("spec", e.to_string()));
+            return;
+        }
+    };
+    if let Err(report) = spec.validate() {
+        for (path, error) in report.iter() {
+            errors.push(ValidationError::new(
+                &format!("spec.{}", path),
+                error.to_string(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository(full_name: &str) -> Value {
+        json!({
+            "apiVersion": V1BETA1,
+            "kind": "Repository",
+            "metadata": { "name": "test", "namespace": "otto-ec" },
+            "spec": { "fullName": full_name },
+        })
+    }
+
+    fn autolink_reference(key_prefix: &str, url_template: &str) -> Value {
+        json!({
+            "apiVersion": V1BETA1,
+            "kind": "AutolinkReference",
+            "metadata": { "name": "test-jira", "namespace": "otto-ec" },
+            "spec": {
+                "fullName": "otto-ec/pdh-da_test",
+                "keyPrefix": key_prefix,
+                "urlTemplate": url_template,
+                "isAlphanumeric": false,
+            },
+        })
+    }
+
+    fn permission(permission: &str) -> Value {
+        json!({
+            "apiVersion": V1BETA1,
+            "kind": "RepositoryPermission",
+            "metadata": { "name": "test-developers", "namespace": "otto-ec" },
+            "spec": {
+                "fullName": "otto-ec/pdh-da_test",
+                "fullTeamName": "otto-ec/developers",
+                "permission": permission,
+            },
+        })
+    }
+
+    fn paths(errors: Vec<ValidationError>) -> Vec<String> {
+        errors.into_iter().map(|error| error.path).collect()
+    }
+
+    #[test]
+    fn checks_the_patterns_of_the_schema() {
+        assert_eq!(
+            paths(validate_document(&repository("otto-ec/pdh-da_test"), None)),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            paths(validate_document(&repository("otto-ec"), None)),
+            vec!["spec.fullName"]
+        );
+        assert_eq!(
+            paths(validate_document(&repository("-otto/repo"), None)),
+            vec!["spec.fullName"]
+        );
+        assert_eq!(
+            paths(validate_document(
+                &autolink_reference("JIRA-", "https://jira.example.com/<num>"),
+                None
+            )),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            paths(validate_document(
+                &autolink_reference("JIRA ", "https://jira.example.com/<num>"),
+                None
+            )),
+            vec!["spec.keyPrefix"]
+        );
+        assert_eq!(
+            paths(validate_document(
+                &autolink_reference("JIRA-", "ftp://jira.example.com/<num>"),
+                None
+            )),
+            vec!["spec.urlTemplate"]
+        );
+    }
+
+    #[test]
+    fn the_repository_name_must_not_be_reserved() {
+        let errors = validate_document(&repository("otto-ec/.."), None);
+
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].message, "repository name is reserved");
+    }
+
+    #[test]
+    fn the_url_template_must_contain_the_reference() {
+        let errors = validate_document(
+            &autolink_reference("JIRA-", "https://jira.example.com/browse/"),
+            None,
+        );
+
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].message, "must contain <num>");
+    }
+
+    #[test]
+    fn accepts_built_in_permissions_and_custom_roles() {
+        for valid in [
+            "pull",
+            "triage",
+            "push",
+            "maintain",
+            "admin",
+            "security reviewer",
+        ] {
+            assert_eq!(
+                validate_document(&permission(valid), None),
+                Vec::new(),
+                "{}",
+                valid
+            );
+        }
+        let errors = validate_document(&permission("Admin"), None);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(
+            errors[0].message,
+            "built-in permissions are lowercase, use \"admin\""
+        );
+        assert_eq!(
+            paths(validate_document(&permission(" push"), None)),
+            vec!["spec.permission"]
+        );
+    }
+
+    #[test]
+    fn immutable_fields_may_not_change() {
+        let previous = autolink_reference("JIRA-", "https://jira.example.com/<num>");
+
+        let errors = validate_document(
+            &autolink_reference("TICKET-", "https://jira.example.com/<num>"),
+            Some(&previous),
+        );
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].path, "spec.keyPrefix");
+        assert_eq!(errors[0].message, "Value is immutable");
+
+        // without the previous version the spec is a new one
+        let changed = autolink_reference("TICKET-", "https://tickets.example.com/<num>");
+        assert_eq!(validate_document(&changed, None), Vec::new());
+        assert_eq!(
+            paths(validate_document(
+                &autolink_reference("JIRA-", "https://tickets.example.com/<num>"),
+                Some(&previous)
+            )),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn validates_a_spec_without_a_manifest() {
+        let spec = RepositorySpec {
+            full_name: "otto-ec/pdh-da_test".into(),
+            delete_branch_on_merge: Some(true),
+            ..RepositorySpec::default()
+        };
+        assert_eq!(
+            validate_spec("Repository", &serde_json::to_value(&spec).unwrap()),
+            Vec::new()
+        );
+
+        let spec = RepositorySpec {
+            full_name: "otto-ec".into(),
+            ..spec
+        };
+        let errors = validate_spec("Repository", &serde_json::to_value(&spec).unwrap());
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].path, "spec.fullName");
+    }
+}
@@ -0,0 +1,374 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta};
+use k8s_openapi::chrono;
+use kube::api::PostParams;
+use kube::{Api, Client};
+
+use crate::config::{env_duration_or, env_or};
+use crate::ControllerError;
+
+// see also: https://kubernetes.io/docs/concepts/architecture/leases/#leader-election
+
+#[derive(Clone, Debug)]
+pub struct LeaderElectionConfig {
+    pub enabled: bool,
+    pub lease_name: String,
+    pub lease_namespace: String,
+    /// Unique name of this replica, the pod name if running in a cluster
+    pub identity: String,
+    /// How long a lease is valid without being renewed
+    pub lease_duration: Duration,
+    /// How long the leader keeps trying to renew the lease before it gives up leadership
+    pub renew_deadline: Duration,
+    /// How often candidates try to acquire and the leader tries to renew the lease
+    pub retry_period: Duration,
+}
+
+impl LeaderElectionConfig {
+    pub fn from_env() -> Result<Self, ControllerError> {
+        let identity = std::env::var("POD_NAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| format!("github-operator-{}", std::process::id()));
+        let config = Self {
+            enabled: env_or("APP_LEADER_ELECTION_ENABLED", true)?,
+            lease_name: env_or(
+                "APP_LEADER_ELECTION_LEASE_NAME",
+                "github-operator".to_string(),
+            )?,
+            lease_namespace: std::env::var("APP_LEADER_ELECTION_LEASE_NAMESPACE")
+                .or_else(|_| std::env::var("POD_NAMESPACE"))
+                .unwrap_or("default".to_string()),
+            identity,
+            lease_duration: env_duration_or(
+                "APP_LEADER_ELECTION_LEASE_DURATION",
+                Duration::from_secs(15),
+            )?,
+            renew_deadline: env_duration_or(
+                "APP_LEADER_ELECTION_RENEW_DEADLINE",
+                Duration::from_secs(10),
+            )?,
+            retry_period: env_duration_or(
+                "APP_LEADER_ELECTION_RETRY_PERIOD",
+                Duration::from_secs(2),
+            )?,
+        };
+        if config.renew_deadline >= config.lease_duration
+            || config.retry_period >= config.renew_deadline
+        {
+            return Err(ControllerError::ConfigurationError(
+                "leader election requires retry period < renew deadline < lease duration".into(),
+            ));
+        }
+        Ok(config)
+    }
+}
+
+/// How often a conflicting update of the lease is retried with the current lease.
+const CONFLICT_RETRIES: usize = 3;
+
+/// Lease based leader election, so only one replica reconciles at a time.
+pub struct LeaderElector {
+    api: Api<Lease>,
+    config: LeaderElectionConfig,
+    observed: Mutex<Option<Observed>>,
+}
+
+/// The last version of the lease this replica saw and when it saw it first.
+///
+/// Like client-go, a lease expires when it didn't change for its duration on the local clock,
+/// the `renewTime` written by another replica is never compared with the local time.
+#[derive(Clone, Debug)]
+struct Observed {
+    resource_version: Option<String>,
+    at: Instant,
+}
+
+#[derive(Debug, PartialEq)]
+enum Decision {
+    Renew,
+    TakeOver,
+    Wait,
+}
+
+impl LeaderElector {
+    pub fn new(client: Client, config: LeaderElectionConfig) -> Self {
+        Self {
+            api: Api::namespaced(client, &config.lease_namespace),
+            config,
+            observed: Mutex::new(None),
+        }
+    }
+
+    /// Wait until this replica holds the lease.
+    pub async fn acquire(&self) {
+        log::info!(
+            "waiting for lease {}/{} as {}",
+            self.config.lease_namespace,
+            self.config.lease_name,
+            self.config.identity
+        );
+        loop {
+            match self.try_acquire_or_renew().await {
+                Ok(true) => {
+                    log::info!("acquired lease {}", self.config.lease_name);
+                    return;
+                }
+                Ok(false) => {}
+                Err(e) => log::warn!("failed to acquire lease: {:?}", e),
+            }
+            tokio::time::sleep(self.config.retry_period).await;
+        }
+    }
+
+    /// Keep renewing the lease, returns as soon as the leadership is lost.
+    pub async fn hold(&self) {
+        let mut last_renew = Instant::now();
+        loop {
+            tokio::time::sleep(self.config.retry_period).await;
+            match self.try_acquire_or_renew().await {
+                Ok(true) => last_renew = Instant::now(),
+                Ok(false) => {
+                    log::warn!(
+                        "lease {} taken over by another replica",
+                        self.config.lease_name
+                    );
+                    return;
+                }
+                Err(e) => {
+                    log::warn!("failed to renew lease: {:?}", e);
+                    if last_renew.elapsed() > self.config.renew_deadline {
+                        log::warn!(
+                            "renew deadline of lease {} exceeded",
+                            self.config.lease_name
+                        );
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Give up the lease, so another replica can take over without waiting for it to expire.
+    pub async fn release(&self) -> Result<(), ControllerError> {
+        let Some(mut lease) = self
+            .api
+            .get_opt(&self.config.lease_name)
+            .await
+            .map_err(ControllerError::KubeError)?
+        else {
+            return Ok(());
+        };
+        let spec = lease.spec.get_or_insert_with(LeaseSpec::default);
+        if spec.holder_identity.as_ref() != Some(&self.config.identity) {
+            return Ok(());
+        }
+        spec.holder_identity = None;
+        spec.acquire_time = None;
+        spec.renew_time = None;
+        self.api
+            .replace(&self.config.lease_name, &PostParams::default(), &lease)
+            .await
+            .map_err(ControllerError::KubeError)?;
+        log::info!("released lease {}", self.config.lease_name);
+        Ok(())
+    }
+
+    /// Returns `true` if this replica holds the lease afterwards.
+    async fn try_acquire_or_renew(&self) -> Result<bool, ControllerError> {
+        let mut attempt = 0;
+        loop {
+            match self.try_update().await {
+                // the lease changed since it was read, decide again with the current one
+                Err(kube::Error::Api(e)) if e.code == 409 && attempt < CONFLICT_RETRIES => {
+                    attempt += 1;
+                }
+                result => return result.map_err(ControllerError::KubeError),
+            }
+        }
+    }
+
+    async fn try_update(&self) -> Result<bool, kube::Error> {
+        let now = chrono::Utc::now();
+        let lease_duration_seconds = self.config.lease_duration.as_secs() as i32;
+        let Some(mut lease) = self.api.get_opt(&self.config.lease_name).await? else {
+            let lease = Lease {
+                metadata: ObjectMeta {
+                    name: Some(self.config.lease_name.clone()),
+                    namespace: Some(self.config.lease_namespace.clone()),
+                    ..ObjectMeta::default()
+                },
+                spec: Some(LeaseSpec {
+                    holder_identity: Some(self.config.identity.clone()),
+                    lease_duration_seconds: Some(lease_duration_seconds),
+                    acquire_time: Some(MicroTime(now)),
+                    renew_time: Some(MicroTime(now)),
+                    lease_transitions: Some(0),
+                    ..LeaseSpec::default()
+                }),
+            };
+            let created = self.api.create(&PostParams::default(), &lease).await?;
+            self.observe(&created, Instant::now());
+            return Ok(true);
+        };
+
+        let decision = decide(
+            &self.config.identity,
+            &lease,
+            &mut self.observed.lock().unwrap(),
+            Instant::now(),
+        );
+        let spec = lease.spec.get_or_insert_with(LeaseSpec::default);
+        match decision {
+            Decision::Renew => {
+                spec.renew_time = Some(MicroTime(now));
+                spec.lease_duration_seconds = Some(lease_duration_seconds);
+            }
+            Decision::TakeOver => {
+                spec.holder_identity = Some(self.config.identity.clone());
+                spec.acquire_time = Some(MicroTime(now));
+                spec.renew_time = Some(MicroTime(now));
+                spec.lease_duration_seconds = Some(lease_duration_seconds);
+                spec.lease_transitions = Some(spec.lease_transitions.unwrap_or(0) + 1);
+            }
+            Decision::Wait => return Ok(false),
+        }
+
+        // the resource version in the metadata guards against concurrent updates
+        let replaced = self
+            .api
+            .replace(&self.config.lease_name, &PostParams::default(), &lease)
+            .await?;
+        self.observe(&replaced, Instant::now());
+        Ok(true)
+    }
+
+    fn observe(&self, lease: &Lease, now: Instant) {
+        *self.observed.lock().unwrap() = Some(Observed {
+            resource_version: lease.metadata.resource_version.clone(),
+            at: now,
+        });
+    }
+}
+
+/// Whether this replica renews, takes over or waits for the lease, `observed` is updated when
+/// the lease changed since it was last seen.
+fn decide(
+    identity: &str,
+    lease: &Lease,
+    observed: &mut Option<Observed>,
+    now: Instant,
+) -> Decision {
+    let resource_version = &lease.metadata.resource_version;
+    let observed_at = match observed {
+        Some(observed) if observed.resource_version == *resource_version => observed.at,
+        _ => {
+            *observed = Some(Observed {
+                resource_version: resource_version.clone(),
+                at: now,
+            });
+            now
+        }
+    };
+    let spec = lease.spec.clone().unwrap_or_default();
+    let lease_duration =
+        Duration::from_secs(spec.lease_duration_seconds.unwrap_or_default().max(0) as u64);
+    match spec.holder_identity.as_deref() {
+        Some(holder) if holder == identity => Decision::Renew,
+        None | Some("") => Decision::TakeOver,
+        Some(_) if observed_at + lease_duration <= now => Decision::TakeOver,
+        Some(_) => Decision::Wait,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lease(holder: Option<&str>, resource_version: &str) -> Lease {
+        Lease {
+            metadata: ObjectMeta {
+                resource_version: Some(resource_version.into()),
+                ..ObjectMeta::default()
+            },
+            spec: Some(LeaseSpec {
+                holder_identity: holder.map(str::to_string),
+                lease_duration_seconds: Some(15),
+                // far in the past, as seen from a replica with a skewed clock
+                renew_time: Some(MicroTime(chrono::DateTime::UNIX_EPOCH)),
+                ..LeaseSpec::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn the_holder_renews() {
+        let mut observed = None;
+
+        let decision = decide("a", &lease(Some("a"), "1"), &mut observed, Instant::now());
+
+        assert_eq!(decision, Decision::Renew);
+    }
+
+    #[test]
+    fn takes_over_a_released_lease() {
+        let mut observed = None;
+
+        let decision = decide("a", &lease(None, "1"), &mut observed, Instant::now());
+
+        assert_eq!(decision, Decision::TakeOver);
+    }
+
+    #[test]
+    fn waits_a_lease_duration_before_taking_over_regardless_of_the_renew_time() {
+        let start = Instant::now();
+        let mut observed = None;
+        let lease = lease(Some("b"), "1");
+
+        assert_eq!(decide("a", &lease, &mut observed, start), Decision::Wait);
+        assert_eq!(
+            decide("a", &lease, &mut observed, start + Duration::from_secs(14)),
+            Decision::Wait
+        );
+        assert_eq!(
+            decide("a", &lease, &mut observed, start + Duration::from_secs(15)),
+            Decision::TakeOver
+        );
+    }
+
+    #[test]
+    fn a_renewed_lease_is_observed_again() {
+        let start = Instant::now();
+        let mut observed = None;
+
+        decide("a", &lease(Some("b"), "1"), &mut observed, start);
+        let decision = decide(
+            "a",
+            &lease(Some("b"), "2"),
+            &mut observed,
+            start + Duration::from_secs(10),
+        );
+
+        assert_eq!(decision, Decision::Wait);
+        assert_eq!(
+            decide(
+                "a",
+                &lease(Some("b"), "2"),
+                &mut observed,
+                start + Duration::from_secs(20)
+            ),
+            Decision::Wait
+        );
+        assert_eq!(
+            decide(
+                "a",
+                &lease(Some("b"), "2"),
+                &mut observed,
+                start + Duration::from_secs(25)
+            ),
+            Decision::TakeOver
+        );
+    }
+}
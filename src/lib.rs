@@ -1,38 +1,41 @@
-use opentelemetry::{global, KeyValue};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
 
-use opentelemetry::metrics::Meter;
 use opentelemetry::trace::TracerProvider;
+use opentelemetry::{global, KeyValue};
 use opentelemetry_otlp::{Protocol, WithExportConfig};
-use opentelemetry_prometheus::exporter;
-use opentelemetry_sdk::metrics::MeterProviderBuilder;
 use opentelemetry_sdk::trace::SdkTracerProvider;
 use opentelemetry_sdk::Resource;
 use prometheus::Registry;
 use thiserror::Error;
-use tracing::span;
-use tracing_opentelemetry::MetricsLayer;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
 pub mod adapter;
+pub mod config;
 pub mod domain;
 pub mod extensions;
+pub mod metrics;
 
 #[derive(Error, Debug)]
 pub enum ControllerError {
-    #[error("SerializationError")]
+    #[error("SerializationError: {0}")]
     SerializationError(serde_json::Error),
 
     #[error("KubeError: {0}")]
     KubeError(kube::Error),
 
-    #[error("ConfigurationError")]
-    ConfigurationError,
+    #[error("ConfigurationError: {0}")]
+    ConfigurationError(String),
 
+    /// GitHub could not be reached, an error response of GitHub is a [`ControllerError::GitHubError`]
     #[error("HttpError: {0}")]
     HttpError(Box<ureq::Error>),
 
+    #[error("GitHubError: {0}")]
+    GitHubError(GitHubError),
+
     #[error("IoError: {0}")]
     IoError(std::io::Error),
 
@@ -44,11 +47,11 @@ pub enum ControllerError {
     #[error("FinalizerError: {0}")]
     FinalizerError(Box<kube::runtime::finalizer::Error<ControllerError>>),
 
-    #[error("IllegalDocument")]
-    IllegalDocument,
+    #[error("IllegalDocument: {0}")]
+    IllegalDocument(String),
 
-    #[error("UseCaseError")]
-    UseCaseError,
+    #[error("UseCaseError: {0}")]
+    UseCaseError(String),
 
     #[error("CRD is not queryable; {0}. Is the CRD installed?")]
     CrdNotFound(kube::Error),
@@ -58,15 +61,206 @@ pub enum ControllerError {
 
     #[error("NotFound")]
     NotFound,
+
+    #[error("LeadershipLost")]
+    LeadershipLost,
+
+    #[error("ManifestError: {0}")]
+    ManifestError(String),
+
+    #[error("InvalidSpec: {0}")]
+    InvalidSpec(String),
+
+    #[error("PolicyViolation: {0}")]
+    PolicyViolation(String),
+
+    /// The `Repository` of a child object doesn't exist or isn't ready yet
+    #[error("WaitingForRepository: {0}")]
+    WaitingForRepository(String),
+
+    /// A `Repository` is deleted after its child objects are gone
+    #[error("WaitingForChildren: {0}")]
+    WaitingForChildren(String),
+}
+
+/// How to handle a failed reconciliation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// May succeed later without any change, e.g. GitHub is unavailable or rate limited
+    Retryable,
+    /// Fails until the spec changes
+    Permanent,
+    /// The token of the operator is invalid
+    Auth,
+    /// The token or the tenant may not change the resource
+    PermissionDenied,
+}
+
+impl ControllerError {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            ControllerError::GitHubError(error) => error.class(),
+            ControllerError::InvalidSpec(_)
+            | ControllerError::IllegalDocument(_)
+            | ControllerError::ConfigurationError(_)
+            | ControllerError::SerializationError(_)
+            | ControllerError::NotFound
+            | ControllerError::AlreadyExists => ErrorClass::Permanent,
+            ControllerError::PolicyViolation(_) => ErrorClass::PermissionDenied,
+            _ => ErrorClass::Retryable,
+        }
+    }
+
+    /// The reason of the `Ready` condition, e.g. `Forbidden`.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ControllerError::GitHubError(error) => error.reason(),
+            ControllerError::HttpError(_) => "GitHubUnreachable",
+            ControllerError::KubeError(_) => "KubernetesError",
+            ControllerError::InvalidSpec(_) => "InvalidSpec",
+            ControllerError::PolicyViolation(_) => "PolicyViolation",
+            ControllerError::NotFound => "NotFound",
+            ControllerError::AlreadyExists => "AlreadyExists",
+            ControllerError::WaitingForRepository(_) => "WaitingForRepository",
+            ControllerError::WaitingForChildren(_) => "WaitingForChildren",
+            _ => "ReconcileFailed",
+        }
+    }
+
+    /// The message of the `Ready` condition.
+    pub fn message(&self) -> String {
+        match self {
+            ControllerError::InvalidSpec(message)
+            | ControllerError::PolicyViolation(message)
+            | ControllerError::WaitingForRepository(message)
+            | ControllerError::WaitingForChildren(message) => message.clone(),
+            error => error.to_string(),
+        }
+    }
+}
+
+/// An error response of the GitHub API.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitHubError {
+    pub status: u16,
+    pub message: String,
+    pub documentation_url: Option<String>,
+    /// The details of a `422`, e.g. `Autolink.key_prefix already_exists`
+    pub errors: Vec<String>,
+    /// From the `x-ratelimit-remaining` header
+    pub rate_limit_remaining: Option<u32>,
+    /// From the `retry-after` or `x-ratelimit-reset` header
+    pub retry_after: Option<Duration>,
+}
+
+impl GitHubError {
+    pub fn is_rate_limited(&self) -> bool {
+        self.status == 429
+            || (self.status == 403
+                && (self.rate_limit_remaining == Some(0)
+                    || self.retry_after.is_some()
+                    || self.message.to_lowercase().contains("rate limit")))
+    }
+
+    pub fn class(&self) -> ErrorClass {
+        match self.status {
+            _ if self.is_rate_limited() => ErrorClass::Retryable,
+            401 => ErrorClass::Auth,
+            403 => ErrorClass::PermissionDenied,
+            408 | 500.. => ErrorClass::Retryable,
+            _ => ErrorClass::Permanent,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self.status {
+            _ if self.is_rate_limited() => "RateLimited",
+            401 => "Unauthorized",
+            403 => "Forbidden",
+            404 => "NotFound",
+            409 => "Conflict",
+            422 => "ValidationFailed",
+            500.. => "GitHubUnavailable",
+            _ => "GitHubError",
+        }
+    }
+}
+
+impl Display for GitHubError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.status, self.message)?;
+        if !self.errors.is_empty() {
+            write!(f, " ({})", self.errors.join(", "))?;
+        }
+        if let Some(documentation_url) = &self.documentation_url {
+            write!(f, ", see {}", documentation_url)?;
+        }
+        Ok(())
+    }
 }
 
 pub fn init_registry() -> Result<Registry, ControllerError> {
     Ok(Registry::new())
 }
 
+/// Where the traces are exported to, configured by the standard `OTEL_*` environment variables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TracingExporter {
+    None,
+    OtlpGrpc,
+    OtlpHttp,
+}
+
+impl TracingExporter {
+    /// Traces are only exported if an OTLP endpoint is configured and the SDK is not disabled.
+    pub fn from_env() -> Result<Self, ControllerError> {
+        // the logging is not initialized yet
+        let disabled = std::env::var("OTEL_SDK_DISABLED").is_ok_and(|value| value == "true");
+        let exporter = std::env::var("OTEL_TRACES_EXPORTER").unwrap_or("otlp".to_string());
+        let endpoint_configured = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_ok()
+            || std::env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_ok();
+        if disabled || exporter == "none" || !endpoint_configured {
+            return Ok(Self::None);
+        }
+        if exporter != "otlp" {
+            return Err(ControllerError::ConfigurationError(format!(
+                "unsupported traces exporter: {}",
+                exporter
+            )));
+        }
+        let protocol = std::env::var("OTEL_EXPORTER_OTLP_TRACES_PROTOCOL")
+            .or_else(|_| std::env::var("OTEL_EXPORTER_OTLP_PROTOCOL"))
+            .unwrap_or("grpc".to_string());
+        match protocol.as_str() {
+            "grpc" => Ok(Self::OtlpGrpc),
+            "http/protobuf" => Ok(Self::OtlpHttp),
+            _ => Err(ControllerError::ConfigurationError(format!(
+                "unsupported OTLP protocol: {}",
+                protocol
+            ))),
+        }
+    }
+}
+
+/// Flushes the pending spans when dropped, keep it alive until the process exits.
+#[must_use]
+pub struct TracingGuard {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("failed to shut down tracer provider: {:?}", e);
+            }
+        }
+    }
+}
+
 // see also: https://broch.tech/posts/rust-tracing-opentelemetry/
 // see also: https://github.com/tekul/rust-tracing-otlp/
-pub fn init_tracing() -> Result<(), ControllerError> {
+pub fn init_tracing(service_name: &'static str) -> Result<TracingGuard, ControllerError> {
     let logging_format = std::env::var("APP_LOGGING_FORMAT")
         .unwrap_or("plain".to_string())
         .to_lowercase();
@@ -77,18 +271,13 @@ pub fn init_tracing() -> Result<(), ControllerError> {
         (None, Some(tracing_subscriber::fmt::layer()))
     };
 
-    let otlp_exporter = opentelemetry_otlp::SpanExporter::builder()
-        .with_tonic()
-        .build()
-        .expect("Couldn't create OTLP exporter");
-
-    let provider = SdkTracerProvider::builder()
-        .with_simple_exporter(otlp_exporter)
-        .build();
-
-    let tracer = provider.tracer("readme_example");
-
-    let telemetry = tracing_opentelemetry::layer().with_tracer(tracer);
+    let provider = match TracingExporter::from_env()? {
+        TracingExporter::None => None,
+        exporter => Some(init_tracer_provider(service_name, exporter)?),
+    };
+    let telemetry = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(service_name)));
 
     tracing_subscriber::registry()
         .with(EnvFilter::from_default_env())
@@ -97,5 +286,36 @@ pub fn init_tracing() -> Result<(), ControllerError> {
         .with(telemetry)
         .init();
 
-    Ok(())
+    Ok(TracingGuard { provider })
+}
+
+fn init_tracer_provider(
+    service_name: &'static str,
+    exporter: TracingExporter,
+) -> Result<SdkTracerProvider, ControllerError> {
+    // the endpoint, headers and timeout are read from the OTEL_EXPORTER_OTLP_* variables
+    let exporter = match exporter {
+        TracingExporter::OtlpHttp => opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_protocol(Protocol::HttpBinary)
+            .build(),
+        _ => opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .build(),
+    }
+    .map_err(|e| ControllerError::ConfigurationError(e.to_string()))?;
+
+    // OTEL_SERVICE_NAME and OTEL_RESOURCE_ATTRIBUTES take precedence
+    let mut resource = Resource::builder()
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")));
+    if std::env::var("OTEL_SERVICE_NAME").is_err() {
+        resource = resource.with_service_name(service_name);
+    }
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+    global::set_tracer_provider(provider.clone());
+    Ok(provider)
 }
@@ -1,142 +1,316 @@
-use std::net::SocketAddr;
-
-use axum::extract::State;
-use axum::http::header;
-use axum::response::IntoResponse;
-use axum::routing::get;
-use axum::Router;
-use futures::TryFutureExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum_server::Handle;
+use futures::channel::oneshot;
+use futures::{future, FutureExt};
 use kube::api::ListParams;
 use kube::{Api, Client};
-use prometheus::{Encoder, Registry, TextEncoder};
 use tokio::signal::unix::{signal, SignalKind};
-use tokio::task::JoinSet;
+use tokio::task::{JoinHandle, JoinSet};
 use tracing::event;
 
-use github_operator::{init_registry, init_tracing, ControllerError};
+use github_operator::{
+    adapter, config, domain, extensions, init_registry, init_tracing, metrics, ControllerError,
+    ErrorClass,
+};
 
 use crate::adapter::http_github_service::HttpGithubService;
-use crate::controller::autolink_reference_controller::{self, AutolinkReferenceControllerContext};
-use crate::controller::permission_controller::{self, PermissionControllerContext};
-use crate::controller::repository_controller::{self, RepositoryControllerContext};
+use crate::adapter::kubernetes_event_publisher::KubernetesEventPublisher;
+use crate::controller::autolink_reference_controller::AutolinkReferenceControllerContext;
+use crate::controller::managed_resource::{
+    self, ControllerContext, ManagedResource, ShutdownSignal,
+};
+use crate::controller::ownership::RepositoryOwnership;
+use crate::controller::permission_controller::PermissionControllerContext;
+use crate::controller::reporter;
+use crate::controller::repository_controller::RepositoryControllerContext;
+use crate::controller::requeue::{Requeue, RequeueConfig};
+use crate::controller::storage_version;
+use crate::controller::tenancy::TenantPolicyResolver;
 use crate::domain::archive_repository_use_case::ArchiveRepositoryUseCase;
 use crate::domain::delete_autolink_reference_use_case::DeleteAutolinkReferenceUseCase;
 use crate::domain::delete_permissions_use_case::DeletePermissionUseCase;
 use crate::domain::model::autolink_reference::AutolinkReference;
 use crate::domain::model::permission::RepositoryPermission;
 use crate::domain::model::repository::Repository;
+use crate::domain::model::tenant_policy::GitHubTenantPolicy;
 use crate::domain::reconcile_autolink_reference_use_case::ReconcileAutolinkReferenceUseCase;
 use crate::domain::reconcile_permissions_use_case::ReconcilePermissionUseCase;
 use crate::domain::reconcile_repository_use_case::ReconcileRepositoryUseCase;
+use crate::domain::service::github_service::GitHubService;
+use crate::extensions::DurationExtension;
+use crate::leader_election::{LeaderElectionConfig, LeaderElector};
+use crate::metrics::Metrics;
+use crate::server::{HttpServerConfig, Readiness};
+use crate::webhook::{AdmissionValidator, WebhookConfig};
 
-mod adapter;
 mod controller;
-mod domain;
-mod extensions;
+mod leader_election;
+mod server;
+mod webhook;
 
 #[tokio::main]
 async fn main() -> Result<(), ControllerError> {
-    init_tracing()?;
+    let _tracing = init_tracing("github-operator")?;
     let registry = init_registry()?;
+    let metrics = Metrics::register(&registry)?;
+    let http_server_config = HttpServerConfig::from_env()?;
+    let leader_election_config = LeaderElectionConfig::from_env()?;
+    let webhook_config = WebhookConfig::from_env()?;
+    let repository_requeue_config = RequeueConfig::from_env(Repository::NAME)?;
+    let autolink_reference_requeue_config = RequeueConfig::from_env(AutolinkReference::NAME)?;
+    let permission_requeue_config = RequeueConfig::from_env(RepositoryPermission::NAME)?;
     event!(tracing::Level::INFO, "starting controllers...");
 
+    let signal = shutdown_signal();
+    let readiness = Arc::new(Readiness::default());
+
     let client = Client::try_default()
         .await
         .map_err(ControllerError::KubeError)?;
 
-    // the kubernetes API for our CRD
-    let repository_api = Api::<Repository>::all(client.clone());
-    let autolink_reference_api = Api::<AutolinkReference>::all(client.clone());
-    let permission_api = Api::<RepositoryPermission>::all(client.clone());
+    // start server to expose metrics, probes and the webhooks
+    let http_handle = Handle::new();
+    let http_server = tokio::spawn(server::serve(
+        http_server_config,
+        registry.clone(),
+        readiness.clone(),
+        webhook_config.map(|config| (config, AdmissionValidator::new(client.clone()))),
+        http_handle.clone(),
+    ));
 
-    // check if the CRD is installed, or else throw an error
-    repository_api
-        .list(&ListParams::default().limit(1))
-        .await
-        .map_err(ControllerError::CrdNotFound)?;
-    autolink_reference_api
-        .list(&ListParams::default().limit(1))
-        .await
-        .map_err(ControllerError::CrdNotFound)?;
-    permission_api
-        .list(&ListParams::default().limit(1))
-        .await
-        .map_err(ControllerError::CrdNotFound)?;
+    // wait until the CRDs are installed
+    loop {
+        match crds_installed(&client).await {
+            Ok(()) => break,
+            Err(e) => log::warn!("{}", e),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(10)) => {}
+            _ = signal.clone() => return stop_http_server(http_handle, http_server).await,
+        }
+    }
+    readiness.set_crds_found(true);
 
     // dependencies
-    let github_service = HttpGithubService::from_env();
+    let github_service = HttpGithubService::from_env().with_metrics(metrics.github.clone());
 
-    let mut tasks = JoinSet::new();
+    // verify the credentials periodically, so the readiness reflects revoked tokens
+    let credentials_check = {
+        let github_service = github_service.clone();
+        let readiness = readiness.clone();
+        tokio::spawn(async move {
+            loop {
+                match github_service.verify_credentials().await {
+                    Ok(()) => readiness.set_github_credentials_valid(true),
+                    Err(e) => {
+                        log::warn!("GitHub credentials are not valid: {}", e);
+                        readiness.set_github_credentials_valid(false);
+                    }
+                }
+                tokio::time::sleep(Duration::from_minutes(5)).await;
+            }
+        })
+    };
+
+    // only the leader runs the controllers, the others keep serving the http server
+    let leader_elector = leader_election_config
+        .enabled
+        .then(|| Arc::new(LeaderElector::new(client.clone(), leader_election_config)));
+    let (leadership_lost, leadership_handle): (ShutdownSignal, _) = match &leader_elector {
+        Some(leader_elector) => {
+            readiness.set_standby(true);
+            tokio::select! {
+                _ = leader_elector.acquire() => {}
+                _ = signal.clone() => {
+                    credentials_check.abort();
+                    return stop_http_server(http_handle, http_server).await;
+                }
+            }
+            readiness.set_standby(false);
+            let (leadership_lost_tx, leadership_lost_rx) = oneshot::channel::<()>();
+            let leader_elector = leader_elector.clone();
+            let leadership_handle = tokio::spawn(async move {
+                leader_elector.hold().await;
+                let _ = leadership_lost_tx.send(());
+            });
+            (
+                leadership_lost_rx.map(|_| ()).boxed().shared(),
+                Some(leadership_handle),
+            )
+        }
+        None => (future::pending().boxed().shared(), None),
+    };
+    let shutdown: ShutdownSignal = future::select(signal, leadership_lost)
+        .map(|_| ())
+        .boxed()
+        .shared();
 
-    // start server to expose metrics
-    let app = Router::new()
-        .route("/prometheus", get(metrics_handler))
-        .with_state(HttpState {
-            registry: registry.clone(),
-        });
-    let addr = SocketAddr::from(([127, 0, 0, 1], 9100));
-    let server = axum_server::bind(addr)
-        .serve(app.into_make_service())
-        .map_err(ControllerError::IoError);
-    let http_handle = tokio::spawn(server);
+    // the objects of older versions are converted on every read until they are rewritten
+    if let Err(e) = migrate_stored_versions(&client).await {
+        log::warn!("couldn't migrate the stored versions: {}", e);
+    }
+
+    let mut tasks = JoinSet::new();
 
     // add repository controller
-    tasks.spawn(repository_controller::run(RepositoryControllerContext {
-        client: client.clone(),
-        repository_api,
-        reconcile_use_case: ReconcileRepositoryUseCase::new(Box::new(github_service.clone())),
-        archive_use_case: ArchiveRepositoryUseCase::new(Box::new(github_service.clone())),
-    }));
+    let events = KubernetesEventPublisher::new(client.clone(), &reporter(Repository::NAME));
+    tasks.spawn(managed_resource::run(
+        ControllerContext {
+            client: client.clone(),
+            api: Api::<Repository>::all(client.clone()),
+            context: RepositoryControllerContext {
+                reconcile_use_case: ReconcileRepositoryUseCase::new(
+                    Box::new(github_service.clone()),
+                    Box::new(events.clone()),
+                ),
+                archive_use_case: ArchiveRepositoryUseCase::new(
+                    Box::new(github_service.clone()),
+                    Box::new(events),
+                ),
+                ownership: RepositoryOwnership::new(client.clone()),
+            },
+            metrics: metrics.reconcile.clone(),
+            tenancy: TenantPolicyResolver::new(client.clone()),
+            requeue: Requeue::new(repository_requeue_config),
+        },
+        shutdown.clone(),
+        readiness.clone(),
+    ));
 
     // add autolink reference controller
-    tasks.spawn(autolink_reference_controller::run(
-        AutolinkReferenceControllerContext {
+    let events = KubernetesEventPublisher::new(client.clone(), &reporter(AutolinkReference::NAME));
+    tasks.spawn(managed_resource::run(
+        ControllerContext {
             client: client.clone(),
-            autolink_reference_api,
-            reconcile_use_case: ReconcileAutolinkReferenceUseCase::new(Box::new(
-                github_service.clone(),
-            )),
-            delete_use_case: DeleteAutolinkReferenceUseCase::new(Box::new(github_service.clone())),
+            api: Api::<AutolinkReference>::all(client.clone()),
+            context: AutolinkReferenceControllerContext {
+                reconcile_use_case: ReconcileAutolinkReferenceUseCase::new(
+                    Box::new(github_service.clone()),
+                    Box::new(events.clone()),
+                ),
+                delete_use_case: DeleteAutolinkReferenceUseCase::new(
+                    Box::new(github_service.clone()),
+                    Box::new(events),
+                ),
+                ownership: RepositoryOwnership::new(client.clone()),
+            },
+            metrics: metrics.reconcile.clone(),
+            tenancy: TenantPolicyResolver::new(client.clone()),
+            requeue: Requeue::new(autolink_reference_requeue_config),
         },
+        shutdown.clone(),
+        readiness.clone(),
     ));
 
     // add permission controller
-    tasks.spawn(permission_controller::run(PermissionControllerContext {
-        client: client.clone(),
-        permission_api,
-        reconcile_use_case: ReconcilePermissionUseCase::new(Box::new(github_service.clone())),
-        delete_use_case: DeletePermissionUseCase::new(Box::new(github_service.clone())),
-    }));
+    let events =
+        KubernetesEventPublisher::new(client.clone(), &reporter(RepositoryPermission::NAME));
+    tasks.spawn(managed_resource::run(
+        ControllerContext {
+            client: client.clone(),
+            api: Api::<RepositoryPermission>::all(client.clone()),
+            context: PermissionControllerContext {
+                reconcile_use_case: ReconcilePermissionUseCase::new(
+                    Box::new(github_service.clone()),
+                    Box::new(events.clone()),
+                ),
+                delete_use_case: DeletePermissionUseCase::new(
+                    Box::new(github_service.clone()),
+                    Box::new(events),
+                ),
+                ownership: RepositoryOwnership::new(client.clone()),
+            },
+            metrics: metrics.reconcile.clone(),
+            tenancy: TenantPolicyResolver::new(client.clone()),
+            requeue: Requeue::new(permission_requeue_config),
+        },
+        shutdown.clone(),
+        readiness.clone(),
+    ));
 
+    // the controllers finish their running reconciliations before they stop
     while let Some(res) = tasks.join_next().await {
         if let Err(e) = res {
             event!(tracing::Level::ERROR, "error: {:?}", e);
         }
     }
+    credentials_check.abort();
 
-    // Listen for SIGINT signal for graceful shutdown
-    let mut stream = signal(SignalKind::interrupt()).unwrap();
-    tokio::spawn(async move {
-        stream.recv().await;
-        http_handle.abort();
-    });
+    // the controllers stopped either because the leadership was lost or because of a signal
+    let leadership_lost = leadership_handle
+        .as_ref()
+        .is_some_and(|leadership_handle| leadership_handle.is_finished());
+    if let (Some(leader_elector), Some(leadership_handle)) = (leader_elector, leadership_handle) {
+        if !leadership_lost {
+            leadership_handle.abort();
+            if let Err(e) = leader_elector.release().await {
+                log::warn!("failed to release lease: {:?}", e);
+            }
+        }
+    }
 
+    stop_http_server(http_handle, http_server).await?;
+    if leadership_lost {
+        return Err(ControllerError::LeadershipLost);
+    }
     Ok(())
 }
 
-#[derive(Clone)]
-struct HttpState {
-    pub registry: Registry,
+/// Resolves on SIGINT or SIGTERM, the handlers are registered immediately.
+fn shutdown_signal() -> ShutdownSignal {
+    let mut interrupt = signal(SignalKind::interrupt()).expect("Couldn't listen for SIGINT");
+    let mut terminate = signal(SignalKind::terminate()).expect("Couldn't listen for SIGTERM");
+    async move {
+        tokio::select! {
+            _ = interrupt.recv() => log::info!("received SIGINT, shutting down"),
+            _ = terminate.recv() => log::info!("received SIGTERM, shutting down"),
+        }
+    }
+    .boxed()
+    .shared()
 }
 
-async fn metrics_handler(State(HttpState { registry }): State<HttpState>) -> impl IntoResponse {
-    let encoder = TextEncoder::new();
-    let metric_families = registry.gather();
-    let mut result = Vec::new();
-    encoder
-        .encode(&metric_families, &mut result)
-        .map_err(ControllerError::PrometheusError)
-        .expect("Couldn't encode metrics");
+/// Check if the CRDs are installed.
+async fn crds_installed(client: &Client) -> Result<(), ControllerError> {
+    let params = ListParams::default().limit(1);
+    Api::<Repository>::all(client.clone())
+        .list(&params)
+        .await
+        .map_err(ControllerError::CrdNotFound)?;
+    Api::<AutolinkReference>::all(client.clone())
+        .list(&params)
+        .await
+        .map_err(ControllerError::CrdNotFound)?;
+    Api::<RepositoryPermission>::all(client.clone())
+        .list(&params)
+        .await
+        .map_err(ControllerError::CrdNotFound)?;
+    Api::<GitHubTenantPolicy>::all(client.clone())
+        .list(&params)
+        .await
+        .map_err(ControllerError::CrdNotFound)?;
+    Ok(())
+}
+
+/// Rewrite the objects in the storage version of their kind.
+async fn migrate_stored_versions(client: &Client) -> Result<(), ControllerError> {
+    storage_version::migrate::<Repository>(client).await?;
+    storage_version::migrate::<AutolinkReference>(client).await?;
+    storage_version::migrate::<RepositoryPermission>(client).await
+}
 
-    ([(header::CONTENT_TYPE, "text/plain")], result)
+async fn stop_http_server(
+    handle: Handle,
+    server: JoinHandle<Result<(), ControllerError>>,
+) -> Result<(), ControllerError> {
+    handle.graceful_shutdown(Some(Duration::from_secs(10)));
+    match server.await {
+        Ok(result) => result,
+        Err(e) => {
+            log::error!("http server task failed: {:?}", e);
+            Ok(())
+        }
+    }
 }
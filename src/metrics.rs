@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use prometheus::{
+    exponential_buckets, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry,
+};
+
+use crate::ControllerError;
+
+const NAMESPACE: &str = "github_operator";
+
+/// All metrics exposed by the operator.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    pub reconcile: ReconcileMetrics,
+    pub github: GitHubApiMetrics,
+}
+
+impl Metrics {
+    pub fn register(registry: &Registry) -> Result<Self, ControllerError> {
+        Ok(Self {
+            reconcile: ReconcileMetrics::register(registry)?,
+            github: GitHubApiMetrics::register(registry)?,
+        })
+    }
+}
+
+/// Metrics of the controllers, labeled by the name of the controller.
+#[derive(Clone, Debug)]
+pub struct ReconcileMetrics {
+    reconciliations: IntCounterVec,
+    duration: HistogramVec,
+    drift_corrections: IntCounterVec,
+    finalizer_cleanups: IntCounterVec,
+    not_ready: IntGaugeVec,
+    /// The objects that are currently not ready, by controller
+    not_ready_objects: Arc<Mutex<HashMap<&'static str, HashSet<String>>>>,
+}
+
+impl ReconcileMetrics {
+    fn register(registry: &Registry) -> Result<Self, ControllerError> {
+        let metrics = Self {
+            reconciliations: IntCounterVec::new(
+                Opts::new(
+                    "reconciliations_total",
+                    "Number of reconciliations by result",
+                )
+                .namespace(NAMESPACE),
+                &["controller", "result"],
+            )
+            .map_err(ControllerError::PrometheusError)?,
+            duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "reconcile_duration_seconds",
+                    "Duration of a reconciliation in seconds",
+                )
+                .namespace(NAMESPACE)
+                .buckets(
+                    exponential_buckets(0.05, 2.0, 10).map_err(ControllerError::PrometheusError)?,
+                ),
+                &["controller"],
+            )
+            .map_err(ControllerError::PrometheusError)?,
+            drift_corrections: IntCounterVec::new(
+                Opts::new(
+                    "drift_corrections_total",
+                    "Number of GitHub resources that differed from the spec and were updated",
+                )
+                .namespace(NAMESPACE),
+                &["controller"],
+            )
+            .map_err(ControllerError::PrometheusError)?,
+            finalizer_cleanups: IntCounterVec::new(
+                Opts::new(
+                    "finalizer_cleanups_total",
+                    "Number of cleanups of deleted resources by result",
+                )
+                .namespace(NAMESPACE),
+                &["controller", "result"],
+            )
+            .map_err(ControllerError::PrometheusError)?,
+            not_ready: IntGaugeVec::new(
+                Opts::new(
+                    "resources_not_ready",
+                    "Number of managed resources that are not ready",
+                )
+                .namespace(NAMESPACE),
+                &["controller"],
+            )
+            .map_err(ControllerError::PrometheusError)?,
+            not_ready_objects: Arc::default(),
+        };
+        registry
+            .register(Box::new(metrics.reconciliations.clone()))
+            .map_err(ControllerError::PrometheusError)?;
+        registry
+            .register(Box::new(metrics.duration.clone()))
+            .map_err(ControllerError::PrometheusError)?;
+        registry
+            .register(Box::new(metrics.drift_corrections.clone()))
+            .map_err(ControllerError::PrometheusError)?;
+        registry
+            .register(Box::new(metrics.finalizer_cleanups.clone()))
+            .map_err(ControllerError::PrometheusError)?;
+        registry
+            .register(Box::new(metrics.not_ready.clone()))
+            .map_err(ControllerError::PrometheusError)?;
+        Ok(metrics)
+    }
+
+    pub fn reconciled(&self, controller: &str, success: bool, duration: Duration) {
+        let result = if success { "success" } else { "error" };
+        self.reconciliations
+            .with_label_values(&[controller, result])
+            .inc();
+        self.duration
+            .with_label_values(&[controller])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn drift_corrected(&self, controller: &str) {
+        self.drift_corrections
+            .with_label_values(&[controller])
+            .inc();
+    }
+
+    pub fn cleaned_up(&self, controller: &str, success: bool) {
+        let result = if success { "success" } else { "error" };
+        self.finalizer_cleanups
+            .with_label_values(&[controller, result])
+            .inc();
+    }
+
+    /// Track whether an object is ready, `None` once the object is deleted.
+    pub fn set_ready(&self, controller: &'static str, object: String, ready: Option<bool>) {
+        let mut not_ready_objects = self.not_ready_objects.lock().unwrap();
+        let objects = not_ready_objects.entry(controller).or_default();
+        match ready {
+            Some(false) => objects.insert(object),
+            _ => objects.remove(&object),
+        };
+        self.not_ready
+            .with_label_values(&[controller])
+            .set(objects.len() as i64);
+    }
+}
+
+/// Metrics of the calls to the GitHub API, labeled by the endpoint template, e.g. `/repos/{owner}/{repo}`.
+#[derive(Clone, Debug)]
+pub struct GitHubApiMetrics {
+    requests: IntCounterVec,
+    duration: HistogramVec,
+    rate_limit_remaining: IntGauge,
+}
+
+impl GitHubApiMetrics {
+    fn register(registry: &Registry) -> Result<Self, ControllerError> {
+        let metrics = Self {
+            requests: IntCounterVec::new(
+                Opts::new(
+                    "github_requests_total",
+                    "Number of requests to the GitHub API by status code",
+                )
+                .namespace(NAMESPACE),
+                &["method", "endpoint", "status"],
+            )
+            .map_err(ControllerError::PrometheusError)?,
+            duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "github_request_duration_seconds",
+                    "Duration of requests to the GitHub API in seconds",
+                )
+                .namespace(NAMESPACE)
+                .buckets(
+                    exponential_buckets(0.025, 2.0, 10)
+                        .map_err(ControllerError::PrometheusError)?,
+                ),
+                &["method", "endpoint"],
+            )
+            .map_err(ControllerError::PrometheusError)?,
+            rate_limit_remaining: IntGauge::with_opts(
+                Opts::new(
+                    "github_rate_limit_remaining",
+                    "Remaining requests of the GitHub API rate limit",
+                )
+                .namespace(NAMESPACE),
+            )
+            .map_err(ControllerError::PrometheusError)?,
+        };
+        registry
+            .register(Box::new(metrics.requests.clone()))
+            .map_err(ControllerError::PrometheusError)?;
+        registry
+            .register(Box::new(metrics.duration.clone()))
+            .map_err(ControllerError::PrometheusError)?;
+        registry
+            .register(Box::new(metrics.rate_limit_remaining.clone()))
+            .map_err(ControllerError::PrometheusError)?;
+        Ok(metrics)
+    }
+
+    /// Record a request, the status is `error` if no response was received.
+    pub fn requested(
+        &self,
+        method: &str,
+        endpoint: &str,
+        status: Option<u16>,
+        duration: Duration,
+        rate_limit_remaining: Option<i64>,
+    ) {
+        let status = status.map_or("error".to_string(), |status| status.to_string());
+        self.requests
+            .with_label_values(&[method, endpoint, status.as_str()])
+            .inc();
+        self.duration
+            .with_label_values(&[method, endpoint])
+            .observe(duration.as_secs_f64());
+        if let Some(remaining) = rate_limit_remaining {
+            self.rate_limit_remaining.set(remaining);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use prometheus::proto::MetricType;
+
+    use super::*;
+
+    /// The label sets of a metric family with their counter or gauge value.
+    fn samples(registry: &Registry, name: &str) -> Vec<(BTreeMap<String, String>, f64)> {
+        registry
+            .gather()
+            .into_iter()
+            .filter(|family| family.name() == format!("{}_{}", NAMESPACE, name))
+            .flat_map(|family| {
+                let metric_type = family.get_field_type();
+                family
+                    .get_metric()
+                    .iter()
+                    .map(move |metric| {
+                        let labels = metric
+                            .get_label()
+                            .iter()
+                            .map(|label| (label.name().to_string(), label.value().to_string()))
+                            .collect();
+                        let value = match metric_type {
+                            MetricType::COUNTER => metric.get_counter().value(),
+                            MetricType::GAUGE => metric.get_gauge().value(),
+                            _ => metric.get_histogram().get_sample_count() as f64,
+                        };
+                        (labels, value)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn labels(labels: &[(&str, &str)]) -> BTreeMap<String, String> {
+        labels
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn reconciliations_are_labeled_by_controller_and_result() {
+        let registry = Registry::new();
+        let metrics = Metrics::register(&registry).unwrap().reconcile;
+
+        metrics.reconciled("repository", true, Duration::from_millis(10));
+        metrics.reconciled("repository", false, Duration::from_millis(10));
+        metrics.cleaned_up("permission", true);
+        metrics.drift_corrected("repository");
+
+        let mut reconciliations = samples(&registry, "reconciliations_total");
+        reconciliations.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            reconciliations,
+            vec![
+                (
+                    labels(&[("controller", "repository"), ("result", "error")]),
+                    1.0
+                ),
+                (
+                    labels(&[("controller", "repository"), ("result", "success")]),
+                    1.0
+                ),
+            ]
+        );
+        assert_eq!(
+            samples(&registry, "reconcile_duration_seconds"),
+            vec![(labels(&[("controller", "repository")]), 2.0)]
+        );
+        assert_eq!(
+            samples(&registry, "finalizer_cleanups_total"),
+            vec![(
+                labels(&[("controller", "permission"), ("result", "success")]),
+                1.0
+            )]
+        );
+        assert_eq!(
+            samples(&registry, "drift_corrections_total"),
+            vec![(labels(&[("controller", "repository")]), 1.0)]
+        );
+    }
+
+    #[test]
+    fn counts_the_objects_that_are_not_ready() {
+        let registry = Registry::new();
+        let metrics = Metrics::register(&registry).unwrap().reconcile;
+
+        metrics.set_ready("repository", "ns/a".into(), Some(false));
+        metrics.set_ready("repository", "ns/b".into(), Some(false));
+        metrics.set_ready("repository", "ns/b".into(), Some(false));
+        assert_eq!(
+            samples(&registry, "resources_not_ready"),
+            vec![(labels(&[("controller", "repository")]), 2.0)]
+        );
+
+        metrics.set_ready("repository", "ns/a".into(), Some(true));
+        metrics.set_ready("repository", "ns/b".into(), None);
+        assert_eq!(
+            samples(&registry, "resources_not_ready"),
+            vec![(labels(&[("controller", "repository")]), 0.0)]
+        );
+    }
+
+    #[test]
+    fn github_requests_are_labeled_by_method_endpoint_and_status() {
+        let registry = Registry::new();
+        let metrics = Metrics::register(&registry).unwrap().github;
+
+        metrics.requested(
+            "GET",
+            "/repos/{owner}/{repo}",
+            Some(404),
+            Duration::from_millis(10),
+            Some(42),
+        );
+        metrics.requested(
+            "PATCH",
+            "/repos/{owner}/{repo}",
+            None,
+            Duration::from_millis(10),
+            None,
+        );
+
+        let mut requests = samples(&registry, "github_requests_total");
+        requests.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            requests,
+            vec![
+                (
+                    labels(&[
+                        ("endpoint", "/repos/{owner}/{repo}"),
+                        ("method", "GET"),
+                        ("status", "404")
+                    ]),
+                    1.0
+                ),
+                (
+                    labels(&[
+                        ("endpoint", "/repos/{owner}/{repo}"),
+                        ("method", "PATCH"),
+                        ("status", "error")
+                    ]),
+                    1.0
+                ),
+            ]
+        );
+        assert_eq!(
+            samples(&registry, "github_rate_limit_remaining"),
+            vec![(BTreeMap::new(), 42.0)]
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use kube::core::admission::{AdmissionResponse, AdmissionReview};
+use kube::core::conversion::ConversionReview;
+use kube::core::DynamicObject;
+use prometheus::{Encoder, Registry, TextEncoder};
+use serde::Serialize;
+
+use crate::config::env_or;
+use crate::extensions::DurationExtension;
+use crate::webhook::{convert_review, AdmissionValidator, WebhookConfig};
+use crate::ControllerError;
+
+#[derive(Clone, Debug)]
+pub struct HttpServerConfig {
+    pub address: SocketAddr,
+}
+
+impl HttpServerConfig {
+    pub fn from_env() -> Result<Self, ControllerError> {
+        let host = env_or("APP_HTTP_HOST", IpAddr::V4(Ipv4Addr::UNSPECIFIED))?;
+        let port = env_or("APP_HTTP_PORT", 9100)?;
+        Ok(Self {
+            address: SocketAddr::new(host, port),
+        })
+    }
+}
+
+/// The state the readiness probe is based on.
+#[derive(Default, Debug)]
+pub struct Readiness {
+    crds_found: AtomicBool,
+    github_credentials_valid: AtomicBool,
+    /// Waiting for the leadership, the controllers are not expected to run
+    standby: AtomicBool,
+    /// Whether the watch stream of each controller received its initial list
+    controllers: Mutex<BTreeMap<&'static str, bool>>,
+    /// Whether the webhooks are served over TLS, `None` if they are disabled
+    webhook_serving: Mutex<Option<bool>>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub crds_found: bool,
+    pub github_credentials_valid: bool,
+    pub standby: bool,
+    pub controllers: BTreeMap<&'static str, bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webhook_serving: Option<bool>,
+}
+
+impl Readiness {
+    pub fn set_crds_found(&self, found: bool) {
+        self.crds_found.store(found, Ordering::Relaxed);
+    }
+
+    pub fn set_github_credentials_valid(&self, valid: bool) {
+        self.github_credentials_valid
+            .store(valid, Ordering::Relaxed);
+    }
+
+    pub fn set_standby(&self, standby: bool) {
+        self.standby.store(standby, Ordering::Relaxed);
+    }
+
+    pub fn controller_registered(&self, name: &'static str) {
+        self.controllers.lock().unwrap().insert(name, false);
+    }
+
+    pub fn controller_started(&self, name: &'static str) {
+        self.controllers.lock().unwrap().insert(name, true);
+    }
+
+    pub fn set_webhook_serving(&self, serving: bool) {
+        *self.webhook_serving.lock().unwrap() = Some(serving);
+    }
+
+    pub fn report(&self) -> ReadinessReport {
+        let crds_found = self.crds_found.load(Ordering::Relaxed);
+        let github_credentials_valid = self.github_credentials_valid.load(Ordering::Relaxed);
+        let standby = self.standby.load(Ordering::Relaxed);
+        let controllers = self.controllers.lock().unwrap().clone();
+        let controllers_started =
+            !controllers.is_empty() && controllers.values().all(|started| *started);
+        let webhook_serving = *self.webhook_serving.lock().unwrap();
+        ReadinessReport {
+            ready: crds_found
+                && github_credentials_valid
+                && (standby || controllers_started)
+                && webhook_serving.unwrap_or(true),
+            crds_found,
+            github_credentials_valid,
+            standby,
+            controllers,
+            webhook_serving,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct HttpState {
+    pub registry: Registry,
+    pub readiness: Arc<Readiness>,
+}
+
+/// How often missing webhook certificates are loaded again, e.g. until cert-manager issued them.
+const CERTIFICATES_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Serve metrics and probes, and the admission and conversion webhooks over TLS if configured,
+/// until the handle is shut down. The probes are served even if the webhooks are not, the
+/// readiness reports them instead.
+pub async fn serve(
+    config: HttpServerConfig,
+    registry: Registry,
+    readiness: Arc<Readiness>,
+    webhook: Option<(WebhookConfig, AdmissionValidator)>,
+    handle: Handle,
+) -> Result<(), ControllerError> {
+    let app = Router::new()
+        .route("/prometheus", get(metrics_handler))
+        .route("/healthz", get(liveness_handler))
+        .route("/readyz", get(readiness_handler))
+        .with_state(HttpState {
+            registry,
+            readiness: readiness.clone(),
+        });
+    log::info!("listening on {}", config.address);
+    let http = axum_server::bind(config.address)
+        .handle(handle.clone())
+        .serve(app.into_make_service());
+
+    let Some((webhook_config, validator)) = webhook else {
+        return http.await.map_err(ControllerError::IoError);
+    };
+    readiness.set_webhook_serving(false);
+    tokio::pin!(http);
+    let tls = tokio::select! {
+        result = &mut http => return result.map_err(ControllerError::IoError),
+        tls = load_certificates(&webhook_config) => tls,
+    };
+    // the certificates are rotated, e.g. by cert-manager
+    tokio::spawn(reload_certificates(tls.clone(), webhook_config.clone()));
+    let webhook_app = Router::new()
+        .route("/validate", post(validate_handler))
+        .route("/convert", post(convert_handler))
+        .with_state(validator);
+    log::info!("serving webhooks on {}", webhook_config.address);
+    readiness.set_webhook_serving(true);
+    let https = async {
+        if let Err(e) = axum_server::bind_rustls(webhook_config.address, tls)
+            .handle(handle)
+            .serve(webhook_app.into_make_service())
+            .await
+        {
+            log::error!("couldn't serve the webhooks: {}", e);
+            readiness.set_webhook_serving(false);
+        }
+    };
+    let (result, ()) = tokio::join!(http, https);
+    result.map_err(ControllerError::IoError)
+}
+
+async fn load_certificates(config: &WebhookConfig) -> RustlsConfig {
+    loop {
+        match RustlsConfig::from_pem_file(&config.tls_cert, &config.tls_key).await {
+            Ok(tls) => return tls,
+            Err(e) => {
+                log::warn!(
+                    "couldn't load the webhook certificates {}, retrying in {:?}: {}",
+                    config.tls_cert.display(),
+                    CERTIFICATES_RETRY_INTERVAL,
+                    e
+                );
+                tokio::time::sleep(CERTIFICATES_RETRY_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn reload_certificates(tls: RustlsConfig, config: WebhookConfig) {
+    loop {
+        tokio::time::sleep(Duration::from_minutes(10)).await;
+        if let Err(e) = tls
+            .reload_from_pem_file(&config.tls_cert, &config.tls_key)
+            .await
+        {
+            log::warn!("couldn't reload the webhook certificates: {}", e);
+        }
+    }
+}
+
+async fn metrics_handler(State(HttpState { registry, .. }): State<HttpState>) -> impl IntoResponse {
+    let encoder = TextEncoder::new();
+    let metric_families = registry.gather();
+    let mut result = Vec::new();
+    encoder
+        .encode(&metric_families, &mut result)
+        .map_err(ControllerError::PrometheusError)
+        .expect("Couldn't encode metrics");
+
+    ([(header::CONTENT_TYPE, "text/plain")], result)
+}
+
+async fn liveness_handler() -> impl IntoResponse {
+    (StatusCode::OK, "ok")
+}
+
+async fn readiness_handler(
+    State(HttpState { readiness, .. }): State<HttpState>,
+) -> impl IntoResponse {
+    let report = readiness.report();
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+async fn validate_handler(
+    State(validator): State<AdmissionValidator>,
+    Json(review): Json<AdmissionReview<DynamicObject>>,
+) -> Json<AdmissionReview<DynamicObject>> {
+    let response = match review.try_into() {
+        Ok(request) => validator.review(&request).await,
+        Err(e) => AdmissionResponse::invalid(e.to_string()),
+    };
+    Json(response.into_review())
+}
+
+async fn convert_handler(Json(review): Json<ConversionReview>) -> Json<ConversionReview> {
+    Json(convert_review(review))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ready_leader() -> Readiness {
+        let readiness = Readiness::default();
+        readiness.set_crds_found(true);
+        readiness.set_github_credentials_valid(true);
+        readiness.controller_registered("repository");
+        readiness.controller_started("repository");
+        readiness
+    }
+
+    #[test]
+    fn not_ready_initially() {
+        let report = Readiness::default().report();
+
+        assert!(!report.ready);
+        assert!(report.controllers.is_empty());
+    }
+
+    #[test]
+    fn ready_when_all_controllers_started() {
+        let readiness = ready_leader();
+        assert!(readiness.report().ready);
+
+        readiness.controller_registered("permission");
+        assert!(!readiness.report().ready);
+
+        readiness.controller_started("permission");
+        assert!(readiness.report().ready);
+    }
+
+    #[test]
+    fn not_ready_without_crds_or_valid_credentials() {
+        let readiness = ready_leader();
+        readiness.set_github_credentials_valid(false);
+        assert!(!readiness.report().ready);
+
+        let readiness = ready_leader();
+        readiness.set_crds_found(false);
+        assert!(!readiness.report().ready);
+    }
+
+    #[test]
+    fn a_standby_replica_is_ready_without_controllers() {
+        let readiness = Readiness::default();
+        readiness.set_crds_found(true);
+        readiness.set_github_credentials_valid(true);
+        readiness.set_standby(true);
+        assert!(readiness.report().ready);
+
+        // after acquiring the leadership the controllers have to start first
+        readiness.set_standby(false);
+        readiness.controller_registered("repository");
+        assert!(!readiness.report().ready);
+    }
+
+    #[test]
+    fn not_ready_while_the_webhooks_are_not_served() {
+        let readiness = ready_leader();
+        readiness.set_webhook_serving(false);
+        assert!(!readiness.report().ready);
+
+        readiness.set_webhook_serving(true);
+        assert!(readiness.report().ready);
+    }
+
+    #[tokio::test]
+    async fn serves_the_probes_without_webhook_certificates() {
+        let readiness = Arc::new(Readiness::default());
+        let handle = Handle::new();
+        let client =
+            kube::Client::try_from(kube::Config::new("http://127.0.0.1:1".parse().unwrap()))
+                .unwrap();
+        let webhook = WebhookConfig {
+            address: "127.0.0.1:0".parse().unwrap(),
+            tls_cert: "missing/tls.crt".into(),
+            tls_key: "missing/tls.key".into(),
+        };
+        let server = tokio::spawn(serve(
+            HttpServerConfig {
+                address: "127.0.0.1:0".parse().unwrap(),
+            },
+            Registry::new(),
+            readiness.clone(),
+            Some((webhook, AdmissionValidator::new(client))),
+            handle.clone(),
+        ));
+
+        let address = handle.listening().await.unwrap();
+        let status = tokio::task::spawn_blocking(move || {
+            ureq::get(&format!("http://{}/healthz", address))
+                .call()
+                .unwrap()
+                .status()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(status, 200);
+        assert_eq!(readiness.report().webhook_serving, Some(false));
+        handle.shutdown();
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,301 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+use kube::api::ListParams;
+use kube::core::admission::{AdmissionRequest, AdmissionResponse, Operation};
+use kube::core::conversion::{ConversionRequest, ConversionResponse, ConversionReview};
+use kube::core::{DynamicObject, Status};
+use kube::{Api, Client, ResourceExt};
+use serde::de::DeserializeOwned;
+
+use crate::config::env_or;
+use crate::controller::tenancy::TenantPolicyResolver;
+use crate::domain::model::autolink_reference::AutolinkReference;
+use crate::domain::model::conversion::{convert, V1BETA1};
+use crate::domain::model::permission::RepositoryPermission;
+use crate::domain::model::repository::Repository;
+use crate::ControllerError;
+
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    pub address: SocketAddr,
+    pub tls_cert: PathBuf,
+    pub tls_key: PathBuf,
+}
+
+impl WebhookConfig {
+    /// The CRDs convert their versions with the webhook, it can only be disabled with
+    /// `APP_WEBHOOK_ENABLED=false` if a single version is installed, e.g. for local development.
+    pub fn from_env() -> Result<Option<Self>, ControllerError> {
+        if !env_or("APP_WEBHOOK_ENABLED", true)? {
+            return Ok(None);
+        }
+        let host = env_or("APP_HTTP_HOST", IpAddr::V4(Ipv4Addr::UNSPECIFIED))?;
+        let port = env_or("APP_WEBHOOK_PORT", 9443)?;
+        let certs = PathBuf::from("/tmp/k8s-webhook-server/serving-certs");
+        Ok(Some(Self {
+            address: SocketAddr::new(host, port),
+            tls_cert: env_or("APP_WEBHOOK_TLS_CERT", certs.join("tls.crt"))?,
+            tls_key: env_or("APP_WEBHOOK_TLS_KEY", certs.join("tls.key"))?,
+        }))
+    }
+}
+
+/// Checks the rules that need to know the other objects in the cluster.
+#[derive(Clone)]
+pub struct AdmissionValidator {
+    client: Client,
+    tenancy: TenantPolicyResolver,
+}
+
+impl AdmissionValidator {
+    pub fn new(client: Client) -> Self {
+        Self {
+            tenancy: TenantPolicyResolver::new(client.clone()),
+            client,
+        }
+    }
+
+    /// Review the request, the response denies it with all violated rules.
+    pub async fn review(&self, request: &AdmissionRequest<DynamicObject>) -> AdmissionResponse {
+        let response = AdmissionResponse::from(request);
+        if !matches!(request.operation, Operation::Create | Operation::Update) || is_exempt(request)
+        {
+            return response;
+        }
+        match self.violations(request).await {
+            Ok(violations) if violations.is_empty() => response,
+            Ok(violations) => {
+                log::info!(
+                    "denied {} {}/{}: {}",
+                    request.kind.kind,
+                    request.namespace.as_deref().unwrap_or_default(),
+                    request.name,
+                    violations.join("; ")
+                );
+                response.deny(violations.join("; "))
+            }
+            Err(e) => {
+                log::error!("couldn't review {}: {}", request.name, e);
+                response.deny(format!("couldn't check the object: {}", e))
+            }
+        }
+    }
+
+    async fn violations(
+        &self,
+        request: &AdmissionRequest<DynamicObject>,
+    ) -> Result<Vec<String>, ControllerError> {
+        let Some(object) = &request.object else {
+            return Ok(Vec::new());
+        };
+        let namespace = object
+            .namespace()
+            .or_else(|| request.namespace.clone())
+            .ok_or_else(|| {
+                ControllerError::IllegalDocument("the object has no namespace".into())
+            })?;
+        let policy = self.tenancy.resolve(&namespace).await?;
+        let (full_name, tenancy) = match request.kind.kind.as_str() {
+            "Repository" => {
+                let spec = parse::<Repository>(object)?.spec;
+                // renaming a repository of another tenant would take it over
+                let tenancy = spec
+                    .full_names()
+                    .try_for_each(|full_name| policy.check_repository(full_name));
+                (spec.full_name, tenancy)
+            }
+            "AutolinkReference" => {
+                let spec = parse::<AutolinkReference>(object)?.spec;
+                let tenancy = policy.check_repository(&spec.full_name);
+                (spec.full_name, tenancy)
+            }
+            "RepositoryPermission" => {
+                let spec = parse::<RepositoryPermission>(object)?.spec;
+                let tenancy = policy.check_permission(
+                    &spec.full_name,
+                    &spec.full_team_name,
+                    &spec.permission,
+                );
+                (spec.full_name, tenancy)
+            }
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut violations = Vec::new();
+        if let Err(violation) = tenancy {
+            violations.push(violation);
+        }
+        let repositories = Api::<Repository>::all(self.client.clone())
+            .list(&ListParams::default())
+            .await
+            .map_err(ControllerError::KubeError)?;
+        let mut claims = repositories.into_iter().filter(|repository| {
+            repository.spec.manages(&full_name)
+                && !(repository.namespace().as_deref() == Some(namespace.as_str())
+                    && repository.name_any() == object.name_any())
+        });
+        match request.kind.kind.as_str() {
+            "Repository" => {
+                if let Some(repository) = claims.next() {
+                    violations.push(format!(
+                        "repository {} is already managed by Repository {}/{}",
+                        full_name,
+                        repository.namespace().unwrap_or_default(),
+                        repository.name_any()
+                    ));
+                }
+            }
+            _ => {
+                let owners = claims.collect::<Vec<_>>();
+                if !owners
+                    .iter()
+                    .any(|repository| repository.namespace().as_deref() == Some(namespace.as_str()))
+                {
+                    let managed_elsewhere = owners
+                        .first()
+                        .map(|repository| {
+                            format!(
+                                " (it is managed in namespace {})",
+                                repository.namespace().unwrap_or_default()
+                            )
+                        })
+                        .unwrap_or_default();
+                    violations.push(format!(
+                        "repository {} has no Repository in namespace {}{}",
+                        full_name, namespace, managed_elsewhere
+                    ));
+                }
+            }
+        }
+        Ok(violations)
+    }
+}
+
+/// Updates that don't change the spec, e.g. of the finalizers of a deleted object, are allowed
+/// even if they would violate the rules by now, otherwise the object could get stuck.
+fn is_exempt(request: &AdmissionRequest<DynamicObject>) -> bool {
+    let Some(object) = &request.object else {
+        return true;
+    };
+    if object.metadata.deletion_timestamp.is_some() {
+        return true;
+    }
+    match (&request.operation, &request.old_object) {
+        (Operation::Update, Some(old)) => old.data.get("spec") == object.data.get("spec"),
+        _ => false,
+    }
+}
+
+/// Convert the objects of the review to the desired version, the API server asks when an object
+/// is read or written in another version than it is stored in.
+pub fn convert_review(review: ConversionReview) -> ConversionReview {
+    let request = match ConversionRequest::from_review(review) {
+        Ok(request) => request,
+        Err(e) => {
+            return ConversionResponse::invalid(Status::failure(&e.to_string(), "BadRequest"))
+                .into_review()
+        }
+    };
+    let converted = request
+        .objects
+        .iter()
+        .map(|object| convert(object.clone(), &request.desired_api_version))
+        .collect::<Result<Vec<_>, _>>();
+    let response = ConversionResponse::for_request(request);
+    match converted {
+        Ok(objects) => response.success(objects),
+        Err(e) => {
+            log::warn!("conversion failed: {}", e);
+            response.failure(Status::failure(&e, "ConversionFailed"))
+        }
+    }
+    .into_review()
+}
+
+/// Read the object in the storage version, the request contains the version the client sent.
+fn parse<K: DeserializeOwned>(object: &DynamicObject) -> Result<K, ControllerError> {
+    let object = serde_json::to_value(object).map_err(ControllerError::SerializationError)?;
+    let object = convert(object, V1BETA1).map_err(ControllerError::IllegalDocument)?;
+    serde_json::from_value(object).map_err(ControllerError::SerializationError)
+}
+
+#[cfg(test)]
+mod tests {
+    use kube::core::admission::AdmissionReview;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn repository(full_name: &str, deleted: bool) -> Value {
+        let mut repository = json!({
+            "apiVersion": V1BETA1,
+            "kind": "Repository",
+            "metadata": { "name": "test", "namespace": "otto-ec" },
+            "spec": { "fullName": full_name },
+        });
+        if deleted {
+            repository["metadata"]["deletionTimestamp"] = json!("2024-01-01T00:00:00Z");
+        }
+        repository
+    }
+
+    fn request(
+        operation: &str,
+        object: Value,
+        old_object: Option<Value>,
+    ) -> AdmissionRequest<DynamicObject> {
+        let review: AdmissionReview<DynamicObject> = serde_json::from_value(json!({
+            "apiVersion": "admission.k8s.io/v1",
+            "kind": "AdmissionReview",
+            "request": {
+                "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+                "kind": { "group": "github.platform.benkeil.de", "version": "v1beta1", "kind": "Repository" },
+                "resource": { "group": "github.platform.benkeil.de", "version": "v1beta1", "resource": "repositories" },
+                "name": "test",
+                "namespace": "otto-ec",
+                "operation": operation,
+                "userInfo": {},
+                "object": object,
+                "oldObject": old_object,
+                "dryRun": false,
+            },
+        }))
+        .unwrap();
+        review.try_into().unwrap()
+    }
+
+    #[test]
+    fn a_changed_spec_is_reviewed() {
+        let create = request("CREATE", repository("otto-ec/repo", false), None);
+        assert!(!is_exempt(&create));
+
+        let update = request(
+            "UPDATE",
+            repository("otto-ec/renamed", false),
+            Some(repository("otto-ec/repo", false)),
+        );
+        assert!(!is_exempt(&update));
+    }
+
+    #[test]
+    fn an_update_of_a_deleted_object_is_allowed() {
+        let update = request(
+            "UPDATE",
+            repository("otto-ec/renamed", true),
+            Some(repository("otto-ec/repo", true)),
+        );
+
+        assert!(is_exempt(&update));
+    }
+
+    #[test]
+    fn an_update_without_a_changed_spec_is_allowed() {
+        let mut object = repository("otto-ec/repo", false);
+        object["metadata"]["labels"] = json!({ "team": "developers" });
+
+        let update = request("UPDATE", object, Some(repository("otto-ec/repo", false)));
+
+        assert!(is_exempt(&update));
+    }
+}